@@ -1,8 +1,12 @@
 use dotenvy::dotenv_iter;
 use embuild::espidf;
+use std::path::Path;
+use std::{env, fs};
 
 fn main() {
     load_dotenv_variables();
+    validate_data_labels();
+    embed_pem_files();
     espidf::sysenv::output();
 }
 
@@ -33,3 +37,57 @@ fn load_dotenv_variables() {
         }
     }
 }
+
+/// Fails the build on a malformed `DATA_LABELS`, rather than silently dropping labels a fleet
+/// dashboard groups by: every comma-separated entry must be `key=value` with a unique, non-empty
+/// key.
+fn validate_data_labels() {
+    println!("cargo:rerun-if-env-changed=DATA_LABELS");
+
+    let labels = dotenvy::var("DATA_LABELS").unwrap_or_default();
+    if labels.trim().is_empty() {
+        return;
+    }
+
+    let mut keys = Vec::new();
+    for entry in labels.split(',') {
+        let key = match entry.split_once('=') {
+            Some((key, _)) if !key.trim().is_empty() => key.trim(),
+            _ => panic!("DATA_LABELS: {:?} is not a key=value entry", entry),
+        };
+        if keys.contains(&key) {
+            panic!("DATA_LABELS: duplicate key {:?}", key);
+        }
+        keys.push(key);
+    }
+}
+
+/// Copies the PEM files configured in `.env` (mTLS client identity, pinned server certificate) into
+/// `OUT_DIR`, where `tls.rs` picks them up with `include_bytes!`.
+///
+/// A nul terminator is appended because mbedTLS parses PEM buffers as C strings. When a path is
+/// not configured an empty (nul-only) file is written, so the firmware still builds without them.
+fn embed_pem_files() {
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is set by Cargo");
+
+    for (path_var, file_name) in [
+        ("HTTP_MTLS_CLIENT_CERT_PATH", "client_certificate.pem"),
+        ("HTTP_MTLS_CLIENT_KEY_PATH", "client_private_key.pem"),
+        (
+            "HTTP_PINNED_SERVER_CERT_PATH",
+            "pinned_server_certificate.pem",
+        ),
+    ] {
+        let mut pem = match dotenvy::var(path_var) {
+            Ok(path) if !path.is_empty() => {
+                println!("cargo:rerun-if-changed={}", path);
+                fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+            }
+            _ => Vec::new(),
+        };
+        pem.push(0);
+
+        fs::write(Path::new(&out_dir).join(file_name), pem)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {}", file_name, e));
+    }
+}
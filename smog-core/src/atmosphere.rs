@@ -0,0 +1,65 @@
+/// Absolute humidity in g/m³ from temperature (°C) and relative humidity (%).
+///
+/// Uses the Magnus formula for saturation vapor pressure (hPa), then the ideal gas law for
+/// water vapor: `AH = 6.112 * e^(17.67 * T / (T + 243.5)) * RH * 2.1674 / (273.15 + T)`.
+/// E.g. 20°C / 50% ≈ 8.6 g/m³.
+pub fn absolute_humidity(temp_c: f32, rh_pct: f32) -> f32 {
+    let saturation_vapor_pressure_hpa = 6.112 * ((17.67 * temp_c) / (temp_c + 243.5)).exp();
+    saturation_vapor_pressure_hpa * rh_pct * 2.1674 / (273.15 + temp_c)
+}
+
+/// Station pressure reduced to sea level (QNH) with the ICAO standard atmosphere:
+/// `QNH = P * (1 - 0.0065 * h / 288.15)^-5.255`, i.e. the pressure the station would read at sea
+/// level under a 15 °C, 6.5 K/km standard lapse rate. E.g. 954.6 hPa at 500 m ≈ 1013.2 hPa.
+pub fn to_qnh(pressure_hpa: f32, altitude_m: f32) -> f32 {
+    pressure_hpa * (1.0 - 0.0065 * altitude_m / 288.15).powf(-5.255)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn absolute_humidity_matches_reference_values() {
+        // Saturation vapor density tables: 4.85 g/m³ at 0 °C, 23.05 g/m³ at 25 °C
+        assert_close(absolute_humidity(0.0, 100.0), 4.85, 0.05);
+        assert_close(absolute_humidity(25.0, 100.0), 23.05, 0.1);
+        assert_close(absolute_humidity(20.0, 50.0), 8.65, 0.05);
+        assert_close(absolute_humidity(-10.0, 80.0), 1.89, 0.05);
+    }
+
+    #[test]
+    fn absolute_humidity_scales_with_relative_humidity() {
+        assert_eq!(absolute_humidity(30.0, 0.0), 0.0);
+        assert_close(
+            absolute_humidity(15.0, 80.0),
+            2.0 * absolute_humidity(15.0, 40.0),
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn qnh_of_standard_atmosphere_pressures_is_standard_sea_level_pressure() {
+        // ICAO standard atmosphere: 954.6 hPa at 500 m, 898.76 hPa at 1000 m, 845.6 hPa at 1500 m
+        assert_close(to_qnh(954.6, 500.0), 1013.25, 0.1);
+        assert_close(to_qnh(898.76, 1000.0), 1013.25, 0.1);
+        assert_close(to_qnh(845.6, 1500.0), 1013.25, 0.1);
+    }
+
+    #[test]
+    fn qnh_at_sea_level_is_the_station_pressure() {
+        assert_eq!(to_qnh(1000.0, 0.0), 1000.0);
+    }
+
+    #[test]
+    fn qnh_below_sea_level_is_lower_than_the_station_pressure() {
+        assert!(to_qnh(1020.0, -100.0) < 1020.0);
+    }
+}
@@ -0,0 +1,161 @@
+/// The core fields of a sample, as compared by the change detection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub pressure: Option<f32>,
+    pub voc: Option<u16>,
+}
+
+/// How far each field has to move to count as a change (the `*_CHANGE_DELTA`s).
+#[derive(Clone, Copy, Debug)]
+pub struct ChangeDeltas {
+    pub temperature: f32,
+    pub humidity: f32,
+    pub pressure: f32,
+    pub voc: u16,
+}
+
+/// Whether any field of `current` moved at least its delta away from `last`.
+pub fn has_significant_change(last: &Reading, current: &Reading, deltas: &ChangeDeltas) -> bool {
+    let voc_changed = match (last.voc, current.voc) {
+        (Some(last_voc), Some(voc)) => last_voc.abs_diff(voc) >= deltas.voc,
+        (last_voc, voc) => last_voc.is_some() != voc.is_some(),
+    };
+
+    optional_changed(last.temperature, current.temperature, deltas.temperature)
+        || optional_changed(last.humidity, current.humidity, deltas.humidity)
+        || optional_changed(last.pressure, current.pressure, deltas.pressure)
+        || voc_changed
+}
+
+/// A channel appearing in or dropping out of a partial sample (or a VOC-only one) counts as a
+/// change too.
+fn optional_changed(last: Option<f32>, current: Option<f32>, delta: f32) -> bool {
+    match (last, current) {
+        (Some(last), Some(current)) => (current - last).abs() >= delta,
+        (last, current) => last.is_some() != current.is_some(),
+    }
+}
+
+/// How far `current` moved from `last`, in multiples of the field's delta, for the field that
+/// moved most. Unlike `has_significant_change`, a channel appearing or dropping out doesn't count:
+/// it is no trend.
+pub fn change_in_deltas(last: &Reading, current: &Reading, deltas: &ChangeDeltas) -> f32 {
+    let optional = |last: Option<f32>, current: Option<f32>, delta: f32| match (last, current) {
+        (Some(last), Some(current)) => (current - last).abs() / delta,
+        _ => 0.0,
+    };
+
+    [
+        optional(last.temperature, current.temperature, deltas.temperature),
+        optional(last.humidity, current.humidity, deltas.humidity),
+        optional(last.pressure, current.pressure, deltas.pressure),
+        optional(
+            last.voc.map(f32::from),
+            current.voc.map(f32::from),
+            f32::from(deltas.voc),
+        ),
+    ]
+    .into_iter()
+    .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const DELTAS: ChangeDeltas = ChangeDeltas {
+        temperature: 0.2,
+        humidity: 1.0,
+        pressure: 0.5,
+        voc: 10,
+    };
+
+    pub(crate) const READING: Reading = Reading {
+        temperature: Some(21.0),
+        humidity: Some(45.0),
+        pressure: Some(1013.0),
+        voc: Some(100),
+    };
+
+    #[test]
+    fn changes_below_every_delta_are_insignificant() {
+        let current = Reading {
+            temperature: Some(21.1),
+            humidity: Some(45.9),
+            pressure: Some(1012.6),
+            voc: Some(109),
+        };
+        assert!(!has_significant_change(&READING, &current, &DELTAS));
+    }
+
+    #[test]
+    fn any_field_reaching_its_delta_is_significant() {
+        let changed = [
+            Reading {
+                temperature: Some(20.8),
+                ..READING
+            },
+            Reading {
+                humidity: Some(46.0),
+                ..READING
+            },
+            Reading {
+                pressure: Some(1012.5),
+                ..READING
+            },
+            Reading {
+                voc: Some(90),
+                ..READING
+            },
+        ];
+        for current in changed {
+            assert!(
+                has_significant_change(&READING, &current, &DELTAS),
+                "{current:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_channel_appearing_or_dropping_out_is_significant() {
+        let partial = Reading {
+            temperature: None,
+            humidity: None,
+            voc: None,
+            ..READING
+        };
+        assert!(has_significant_change(&READING, &partial, &DELTAS));
+        assert!(has_significant_change(&partial, &READING, &DELTAS));
+        assert!(!has_significant_change(&partial, &partial, &DELTAS));
+    }
+
+    #[test]
+    fn change_in_deltas_follows_the_fastest_field() {
+        let current = Reading {
+            temperature: Some(21.1),
+            humidity: Some(48.0),
+            ..READING
+        };
+        assert_eq!(change_in_deltas(&READING, &READING, &DELTAS), 0.0);
+        assert_eq!(change_in_deltas(&READING, &current, &DELTAS), 3.0);
+
+        let voc = Reading {
+            voc: Some(75),
+            ..READING
+        };
+        assert_eq!(change_in_deltas(&READING, &voc, &DELTAS), 2.5);
+    }
+
+    #[test]
+    fn change_in_deltas_ignores_a_channel_appearing_or_dropping_out() {
+        let partial = Reading {
+            temperature: None,
+            voc: None,
+            ..READING
+        };
+        assert_eq!(change_in_deltas(&READING, &partial, &DELTAS), 0.0);
+        assert_eq!(change_in_deltas(&partial, &READING, &DELTAS), 0.0);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+/// Something that posts samples and answers with the HTTP status, the part of the HTTP client the
+/// delivery logic needs.
+pub trait Poster<T> {
+    type Error;
+
+    fn post_one(&mut self, sample: &T) -> Result<u16, Self::Error>;
+    fn post_batch(&mut self, batch: &[T]) -> Result<u16, Self::Error>;
+}
+
+/// What one delivery attempt did with the buffered samples.
+#[derive(Debug, PartialEq)]
+pub enum Outcome<E> {
+    /// 2xx: the samples were accepted and cleared from the buffer.
+    Accepted { status: u16, count: usize },
+    /// 429: kept for the next attempt, after a cool-down.
+    RateLimited,
+    /// 5xx: kept for the next attempt.
+    ServerError { status: u16 },
+    /// Any other status: dropped, retrying wouldn't change the answer.
+    Rejected { status: u16, count: usize },
+    /// The request itself failed; the samples are kept and the connection should be rebuilt.
+    Failed(E),
+}
+
+/// Posts `pending` oldest first, a single sample with `post_one` and several as one batch, then
+/// applies the answer to the buffer (see `Outcome`). Does nothing with an empty buffer.
+pub fn deliver<T, P: Poster<T>>(
+    poster: &mut P,
+    pending: &mut VecDeque<T>,
+) -> Option<Outcome<P::Error>> {
+    let result = match pending.len() {
+        0 => return None,
+        1 => poster.post_one(&pending[0]),
+        _ => poster.post_batch(pending.make_contiguous()),
+    };
+
+    let count = pending.len();
+    Some(match result {
+        Ok(status) if (200..300).contains(&status) => {
+            pending.clear();
+            Outcome::Accepted { status, count }
+        }
+        Ok(429) => Outcome::RateLimited,
+        Ok(status) if status >= 500 => Outcome::ServerError { status },
+        Ok(status) => {
+            pending.clear();
+            Outcome::Rejected { status, count }
+        }
+        Err(error) => Outcome::Failed(error),
+    })
+}
+
+/// Appends `sample` to the offline buffer, evicting the oldest once `capacity` (at least 1) is
+/// reached. Returns whether a sample was evicted.
+pub fn buffer<T>(pending: &mut VecDeque<T>, sample: T, capacity: usize) -> bool {
+    let evicted = pending.len() >= capacity.max(1);
+    if evicted {
+        pending.pop_front();
+    }
+    pending.push_back(sample);
+    evicted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers with the queued results in turn and records what it was asked to post.
+    struct MockPoster {
+        answers: VecDeque<Result<u16, &'static str>>,
+        posts: Vec<Vec<u32>>,
+        single_posts: usize,
+    }
+
+    impl MockPoster {
+        fn answering(answers: &[Result<u16, &'static str>]) -> Self {
+            Self {
+                answers: answers.iter().copied().collect(),
+                posts: Vec::new(),
+                single_posts: 0,
+            }
+        }
+
+        fn answer(&mut self) -> Result<u16, &'static str> {
+            self.answers.pop_front().expect("unexpected post")
+        }
+    }
+
+    impl Poster<u32> for MockPoster {
+        type Error = &'static str;
+
+        fn post_one(&mut self, sample: &u32) -> Result<u16, Self::Error> {
+            self.single_posts += 1;
+            self.posts.push(vec![*sample]);
+            self.answer()
+        }
+
+        fn post_batch(&mut self, batch: &[u32]) -> Result<u16, Self::Error> {
+            self.posts.push(batch.to_vec());
+            self.answer()
+        }
+    }
+
+    fn buffered(samples: &[u32]) -> VecDeque<u32> {
+        samples.iter().copied().collect()
+    }
+
+    #[test]
+    fn a_single_sample_is_posted_on_its_own() {
+        let mut poster = MockPoster::answering(&[Ok(201)]);
+        let mut pending = buffered(&[7]);
+
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::Accepted {
+                status: 201,
+                count: 1
+            })
+        );
+        assert_eq!(poster.single_posts, 1);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn any_2xx_clears_the_buffer() {
+        let mut poster = MockPoster::answering(&[Ok(204)]);
+        let mut pending = buffered(&[1, 2, 3]);
+
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::Accepted {
+                status: 204,
+                count: 3
+            })
+        );
+        assert_eq!(poster.posts, [vec![1, 2, 3]]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn retries_keep_the_samples_in_order() {
+        let mut poster = MockPoster::answering(&[Ok(429), Ok(503), Err("timeout"), Ok(200)]);
+        let mut pending = buffered(&[1, 2]);
+
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::RateLimited)
+        );
+        buffer(&mut pending, 3, 8);
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::ServerError { status: 503 })
+        );
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::Failed("timeout"))
+        );
+        buffer(&mut pending, 4, 8);
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::Accepted {
+                status: 200,
+                count: 4
+            })
+        );
+
+        assert_eq!(
+            poster.posts,
+            [vec![1, 2], vec![1, 2, 3], vec![1, 2, 3], vec![1, 2, 3, 4]]
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn other_statuses_drop_the_samples() {
+        let mut poster = MockPoster::answering(&[Ok(400), Ok(302)]);
+        let mut pending = buffered(&[1, 2]);
+
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::Rejected {
+                status: 400,
+                count: 2
+            })
+        );
+        assert!(pending.is_empty());
+
+        buffer(&mut pending, 3, 8);
+        assert_eq!(
+            deliver(&mut poster, &mut pending),
+            Some(Outcome::Rejected {
+                status: 302,
+                count: 1
+            })
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn an_empty_buffer_posts_nothing() {
+        let mut poster = MockPoster::answering(&[]);
+        assert_eq!(deliver(&mut poster, &mut VecDeque::new()), None);
+        assert!(poster.posts.is_empty());
+    }
+
+    #[test]
+    fn a_full_buffer_evicts_the_oldest() {
+        let mut pending = buffered(&[1, 2]);
+
+        assert!(!buffer(&mut pending, 3, 3));
+        assert!(buffer(&mut pending, 4, 3));
+        assert_eq!(pending, [2, 3, 4]);
+
+        // A zero capacity still holds the newest sample
+        let mut pending = VecDeque::new();
+        assert!(!buffer(&mut pending, 1, 0));
+        assert!(buffer(&mut pending, 2, 0));
+        assert_eq!(pending, [2]);
+    }
+}
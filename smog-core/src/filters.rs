@@ -0,0 +1,287 @@
+use std::collections::VecDeque;
+
+/// A per-field processing stage. Returning `None` rejects the sample.
+pub trait SampleFilter {
+    fn filter(&mut self, value: f32) -> Option<f32>;
+
+    /// Feeds this filter's output into `next`.
+    fn then<F: SampleFilter>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain { first: self, next }
+    }
+}
+
+/// Filters composed per metric, e.g. `RangeClamp` -> `MedianFilter` -> `EmaFilter`.
+pub type FilterChain = Box<dyn SampleFilter + Send>;
+
+pub struct Chain<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<A: SampleFilter, B: SampleFilter> SampleFilter for Chain<A, B> {
+    fn filter(&mut self, value: f32) -> Option<f32> {
+        self.first
+            .filter(value)
+            .and_then(|value| self.next.filter(value))
+    }
+}
+
+/// Median of the last `window` values; removes single-sample spikes. A window of 1 passes through.
+pub struct MedianFilter {
+    window: usize,
+    values: VecDeque<f32>,
+}
+
+impl MedianFilter {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            values: VecDeque::with_capacity(window.max(1)),
+        }
+    }
+}
+
+impl SampleFilter for MedianFilter {
+    fn filter(&mut self, value: f32) -> Option<f32> {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+
+        let mut sorted: Vec<f32> = self.values.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+
+        let mid = sorted.len() / 2;
+        if sorted.len().is_multiple_of(2) {
+            Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+        } else {
+            Some(sorted[mid])
+        }
+    }
+}
+
+/// Exponential moving average: `alpha = 1.0` passes through, smaller values smooth more.
+pub struct EmaFilter {
+    alpha: f32,
+    state: Option<f32>,
+}
+
+impl EmaFilter {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            state: None,
+        }
+    }
+}
+
+impl SampleFilter for EmaFilter {
+    fn filter(&mut self, value: f32) -> Option<f32> {
+        let smoothed = match self.state {
+            Some(previous) => previous + self.alpha * (value - previous),
+            None => value,
+        };
+        self.state = Some(smoothed);
+        Some(smoothed)
+    }
+}
+
+/// Clamps values into the sensor's physical range so a glitch can't poison the later stages.
+pub struct RangeClamp {
+    min: f32,
+    max: f32,
+}
+
+impl RangeClamp {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+}
+
+impl SampleFilter for RangeClamp {
+    fn filter(&mut self, value: f32) -> Option<f32> {
+        value.is_finite().then(|| value.clamp(self.min, self.max))
+    }
+}
+
+/// What the spike guard does with a reading outside the allowed delta.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpikePolicy {
+    /// Pull it back to the edge of the allowed band.
+    Clamp,
+    /// Drop the sample (the whole reading is skipped, like any rejected filter stage).
+    Reject,
+}
+
+const SPIKE_AVERAGE_ALPHA: f32 = 0.5;
+const SPIKE_MAX_CONSECUTIVE_REJECTS: u32 = 5;
+
+/// Keeps values within `max_delta` of a running average (EMA), so a single bad sample can't jump
+/// the output while a genuine trend (under half of `max_delta` per sample) passes as-is.
+///
+/// Rejected values don't move the average. After `SPIKE_MAX_CONSECUTIVE_REJECTS` in a row the
+/// level is taken to have really changed and the average restarts from the current value.
+pub struct SpikeGuard {
+    max_delta: f32,
+    policy: SpikePolicy,
+    average: Option<f32>,
+    consecutive_rejects: u32,
+}
+
+impl SpikeGuard {
+    pub fn new(max_delta: f32, policy: SpikePolicy) -> Self {
+        Self {
+            max_delta: max_delta.abs(),
+            policy,
+            average: None,
+            consecutive_rejects: 0,
+        }
+    }
+}
+
+impl SampleFilter for SpikeGuard {
+    fn filter(&mut self, value: f32) -> Option<f32> {
+        let Some(average) = self.average else {
+            self.average = Some(value);
+            return Some(value);
+        };
+
+        let delta = value - average;
+        let value = if delta.abs() <= self.max_delta {
+            self.consecutive_rejects = 0;
+            value
+        } else {
+            match self.policy {
+                SpikePolicy::Clamp => average + delta.clamp(-self.max_delta, self.max_delta),
+                SpikePolicy::Reject if self.consecutive_rejects < SPIKE_MAX_CONSECUTIVE_REJECTS => {
+                    self.consecutive_rejects += 1;
+                    return None;
+                }
+                SpikePolicy::Reject => {
+                    self.consecutive_rejects = 0;
+                    self.average = Some(value);
+                    return Some(value);
+                }
+            }
+        };
+
+        self.average = Some(average + SPIKE_AVERAGE_ALPHA * (value - average));
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(filter: &mut impl SampleFilter, values: &[f32]) -> Vec<Option<f32>> {
+        values.iter().map(|&value| filter.filter(value)).collect()
+    }
+
+    #[test]
+    fn median_removes_a_single_spike() {
+        let mut median = MedianFilter::new(3);
+        let output = run(&mut median, &[20.0, 20.2, 35.0, 20.4, 20.6]);
+        assert_eq!(
+            output,
+            [Some(20.0), Some(20.1), Some(20.2), Some(20.4), Some(20.6)]
+        );
+    }
+
+    #[test]
+    fn median_window_of_one_passes_through() {
+        let mut median = MedianFilter::new(0);
+        assert_eq!(
+            run(&mut median, &[1.0, 9.0, 3.0]),
+            [Some(1.0), Some(9.0), Some(3.0)]
+        );
+    }
+
+    #[test]
+    fn ema_starts_at_the_first_value_and_smooths_after() {
+        let mut ema = EmaFilter::new(0.5);
+        assert_eq!(
+            run(&mut ema, &[10.0, 20.0, 20.0]),
+            [Some(10.0), Some(15.0), Some(17.5)]
+        );
+
+        let mut passthrough = EmaFilter::new(1.0);
+        assert_eq!(
+            run(&mut passthrough, &[10.0, 20.0]),
+            [Some(10.0), Some(20.0)]
+        );
+    }
+
+    #[test]
+    fn range_clamp_clamps_and_rejects_non_finite_values() {
+        let mut clamp = RangeClamp::new(-40.0, 85.0);
+        assert_eq!(
+            run(&mut clamp, &[-60.0, 21.5, 120.0, f32::NAN, f32::INFINITY]),
+            [Some(-40.0), Some(21.5), Some(85.0), None, None]
+        );
+    }
+
+    #[test]
+    fn chain_feeds_each_stage_into_the_next() {
+        let mut chain = RangeClamp::new(0.0, 100.0)
+            .then(MedianFilter::new(3))
+            .then(EmaFilter::new(0.5));
+        assert_eq!(
+            run(&mut chain, &[50.0, 150.0, f32::NAN, 50.0]),
+            [Some(50.0), Some(62.5), None, Some(56.25)]
+        );
+    }
+
+    #[test]
+    fn boxed_chain_is_usable_as_a_filter_chain() {
+        let mut chain: FilterChain = Box::new(RangeClamp::new(0.0, 1.0).then(EmaFilter::new(1.0)));
+        assert_eq!(chain.filter(2.0), Some(1.0));
+    }
+
+    #[test]
+    fn spike_guard_passes_values_within_the_delta() {
+        let mut guard = SpikeGuard::new(2.0, SpikePolicy::Clamp);
+        assert_eq!(
+            run(&mut guard, &[1000.0, 1001.0, 1001.5]),
+            [Some(1000.0), Some(1001.0), Some(1001.5)]
+        );
+    }
+
+    #[test]
+    fn spike_guard_clamps_to_the_edge_of_the_band() {
+        let mut guard = SpikeGuard::new(2.0, SpikePolicy::Clamp);
+        // The average moves halfway towards each output, to 1001 after the first spike
+        assert_eq!(
+            run(&mut guard, &[1000.0, 1010.0, 990.0]),
+            [Some(1000.0), Some(1002.0), Some(999.0)]
+        );
+    }
+
+    #[test]
+    fn spike_guard_rejects_a_spike_without_moving_the_average() {
+        let mut guard = SpikeGuard::new(2.0, SpikePolicy::Reject);
+        assert_eq!(
+            run(&mut guard, &[1000.0, 1050.0, 1001.0]),
+            [Some(1000.0), None, Some(1001.0)]
+        );
+    }
+
+    #[test]
+    fn spike_guard_accepts_a_persistent_level_change() {
+        let mut guard = SpikeGuard::new(2.0, SpikePolicy::Reject);
+        guard.filter(1000.0);
+        let output = run(&mut guard, &[1020.0; 7]);
+        assert_eq!(output[..5], [None; 5]);
+        assert_eq!(output[5..], [Some(1020.0); 2]);
+    }
+
+    #[test]
+    fn spike_guard_follows_a_slow_trend() {
+        let mut guard = SpikeGuard::new(2.0, SpikePolicy::Reject);
+        let trend: Vec<f32> = (0..20).map(|i| 1000.0 + 0.5 * i as f32).collect();
+        assert!(run(&mut guard, &trend).iter().all(Option::is_some));
+    }
+}
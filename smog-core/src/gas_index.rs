@@ -0,0 +1,496 @@
+//! Port of Sensirion's gas index algorithm (v3.2), which turns the SGP41's raw VOC and NOx
+//! signals into the same indices the SGP40 driver computes on its own: VOC 1..=500 around a
+//! learned average of 100, NOx 1..=500 around a baseline of 1.
+//!
+//! The structure and constants follow the reference C implementation, so it can be diffed against
+//! upstream; only the state persistence and tuning setters are left out.
+
+/// Samples ignored after power-up, while the sensor settles.
+const INITIAL_BLACKOUT_S: f32 = 45.0;
+const INDEX_GAIN: f32 = 230.0;
+const SRAW_STD_INITIAL: f32 = 50.0;
+const SRAW_STD_BONUS_VOC: f32 = 220.0;
+const SRAW_STD_NOX: f32 = 2000.0;
+const TAU_MEAN_HOURS: f32 = 12.0;
+const TAU_VARIANCE_HOURS: f32 = 12.0;
+const TAU_INITIAL_MEAN_VOC: f32 = 20.0;
+const TAU_INITIAL_MEAN_NOX: f32 = 1200.0;
+const INIT_DURATION_MEAN_VOC: f32 = 3600.0 * 0.75;
+const INIT_DURATION_MEAN_NOX: f32 = 3600.0 * 4.75;
+const INIT_TRANSITION_MEAN: f32 = 0.01;
+const TAU_INITIAL_VARIANCE: f32 = 2500.0;
+const INIT_DURATION_VARIANCE_VOC: f32 = 3600.0 * 1.45;
+const INIT_DURATION_VARIANCE_NOX: f32 = 3600.0 * 5.70;
+const INIT_TRANSITION_VARIANCE: f32 = 0.01;
+const GATING_THRESHOLD_VOC: f32 = 340.0;
+const GATING_THRESHOLD_NOX: f32 = 30.0;
+const GATING_THRESHOLD_INITIAL: f32 = 510.0;
+const GATING_THRESHOLD_TRANSITION: f32 = 0.09;
+const GATING_VOC_MAX_DURATION_MINUTES: f32 = 60.0 * 3.0;
+const GATING_NOX_MAX_DURATION_MINUTES: f32 = 60.0 * 12.0;
+const GATING_MAX_RATIO: f32 = 0.3;
+const SIGMOID_L: f32 = 500.0;
+const SIGMOID_K_VOC: f32 = -0.0065;
+const SIGMOID_X0_VOC: f32 = 213.0;
+const SIGMOID_K_NOX: f32 = -0.0101;
+const SIGMOID_X0_NOX: f32 = 614.0;
+const VOC_INDEX_OFFSET_DEFAULT: f32 = 100.0;
+const NOX_INDEX_OFFSET_DEFAULT: f32 = 1.0;
+const LP_TAU_FAST: f32 = 20.0;
+const LP_TAU_SLOW: f32 = 500.0;
+const LP_ALPHA: f32 = -0.2;
+const VOC_SRAW_MINIMUM: i32 = 20_000;
+const NOX_SRAW_MINIMUM: i32 = 10_000;
+const GAMMA_SCALING: f32 = 64.0;
+const ADDITIONAL_GAMMA_MEAN_SCALING: f32 = 8.0;
+const FIX16_MAX: f32 = 32_767.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GasKind {
+    Voc,
+    Nox,
+}
+
+/// One algorithm instance per signal, fed once every `sampling_interval_s`.
+pub struct GasIndex {
+    kind: GasKind,
+    sampling_interval_s: f32,
+    index_offset: f32,
+    sraw_minimum: i32,
+    gating_max_duration_minutes: f32,
+    init_duration_mean: f32,
+    init_duration_variance: f32,
+    gating_threshold: f32,
+    uptime_s: f32,
+    sraw: f32,
+    gas_index: f32,
+    estimator: MeanVarianceEstimator,
+    mox_model: MoxModel,
+    sigmoid_scaled: SigmoidScaled,
+    lowpass: AdaptiveLowpass,
+}
+
+impl GasIndex {
+    pub fn new(kind: GasKind, sampling_interval_s: f32) -> Self {
+        let (index_offset, sraw_minimum, gating_max, init_mean, init_variance, gating_threshold) =
+            match kind {
+                GasKind::Voc => (
+                    VOC_INDEX_OFFSET_DEFAULT,
+                    VOC_SRAW_MINIMUM,
+                    GATING_VOC_MAX_DURATION_MINUTES,
+                    INIT_DURATION_MEAN_VOC,
+                    INIT_DURATION_VARIANCE_VOC,
+                    GATING_THRESHOLD_VOC,
+                ),
+                GasKind::Nox => (
+                    NOX_INDEX_OFFSET_DEFAULT,
+                    NOX_SRAW_MINIMUM,
+                    GATING_NOX_MAX_DURATION_MINUTES,
+                    INIT_DURATION_MEAN_NOX,
+                    INIT_DURATION_VARIANCE_NOX,
+                    GATING_THRESHOLD_NOX,
+                ),
+            };
+        let estimator = MeanVarianceEstimator::new(kind, sampling_interval_s);
+        let (x0, k) = match kind {
+            GasKind::Voc => (SIGMOID_X0_VOC, SIGMOID_K_VOC),
+            GasKind::Nox => (SIGMOID_X0_NOX, SIGMOID_K_NOX),
+        };
+
+        Self {
+            kind,
+            sampling_interval_s,
+            index_offset,
+            sraw_minimum,
+            gating_max_duration_minutes: gating_max,
+            init_duration_mean: init_mean,
+            init_duration_variance: init_variance,
+            gating_threshold,
+            uptime_s: 0.0,
+            sraw: 0.0,
+            gas_index: 0.0,
+            mox_model: MoxModel {
+                sraw_std: estimator.std,
+                sraw_mean: estimator.mean(),
+            },
+            estimator,
+            sigmoid_scaled: SigmoidScaled {
+                x0,
+                k,
+                offset_default: index_offset,
+            },
+            lowpass: AdaptiveLowpass::new(sampling_interval_s),
+        }
+    }
+
+    /// Feeds one raw signal (SRAW ticks) and returns the index. 0 during the initial blackout.
+    pub fn process(&mut self, sraw: u16) -> u16 {
+        if self.uptime_s <= INITIAL_BLACKOUT_S {
+            self.uptime_s += self.sampling_interval_s;
+        } else {
+            let sraw = i32::from(sraw);
+            if sraw > 0 && sraw < 65_000 {
+                let clamped = sraw.clamp(self.sraw_minimum + 1, self.sraw_minimum + 32_767);
+                self.sraw = (clamped - self.sraw_minimum) as f32;
+            }
+
+            self.gas_index = if self.kind == GasKind::Voc || self.estimator.initialized {
+                let modeled = self.mox_model.process(self.kind, self.sraw);
+                self.sigmoid_scaled.process(modeled, self.index_offset)
+            } else {
+                self.index_offset
+            };
+            self.gas_index = self.lowpass.process(self.gas_index).max(0.5);
+
+            if self.sraw > 0.0 {
+                let gating = Gating {
+                    sampling_interval_s: self.sampling_interval_s,
+                    init_duration_mean: self.init_duration_mean,
+                    init_duration_variance: self.init_duration_variance,
+                    threshold: self.gating_threshold,
+                    max_duration_minutes: self.gating_max_duration_minutes,
+                    gas_index: self.gas_index,
+                };
+                self.estimator.process(self.sraw, &gating);
+                self.mox_model = MoxModel {
+                    sraw_std: self.estimator.std,
+                    sraw_mean: self.estimator.mean(),
+                };
+            }
+        }
+
+        (self.gas_index + 0.5) as u16
+    }
+}
+
+/// The algorithm parameters the estimator's gamma calculation reads.
+struct Gating {
+    sampling_interval_s: f32,
+    init_duration_mean: f32,
+    init_duration_variance: f32,
+    threshold: f32,
+    max_duration_minutes: f32,
+    gas_index: f32,
+}
+
+/// Tracks the mean and standard deviation of the raw signal, learning fast at first and over
+/// `TAU_*_HOURS` later, and pausing (gating) while the index is high so an event isn't learned
+/// as the new normal.
+struct MeanVarianceEstimator {
+    initialized: bool,
+    mean: f32,
+    sraw_offset: f32,
+    std: f32,
+    gamma_mean: f32,
+    gamma_variance: f32,
+    gamma_initial_mean: f32,
+    gamma_initial_variance: f32,
+    current_gamma_mean: f32,
+    current_gamma_variance: f32,
+    uptime_gamma: f32,
+    uptime_gating: f32,
+    gating_duration_minutes: f32,
+}
+
+impl MeanVarianceEstimator {
+    fn new(kind: GasKind, sampling_interval_s: f32) -> Self {
+        let interval_h = sampling_interval_s / 3600.0;
+        let tau_initial_mean = match kind {
+            GasKind::Voc => TAU_INITIAL_MEAN_VOC,
+            GasKind::Nox => TAU_INITIAL_MEAN_NOX,
+        };
+
+        Self {
+            initialized: false,
+            mean: 0.0,
+            sraw_offset: 0.0,
+            std: SRAW_STD_INITIAL,
+            gamma_mean: ADDITIONAL_GAMMA_MEAN_SCALING * GAMMA_SCALING * interval_h
+                / (TAU_MEAN_HOURS + interval_h),
+            gamma_variance: GAMMA_SCALING * interval_h / (TAU_VARIANCE_HOURS + interval_h),
+            gamma_initial_mean: ADDITIONAL_GAMMA_MEAN_SCALING * GAMMA_SCALING * sampling_interval_s
+                / (tau_initial_mean + sampling_interval_s),
+            gamma_initial_variance: GAMMA_SCALING * sampling_interval_s
+                / (TAU_INITIAL_VARIANCE + sampling_interval_s),
+            current_gamma_mean: 0.0,
+            current_gamma_variance: 0.0,
+            uptime_gamma: 0.0,
+            uptime_gating: 0.0,
+            gating_duration_minutes: 0.0,
+        }
+    }
+
+    fn mean(&self) -> f32 {
+        self.mean + self.sraw_offset
+    }
+
+    fn calculate_gamma(&mut self, gating: &Gating) {
+        let uptime_limit = FIX16_MAX - gating.sampling_interval_s;
+        if self.uptime_gamma < uptime_limit {
+            self.uptime_gamma += gating.sampling_interval_s;
+        }
+        if self.uptime_gating < uptime_limit {
+            self.uptime_gating += gating.sampling_interval_s;
+        }
+
+        let sigmoid_gamma_mean = sigmoid(
+            gating.init_duration_mean,
+            INIT_TRANSITION_MEAN,
+            self.uptime_gamma,
+        );
+        let gamma_mean =
+            self.gamma_mean + (self.gamma_initial_mean - self.gamma_mean) * sigmoid_gamma_mean;
+        let gating_threshold_mean = gating.threshold
+            + (GATING_THRESHOLD_INITIAL - gating.threshold)
+                * sigmoid(
+                    gating.init_duration_mean,
+                    INIT_TRANSITION_MEAN,
+                    self.uptime_gating,
+                );
+        let sigmoid_gating_mean = sigmoid(
+            gating_threshold_mean,
+            GATING_THRESHOLD_TRANSITION,
+            gating.gas_index,
+        );
+        self.current_gamma_mean = sigmoid_gating_mean * gamma_mean;
+
+        let sigmoid_gamma_variance = sigmoid(
+            gating.init_duration_variance,
+            INIT_TRANSITION_VARIANCE,
+            self.uptime_gamma,
+        );
+        let gamma_variance = self.gamma_variance
+            + (self.gamma_initial_variance - self.gamma_variance)
+                * (sigmoid_gamma_variance - sigmoid_gamma_mean);
+        let gating_threshold_variance = gating.threshold
+            + (GATING_THRESHOLD_INITIAL - gating.threshold)
+                * sigmoid(
+                    gating.init_duration_variance,
+                    INIT_TRANSITION_VARIANCE,
+                    self.uptime_gating,
+                );
+        let sigmoid_gating_variance = sigmoid(
+            gating_threshold_variance,
+            GATING_THRESHOLD_TRANSITION,
+            gating.gas_index,
+        );
+        self.current_gamma_variance = sigmoid_gating_variance * gamma_variance;
+
+        self.gating_duration_minutes = (self.gating_duration_minutes
+            + gating.sampling_interval_s / 60.0
+                * ((1.0 - sigmoid_gating_mean) * (1.0 + GATING_MAX_RATIO) - GATING_MAX_RATIO))
+            .max(0.0);
+        if self.gating_duration_minutes > gating.max_duration_minutes {
+            self.uptime_gating = 0.0;
+        }
+    }
+
+    fn process(&mut self, sraw: f32, gating: &Gating) {
+        if !self.initialized {
+            self.initialized = true;
+            self.sraw_offset = sraw;
+            self.mean = 0.0;
+            return;
+        }
+
+        if self.mean >= 100.0 || self.mean <= -100.0 {
+            self.sraw_offset += self.mean;
+            self.mean = 0.0;
+        }
+        let sraw = sraw - self.sraw_offset;
+        self.calculate_gamma(gating);
+
+        let delta_sgp = (sraw - self.mean) / GAMMA_SCALING;
+        let c = self.std + delta_sgp.abs();
+        let additional_scaling = if c > 1440.0 {
+            (c / 1440.0) * (c / 1440.0)
+        } else {
+            1.0
+        };
+        self.std = (additional_scaling * (GAMMA_SCALING - self.current_gamma_variance)).sqrt()
+            * (self.std * (self.std / (GAMMA_SCALING * additional_scaling))
+                + self.current_gamma_variance * delta_sgp / additional_scaling * delta_sgp)
+                .sqrt();
+        self.mean += self.current_gamma_mean * delta_sgp / ADDITIONAL_GAMMA_MEAN_SCALING;
+    }
+}
+
+/// Falls from 1 to 0 around `x0`, with steepness `k`.
+fn sigmoid(x0: f32, k: f32, sample: f32) -> f32 {
+    let x = k * (sample - x0);
+    if x < -50.0 {
+        1.0
+    } else if x > 50.0 {
+        0.0
+    } else {
+        1.0 / (1.0 + x.exp())
+    }
+}
+
+/// Raw signal normalized by the learned mean and spread; VOC lowers the VOC signal but raises
+/// the NOx one, hence the sign.
+struct MoxModel {
+    sraw_std: f32,
+    sraw_mean: f32,
+}
+
+impl MoxModel {
+    fn process(&self, kind: GasKind, sraw: f32) -> f32 {
+        match kind {
+            GasKind::Voc => {
+                (sraw - self.sraw_mean) / (-(self.sraw_std + SRAW_STD_BONUS_VOC)) * INDEX_GAIN
+            }
+            GasKind::Nox => (sraw - self.sraw_mean) / SRAW_STD_NOX * INDEX_GAIN,
+        }
+    }
+}
+
+/// Maps the normalized signal onto the 0..=500 index range.
+struct SigmoidScaled {
+    x0: f32,
+    k: f32,
+    offset_default: f32,
+}
+
+impl SigmoidScaled {
+    fn process(&self, sample: f32, index_offset: f32) -> f32 {
+        let x = self.k * (sample - self.x0);
+        if x < -50.0 {
+            SIGMOID_L
+        } else if x > 50.0 {
+            0.0
+        } else if sample >= 0.0 {
+            let shift = if self.offset_default == 1.0 {
+                (500.0 / 499.0) * (1.0 - index_offset)
+            } else {
+                (SIGMOID_L - 5.0 * index_offset) / 4.0
+            };
+            (SIGMOID_L + shift) / (1.0 + x.exp()) - shift
+        } else {
+            index_offset / self.offset_default * (SIGMOID_L / (1.0 + x.exp()))
+        }
+    }
+}
+
+/// Low-pass whose time constant shortens while the signal moves, so events show quickly but
+/// noise at rest is smoothed.
+struct AdaptiveLowpass {
+    a1: f32,
+    a2: f32,
+    sampling_interval_s: f32,
+    state: Option<(f32, f32, f32)>,
+}
+
+impl AdaptiveLowpass {
+    fn new(sampling_interval_s: f32) -> Self {
+        Self {
+            a1: sampling_interval_s / (LP_TAU_FAST + sampling_interval_s),
+            a2: sampling_interval_s / (LP_TAU_SLOW + sampling_interval_s),
+            sampling_interval_s,
+            state: None,
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let (x1, x2, x3) = self.state.unwrap_or((sample, sample, sample));
+
+        let x1 = (1.0 - self.a1) * x1 + self.a1 * sample;
+        let x2 = (1.0 - self.a2) * x2 + self.a2 * sample;
+        let f1 = (LP_ALPHA * (x1 - x2).abs()).exp();
+        let tau_a = (LP_TAU_SLOW - LP_TAU_FAST) * f1 + LP_TAU_FAST;
+        let a3 = self.sampling_interval_s / (self.sampling_interval_s + tau_a);
+        let x3 = (1.0 - a3) * x3 + a3 * sample;
+
+        self.state = Some((x1, x2, x3));
+        x3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A typical clean-air reading of each pixel.
+    const VOC_SRAW: u16 = 30_000;
+    const NOX_SRAW: u16 = 15_000;
+
+    fn feed(index: &mut GasIndex, sraw: u16, seconds: u32) -> u16 {
+        (0..seconds).map(|_| index.process(sraw)).last().unwrap()
+    }
+
+    /// One index, fed a steady signal for `hours`.
+    fn settled(kind: GasKind, sraw: u16, hours: u32) -> GasIndex {
+        let mut index = GasIndex::new(kind, 1.0);
+        feed(&mut index, sraw, hours * 3600);
+        index
+    }
+
+    #[test]
+    fn reports_zero_during_the_blackout() {
+        for kind in [GasKind::Voc, GasKind::Nox] {
+            let mut index = GasIndex::new(kind, 1.0);
+            for second in 0..=INITIAL_BLACKOUT_S as u32 {
+                assert_eq!(index.process(VOC_SRAW), 0, "{kind:?} at {second} s");
+            }
+            assert!(index.process(VOC_SRAW) > 0);
+        }
+    }
+
+    #[test]
+    fn blackout_counts_sampling_intervals() {
+        let mut index = GasIndex::new(GasKind::Voc, 10.0);
+        // 0, 10, .., 40: still within the 45 s
+        for _ in 0..5 {
+            assert_eq!(index.process(VOC_SRAW), 0);
+        }
+        index.process(VOC_SRAW);
+        assert!(index.process(VOC_SRAW) > 0);
+    }
+
+    #[test]
+    fn steady_air_settles_at_the_index_offsets() {
+        let mut voc = settled(GasKind::Voc, VOC_SRAW, 1);
+        assert_eq!(voc.process(VOC_SRAW), 100);
+
+        let mut nox = settled(GasKind::Nox, NOX_SRAW, 1);
+        assert_eq!(nox.process(NOX_SRAW), 1);
+    }
+
+    #[test]
+    fn voc_event_lowers_the_signal_and_raises_the_index() {
+        let mut index = settled(GasKind::Voc, VOC_SRAW, 1);
+
+        let during = feed(&mut index, VOC_SRAW - 3000, 120);
+        assert!(during > 150 && during <= 500, "{during}");
+
+        let after = feed(&mut index, VOC_SRAW, 1800);
+        assert!((95..=105).contains(&after), "{after}");
+    }
+
+    #[test]
+    fn cleaner_air_than_learned_lowers_the_voc_index() {
+        let mut index = settled(GasKind::Voc, VOC_SRAW, 1);
+
+        let cleaner = feed(&mut index, VOC_SRAW + 3000, 120);
+        assert!((1..100).contains(&cleaner), "{cleaner}");
+    }
+
+    #[test]
+    fn nox_event_raises_the_signal_and_the_index() {
+        let mut index = settled(GasKind::Nox, NOX_SRAW, 1);
+
+        let during = feed(&mut index, NOX_SRAW + 3000, 120);
+        assert!(during > 1 && during <= 500, "{during}");
+    }
+
+    #[test]
+    fn indices_stay_in_range_at_the_signal_extremes() {
+        for kind in [GasKind::Voc, GasKind::Nox] {
+            let mut index = settled(kind, VOC_SRAW, 1);
+            for sraw in [0, 1, u16::MAX, 1, 64_999] {
+                let value = feed(&mut index, sraw, 600);
+                assert!((1..=500).contains(&value), "{kind:?} at {sraw}: {value}");
+            }
+        }
+    }
+}
@@ -0,0 +1,347 @@
+/// Hash table slots, one position each: a single candidate per hash, no chains.
+const HASH_BITS: u32 = 10;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// The furthest back a DEFLATE match can reach.
+const WINDOW: usize = 32 * 1024;
+
+const END_OF_BLOCK: u16 = 256;
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// `Content-Encoding: gzip` body for `data`: the gzip container (RFC 1952) with a fixed 10-byte
+/// header without name or mtime, the DEFLATE stream, then the CRC-32 and length trailer.
+///
+/// The whole body is in memory already, so matches are looked up in the input itself and the only
+/// state is a 4 KB hash table, where a general-purpose compressor carries its own window, hash
+/// chains and buffers (over 300 KB for miniz_oxide). The stream is one block with the fixed
+/// Huffman codes of RFC 1951; for repetitive JSON lines dynamic codes would add little.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::with_capacity(data.len() / 2 + 32);
+    // Magic, DEFLATE, no flags, no mtime, no extra flags, unknown OS
+    out.bytes
+        .extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+    deflate(data, &mut out);
+    let mut gzip = out.finish();
+    gzip.extend_from_slice(&crc32(data).to_le_bytes());
+    // Input size modulo 2^32
+    gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    gzip
+}
+
+/// Bitwise CRC-32 (IEEE, reflected); bodies are a few KB, not worth a 1 KB table.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// One final block with fixed codes: `BFINAL` set, `BTYPE` 01.
+fn deflate(data: &[u8], out: &mut BitWriter) {
+    out.write_bits(1, 1);
+    out.write_bits(1, 2);
+
+    // Position + 1 of the last occurrence of each hash, 0 when there was none
+    let mut head = vec![0u32; 1 << HASH_BITS];
+    let mut pos = 0;
+    while pos < data.len() {
+        match find_match(data, pos, &head) {
+            Some((length, distance)) => {
+                write_match(out, length, distance);
+                for skipped in pos..pos + length {
+                    insert(data, skipped, &mut head);
+                }
+                pos += length;
+            }
+            None => {
+                insert(data, pos, &mut head);
+                write_literal_length(out, u16::from(data[pos]));
+                pos += 1;
+            }
+        }
+    }
+
+    write_literal_length(out, END_OF_BLOCK);
+}
+
+fn hash(data: &[u8], pos: usize) -> Option<usize> {
+    let bytes = data.get(pos..pos + MIN_MATCH)?;
+    let key = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+    Some((key.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize)
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [u32]) {
+    if let Some(hash) = hash(data, pos) {
+        head[hash] = pos as u32 + 1;
+    }
+}
+
+/// Length and distance of the bytes at `pos` repeating the last occurrence of their hash, if that
+/// is in reach and repeats at least `MIN_MATCH` of them.
+fn find_match(data: &[u8], pos: usize, head: &[u32]) -> Option<(usize, usize)> {
+    let candidate = (head[hash(data, pos)?] as usize).checked_sub(1)?;
+    let distance = pos - candidate;
+    if distance > WINDOW {
+        return None;
+    }
+
+    // May overlap `pos`, as DEFLATE allows: a run is a match at distance 1
+    let max = MAX_MATCH.min(data.len() - pos);
+    let length = (0..max)
+        .take_while(|&offset| data[candidate + offset] == data[pos + offset])
+        .count();
+    (length >= MIN_MATCH).then_some((length, distance))
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.partition_point(|&base| usize::from(base) <= length) - 1;
+    write_literal_length(out, 257 + code as u16);
+    out.write_bits(
+        (length - usize::from(LENGTH_BASE[code])) as u32,
+        LENGTH_EXTRA_BITS[code],
+    );
+
+    let code = DISTANCE_BASE.partition_point(|&base| usize::from(base) <= distance) - 1;
+    // Distance codes are all 5 bits long
+    out.write_code(code as u32, 5);
+    out.write_bits(
+        (distance - usize::from(DISTANCE_BASE[code])) as u32,
+        DISTANCE_EXTRA_BITS[code],
+    );
+}
+
+/// The fixed literal/length code of RFC 1951 3.2.6.
+fn write_literal_length(out: &mut BitWriter, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+        0..=143 => out.write_code(0x30 + symbol, 8),
+        144..=255 => out.write_code(0x190 + symbol - 144, 9),
+        256..=279 => out.write_code(symbol - 256, 7),
+        _ => out.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// DEFLATE packs bits from the least significant end of each byte.
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u32,
+    pending_bits: u8,
+}
+
+impl BitWriter {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+            pending: 0,
+            pending_bits: 0,
+        }
+    }
+
+    /// A value, least significant bit first (header fields and extra bits).
+    fn write_bits(&mut self, value: u32, count: u8) {
+        self.pending |= value << self.pending_bits;
+        self.pending_bits += count;
+        while self.pending_bits >= 8 {
+            self.bytes.push(self.pending as u8);
+            self.pending >>= 8;
+            self.pending_bits -= 8;
+        }
+    }
+
+    /// A Huffman code, most significant bit first.
+    fn write_code(&mut self, code: u32, length: u8) {
+        let reversed = code.reverse_bits() >> (32 - u32::from(length));
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.pending_bits > 0 {
+            self.bytes.push(self.pending as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Just enough of an inflater for what `compress` emits: fixed-code blocks.
+    fn gunzip(gzip: &[u8]) -> Vec<u8> {
+        assert_eq!(gzip[..10], [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+        let (stream, trailer) = gzip[10..].split_at(gzip.len() - 18);
+
+        let mut bits = BitReader {
+            bytes: stream,
+            pos: 0,
+        };
+        let mut data = Vec::new();
+        loop {
+            let is_final = bits.bits(1) == 1;
+            assert_eq!(bits.bits(2), 1, "not a fixed-code block");
+            loop {
+                let symbol = bits.literal_length();
+                match symbol {
+                    0..=255 => data.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let code = usize::from(symbol - 257);
+                        let length = usize::from(LENGTH_BASE[code])
+                            + bits.bits(LENGTH_EXTRA_BITS[code]) as usize;
+                        let code = bits.code(5) as usize;
+                        let distance = usize::from(DISTANCE_BASE[code])
+                            + bits.bits(DISTANCE_EXTRA_BITS[code]) as usize;
+                        for _ in 0..length {
+                            data.push(data[data.len() - distance]);
+                        }
+                    }
+                }
+            }
+            if is_final {
+                break;
+            }
+        }
+        assert_eq!(
+            bits.pos.div_ceil(8),
+            stream.len(),
+            "trailing bytes in the stream"
+        );
+
+        assert_eq!(trailer[..4], crc32(&data).to_le_bytes());
+        assert_eq!(trailer[4..], (data.len() as u32).to_le_bytes());
+        data
+    }
+
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.bytes[self.pos / 8] >> (self.pos % 8)) & 1;
+            self.pos += 1;
+            u32::from(bit)
+        }
+
+        fn bits(&mut self, count: u8) -> u32 {
+            (0..count).fold(0, |value, index| value | (self.bit() << index))
+        }
+
+        fn code(&mut self, length: u8) -> u32 {
+            (0..length).fold(0, |code, _| (code << 1) | self.bit())
+        }
+
+        fn literal_length(&mut self) -> u16 {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code as u16;
+            }
+            let code = (code << 1) | self.bit();
+            match code {
+                0x30..=0xbf => return (code - 0x30) as u16,
+                0xc0..=0xc7 => return 280 + (code - 0xc0) as u16,
+                _ => {}
+            }
+            let code = (code << 1) | self.bit();
+            144 + (code - 0x190) as u16
+        }
+    }
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let gzip = compress(data);
+        assert_eq!(gunzip(&gzip), data);
+        gzip
+    }
+
+    /// Deterministic bytes that don't compress.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn empty_input_is_a_bare_end_of_block() {
+        assert_eq!(
+            compress(b""),
+            [
+                0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 0x03, 0x00, 0, 0, 0, 0, 0, 0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_literals() {
+        round_trip(b"a");
+        round_trip(b"ab");
+        round_trip(&(0..=255).collect::<Vec<u8>>());
+        round_trip(&noise(1000));
+    }
+
+    #[test]
+    fn round_trips_runs_and_longest_matches() {
+        round_trip(&[b'x'; 3]);
+        round_trip(&[0; 258 + 1]);
+        round_trip(&[0; 10_000]);
+        round_trip(&b"abc".repeat(1000));
+    }
+
+    #[test]
+    fn matches_stay_within_the_window() {
+        let block = noise(WINDOW + 100);
+        let mut data = block.clone();
+        data.extend_from_slice(&block[..500]);
+        data.extend_from_slice(&block[WINDOW..]);
+        round_trip(&data);
+    }
+
+    #[test]
+    fn compresses_json_lines() {
+        let body: Vec<u8> = (0..20)
+            .flat_map(|seq| {
+                format!(
+                    "{{\"temperature\":21.{seq},\"humidity\":45.5,\"pressure\":1013.2,\"voc\":100,\"seq\":{seq}}}\n"
+                )
+                .into_bytes()
+            })
+            .collect();
+
+        let gzip = round_trip(&body);
+        assert!(
+            gzip.len() < body.len() / 4,
+            "{} of {}",
+            gzip.len(),
+            body.len()
+        );
+    }
+}
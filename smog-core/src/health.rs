@@ -0,0 +1,203 @@
+/// Detects a gas sensor whose index is stuck at 1 (a known SGP40 failure that only a power cycle
+/// clears): `threshold` ones in a row, with a raw signal that stayed within `raw_tolerance` over
+/// the same readings. Raw movement means the sensor is alive and restarts the run; a missing raw
+/// value (raw not measured) neither confirms nor clears it.
+pub struct StuckDetector {
+    threshold: u16,
+    raw_tolerance: u16,
+    consecutive_one: u16,
+    /// Lowest and highest raw signal seen during the current run of ones.
+    raw_span: Option<(u16, u16)>,
+}
+
+impl StuckDetector {
+    pub fn new(threshold: u16, raw_tolerance: u16) -> Self {
+        Self {
+            threshold,
+            raw_tolerance,
+            consecutive_one: 0,
+            raw_span: None,
+        }
+    }
+
+    pub fn check(&mut self, voc: Option<u16>, raw: Option<u16>) -> bool {
+        match voc {
+            Some(1) => {
+                self.consecutive_one = self.consecutive_one.saturating_add(1);
+
+                if let Some(raw) = raw {
+                    let (low, high) = self
+                        .raw_span
+                        .map_or((raw, raw), |(low, high)| (low.min(raw), high.max(raw)));
+                    if high - low > self.raw_tolerance {
+                        self.consecutive_one = 1;
+                        self.raw_span = Some((raw, raw));
+                    } else {
+                        self.raw_span = Some((low, high));
+                    }
+                }
+
+                self.consecutive_one >= self.threshold
+            }
+            Some(_) | None => {
+                self.reset();
+                false
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.consecutive_one = 0;
+        self.raw_span = None;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresenceCheck {
+    Present,
+    /// Time to try a bus recovery.
+    Recover,
+    /// Still failing right after a recovery: the sensor is gone.
+    Lost,
+}
+
+/// Read errors in a row for one sensor, the error-count counterpart of `StuckDetector`: a sensor
+/// that stops ACKing fails every read instead of returning a frozen value.
+pub struct PresenceWatchdog {
+    /// `SENSOR_LOST_AFTER_ERRORS`; `None` never gives up on a sensor.
+    lost_after: Option<u32>,
+    errors: u32,
+    recovery_tried: bool,
+}
+
+impl PresenceWatchdog {
+    pub fn new(lost_after: Option<u32>) -> Self {
+        Self {
+            lost_after,
+            errors: 0,
+            recovery_tried: false,
+        }
+    }
+
+    pub fn on_success(&mut self) {
+        self.errors = 0;
+        self.recovery_tried = false;
+    }
+
+    /// `lost_after` failures in a row ask for a bus recovery once; as many again right after it
+    /// mean the sensor is gone.
+    pub fn on_error(&mut self) -> PresenceCheck {
+        let Some(limit) = self.lost_after else {
+            return PresenceCheck::Present;
+        };
+        self.errors = self.errors.saturating_add(1);
+        if self.errors < limit {
+            return PresenceCheck::Present;
+        }
+        self.errors = 0;
+
+        if self.recovery_tried {
+            PresenceCheck::Lost
+        } else {
+            self.recovery_tried = true;
+            PresenceCheck::Recover
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use PresenceCheck::{Lost, Present, Recover};
+
+    fn feed(detector: &mut StuckDetector, readings: &[(Option<u16>, Option<u16>)]) -> Vec<bool> {
+        readings
+            .iter()
+            .map(|&(voc, raw)| detector.check(voc, raw))
+            .collect()
+    }
+
+    #[test]
+    fn ones_with_a_flat_raw_signal_are_stuck() {
+        let mut detector = StuckDetector::new(3, 5);
+        let readings = [
+            (Some(1), Some(30_000)),
+            (Some(1), Some(30_004)),
+            (Some(1), Some(30_002)),
+        ];
+        assert_eq!(feed(&mut detector, &readings), [false, false, true]);
+    }
+
+    #[test]
+    fn a_moving_raw_signal_restarts_the_run() {
+        let mut detector = StuckDetector::new(3, 5);
+        let readings = [
+            (Some(1), Some(30_000)),
+            (Some(1), Some(30_001)),
+            (Some(1), Some(30_100)),
+            (Some(1), Some(30_102)),
+            (Some(1), Some(30_099)),
+        ];
+        assert_eq!(
+            feed(&mut detector, &readings),
+            [false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn missing_raw_values_count_on_the_index_alone() {
+        let mut detector = StuckDetector::new(3, 5);
+        let readings = [(Some(1), None), (Some(1), Some(30_000)), (Some(1), None)];
+        assert_eq!(feed(&mut detector, &readings), [false, false, true]);
+    }
+
+    #[test]
+    fn any_other_index_clears_the_run() {
+        let mut detector = StuckDetector::new(2, 5);
+        let readings = [
+            (Some(1), Some(30_000)),
+            (Some(2), Some(30_000)),
+            (Some(1), Some(30_000)),
+            (None, None),
+            (Some(1), Some(30_000)),
+            (Some(1), Some(30_000)),
+        ];
+        assert_eq!(
+            feed(&mut detector, &readings),
+            [false, false, false, false, false, true]
+        );
+    }
+
+    fn errors(watchdog: &mut PresenceWatchdog, count: usize) -> Vec<PresenceCheck> {
+        (0..count).map(|_| watchdog.on_error()).collect()
+    }
+
+    #[test]
+    fn watchdog_recovers_once_then_reports_the_sensor_lost() {
+        let mut watchdog = PresenceWatchdog::new(Some(3));
+        assert_eq!(errors(&mut watchdog, 3), [Present, Present, Recover]);
+        assert_eq!(errors(&mut watchdog, 3), [Present, Present, Lost]);
+    }
+
+    #[test]
+    fn a_successful_read_resets_the_watchdog() {
+        let mut watchdog = PresenceWatchdog::new(Some(2));
+        assert_eq!(errors(&mut watchdog, 2), [Present, Recover]);
+        watchdog.on_success();
+        assert_eq!(errors(&mut watchdog, 1), [Present]);
+        watchdog.on_success();
+        // The recovery is available again after the sensor came back
+        assert_eq!(errors(&mut watchdog, 2), [Present, Recover]);
+    }
+
+    #[test]
+    fn disabled_watchdog_never_gives_up() {
+        let mut watchdog = PresenceWatchdog::new(None);
+        assert!(
+            errors(&mut watchdog, 100)
+                .iter()
+                .all(|check| *check == Present)
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+
+// Quantization steps: 0.01 °C, 0.1 %RH, 0.1 hPa
+const TEMPERATURE_SCALE: f32 = 100.0;
+const HUMIDITY_SCALE: f32 = 10.0;
+const PRESSURE_SCALE: f32 = 10.0;
+
+const HAS_HUMIDITY: u8 = 1 << 0;
+const HAS_PRESSURE: u8 = 1 << 1;
+const HAS_VOC: u8 = 1 << 2;
+const TIME_SYNCED: u8 = 1 << 3;
+const HAS_TEMPERATURE: u8 = 1 << 4;
+
+/// The fields a `DeltaHistory` keeps of a reading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompactReading {
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub pressure: Option<f32>,
+    pub voc: Option<u16>,
+    pub time_synced: bool,
+    pub timestamp_unix_s: i64,
+    pub uptime_us: i64,
+}
+
+/// Absolute values in quantization steps; the state both the encoder and the decoder track.
+#[derive(Clone, Copy)]
+struct Quantized {
+    temperature: i32,
+    humidity: i32,
+    pressure: i32,
+    voc: i32,
+    timestamp_unix_s: i64,
+    uptime_ms: i64,
+}
+
+impl Quantized {
+    const ZERO: Self = Self {
+        temperature: 0,
+        humidity: 0,
+        pressure: 0,
+        voc: 0,
+        timestamp_unix_s: 0,
+        uptime_ms: 0,
+    };
+
+    /// A missing channel keeps its `previous` value, so it costs no change.
+    fn of(reading: &CompactReading, previous: &Quantized) -> Self {
+        let quantize = |value: f32, scale: f32| (value * scale).round() as i32;
+
+        Self {
+            temperature: reading
+                .temperature
+                .map_or(previous.temperature, |temperature| {
+                    quantize(temperature, TEMPERATURE_SCALE)
+                }),
+            humidity: reading.humidity.map_or(previous.humidity, |humidity| {
+                quantize(humidity, HUMIDITY_SCALE)
+            }),
+            pressure: reading.pressure.map_or(previous.pressure, |pressure| {
+                quantize(pressure, PRESSURE_SCALE)
+            }),
+            voc: reading.voc.map_or(previous.voc, i32::from),
+            timestamp_unix_s: reading.timestamp_unix_s,
+            uptime_ms: reading.uptime_us / 1000,
+        }
+    }
+
+    fn apply(self, delta: &Delta) -> Self {
+        Self {
+            temperature: self.temperature + i32::from(delta.temperature),
+            humidity: self.humidity + i32::from(delta.humidity),
+            pressure: self.pressure + i32::from(delta.pressure),
+            voc: self.voc + i32::from(delta.voc),
+            timestamp_unix_s: self.timestamp_unix_s + i64::from(delta.timestamp_s),
+            uptime_ms: self.uptime_ms + i64::from(delta.uptime_ms),
+        }
+    }
+
+    /// The inverse of `apply`: the state before `delta`.
+    fn revert(self, delta: &Delta) -> Self {
+        Self {
+            temperature: self.temperature - i32::from(delta.temperature),
+            humidity: self.humidity - i32::from(delta.humidity),
+            pressure: self.pressure - i32::from(delta.pressure),
+            voc: self.voc - i32::from(delta.voc),
+            timestamp_unix_s: self.timestamp_unix_s - i64::from(delta.timestamp_s),
+            uptime_ms: self.uptime_ms - i64::from(delta.uptime_ms),
+        }
+    }
+
+    fn to_reading(self, flags: u8) -> CompactReading {
+        CompactReading {
+            temperature: (flags & HAS_TEMPERATURE != 0)
+                .then(|| self.temperature as f32 / TEMPERATURE_SCALE),
+            humidity: (flags & HAS_HUMIDITY != 0).then(|| self.humidity as f32 / HUMIDITY_SCALE),
+            pressure: (flags & HAS_PRESSURE != 0).then(|| self.pressure as f32 / PRESSURE_SCALE),
+            voc: (flags & HAS_VOC != 0).then_some(self.voc as u16),
+            time_synced: flags & TIME_SYNCED != 0,
+            timestamp_unix_s: self.timestamp_unix_s,
+            uptime_us: self.uptime_ms * 1000,
+        }
+    }
+}
+
+/// One reading as the change from the previous one, in quantization steps. A missing channel
+/// stores no change and clears its flag.
+#[derive(Clone, Copy)]
+struct Delta {
+    temperature: i16,
+    humidity: i16,
+    pressure: i16,
+    voc: i16,
+    timestamp_s: i32,
+    uptime_ms: u32,
+    flags: u8,
+}
+
+impl Delta {
+    /// A change too large for its field saturates; the caller then advances by what was stored,
+    /// so only that one value is off, never the ones after it.
+    fn between(previous: &Quantized, next: &Quantized, flags: u8) -> Self {
+        let step = |from: i32, to: i32| (to - from).clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+
+        Self {
+            temperature: step(previous.temperature, next.temperature),
+            humidity: step(previous.humidity, next.humidity),
+            pressure: step(previous.pressure, next.pressure),
+            voc: step(previous.voc, next.voc),
+            timestamp_s: (next.timestamp_unix_s - previous.timestamp_unix_s)
+                .clamp(i32::MIN.into(), i32::MAX.into()) as i32,
+            uptime_ms: (next.uptime_ms - previous.uptime_ms).clamp(0, u32::MAX.into()) as u32,
+            flags,
+        }
+    }
+}
+
+fn flags(reading: &CompactReading) -> u8 {
+    [
+        (reading.temperature.is_some(), HAS_TEMPERATURE),
+        (reading.humidity.is_some(), HAS_HUMIDITY),
+        (reading.pressure.is_some(), HAS_PRESSURE),
+        (reading.voc.is_some(), HAS_VOC),
+        (reading.time_synced, TIME_SYNCED),
+    ]
+    .into_iter()
+    .filter(|&(present, _)| present)
+    .fold(0, |flags, (_, flag)| flags | flag)
+}
+
+/// A ring buffer of readings stored as a chain of `Delta`s, ~20 bytes a reading. `last` is the
+/// decoded newest reading: the next one is encoded against it, and decoding walks back from it.
+/// The oldest delta is never applied, so evicting it needs no bookkeeping.
+pub struct DeltaHistory {
+    last: Quantized,
+    deltas: VecDeque<Delta>,
+}
+
+impl DeltaHistory {
+    pub const fn new() -> Self {
+        Self {
+            last: Quantized::ZERO,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Appends `reading`, evicting the oldest one once `capacity` readings are stored.
+    pub fn push(&mut self, reading: &CompactReading, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        if self.deltas.capacity() == 0 {
+            self.deltas.reserve_exact(capacity);
+        }
+        if self.deltas.len() >= capacity {
+            self.deltas.pop_front();
+        }
+
+        let next = Quantized::of(reading, &self.last);
+        let delta = Delta::between(&self.last, &next, flags(reading));
+        self.last = if self.deltas.is_empty() {
+            next
+        } else {
+            // What the decoder will arrive at; differs from `next` only if a step saturated
+            self.last.apply(&delta)
+        };
+        self.deltas.push_back(delta);
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Up to `limit` readings, newest first. Copies only the `limit` newest deltas, so the
+    /// returned iterator can outlive a lock around the history and decodes one reading at a time.
+    pub fn newest_first(&self, limit: usize) -> NewestFirst {
+        NewestFirst {
+            state: self.last,
+            deltas: self
+                .deltas
+                .iter()
+                .rev()
+                .take(limit)
+                .copied()
+                .collect::<Vec<_>>()
+                .into_iter(),
+        }
+    }
+}
+
+impl Default for DeltaHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// See `DeltaHistory::newest_first`.
+pub struct NewestFirst {
+    state: Quantized,
+    deltas: std::vec::IntoIter<Delta>,
+}
+
+impl Iterator for NewestFirst {
+    type Item = CompactReading;
+
+    fn next(&mut self) -> Option<CompactReading> {
+        let delta = self.deltas.next()?;
+        let reading = self.state.to_reading(delta.flags);
+        self.state = self.state.revert(&delta);
+        Some(reading)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.deltas.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(step: u16) -> CompactReading {
+        CompactReading {
+            temperature: Some(21.0 + f32::from(step) * 0.25),
+            humidity: Some(45.0 + f32::from(step) * 0.5),
+            pressure: Some(1013.2 - f32::from(step) * 0.1),
+            voc: Some(100 + step),
+            time_synced: true,
+            timestamp_unix_s: 1_700_000_000 + i64::from(step) * 60,
+            uptime_us: i64::from(step) * 60_000_000,
+        }
+    }
+
+    fn history_of(readings: &[CompactReading], capacity: usize) -> DeltaHistory {
+        let mut history = DeltaHistory::new();
+        for reading in readings {
+            history.push(reading, capacity);
+        }
+        history
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+    }
+
+    fn assert_same(actual: &CompactReading, expected: &CompactReading) {
+        assert_eq!(actual.temperature.is_some(), expected.temperature.is_some());
+        assert_close(
+            actual.temperature.unwrap_or(0.0),
+            expected.temperature.unwrap_or(0.0),
+        );
+        assert_eq!(actual.humidity.is_some(), expected.humidity.is_some());
+        assert_close(
+            actual.humidity.unwrap_or(0.0),
+            expected.humidity.unwrap_or(0.0),
+        );
+        assert_eq!(actual.pressure.is_some(), expected.pressure.is_some());
+        assert_close(
+            actual.pressure.unwrap_or(0.0),
+            expected.pressure.unwrap_or(0.0),
+        );
+        assert_eq!(actual.voc, expected.voc);
+        assert_eq!(actual.time_synced, expected.time_synced);
+        assert_eq!(actual.timestamp_unix_s, expected.timestamp_unix_s);
+        assert_eq!(actual.uptime_us, expected.uptime_us);
+    }
+
+    #[test]
+    fn round_trips_newest_first() {
+        let readings: Vec<_> = (0..10).map(reading).collect();
+        let history = history_of(&readings, 16);
+
+        let decoded: Vec<_> = history.newest_first(usize::MAX).collect();
+        assert_eq!(decoded.len(), readings.len());
+        for (decoded, original) in decoded.iter().zip(readings.iter().rev()) {
+            assert_same(decoded, original);
+        }
+    }
+
+    #[test]
+    fn quantizes_to_its_steps() {
+        let mut original = reading(0);
+        original.temperature = Some(21.234);
+        original.humidity = Some(45.26);
+        original.pressure = Some(1013.24);
+        original.uptime_us = 1_234_567;
+        let history = history_of(&[original], 4);
+
+        let decoded = history.newest_first(1).next().unwrap();
+        assert_close(decoded.temperature.unwrap(), 21.23);
+        assert_close(decoded.humidity.unwrap(), 45.3);
+        assert_close(decoded.pressure.unwrap(), 1013.2);
+        assert_eq!(decoded.uptime_us, 1_234_000);
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let readings: Vec<_> = (0..10).map(reading).collect();
+        let history = history_of(&readings, 16);
+
+        let decoded: Vec<_> = history.newest_first(3).collect();
+        assert_eq!(decoded.len(), 3);
+        assert_same(&decoded[0], &readings[9]);
+        assert_same(&decoded[2], &readings[7]);
+    }
+
+    #[test]
+    fn evicts_oldest_at_capacity() {
+        let readings: Vec<_> = (0..10).map(reading).collect();
+        let history = history_of(&readings, 4);
+        assert_eq!(history.len(), 4);
+
+        let decoded: Vec<_> = history.newest_first(usize::MAX).collect();
+        assert_eq!(decoded.len(), 4);
+        for (decoded, original) in decoded.iter().zip(readings[6..].iter().rev()) {
+            assert_same(decoded, original);
+        }
+    }
+
+    #[test]
+    fn missing_channels_read_as_absent_and_cost_no_change() {
+        let mut gap = reading(1);
+        gap.temperature = None;
+        gap.humidity = None;
+        gap.voc = None;
+        gap.time_synced = false;
+        let readings = [reading(0), gap, reading(2)];
+        let history = history_of(&readings, 4);
+
+        let decoded: Vec<_> = history.newest_first(usize::MAX).collect();
+        assert_same(&decoded[0], &readings[2]);
+        assert_same(&decoded[1], &gap);
+        assert_same(&decoded[2], &readings[0]);
+    }
+
+    #[test]
+    fn saturated_step_only_affects_its_own_reading() {
+        let mut spike = reading(1);
+        // 500 °C in 0.01 °C steps doesn't fit an i16
+        spike.temperature = Some(521.0);
+        let readings = [reading(0), spike, reading(2)];
+        let history = history_of(&readings, 4);
+
+        let decoded: Vec<_> = history.newest_first(usize::MAX).collect();
+        assert_same(&decoded[0], &readings[2]);
+        assert_close(
+            decoded[1].temperature.unwrap(),
+            21.0 + f32::from(i16::MAX) / TEMPERATURE_SCALE,
+        );
+        assert_same(&decoded[2], &readings[0]);
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let history = history_of(&[reading(0)], 0);
+        assert!(history.is_empty());
+        assert_eq!(history.newest_first(usize::MAX).count(), 0);
+    }
+}
@@ -0,0 +1,28 @@
+//! The parts of `smog-rs` that don't touch the hardware or ESP-IDF: schedules, filters, unit
+//! conversions and the like, as plain functions over plain values.
+//!
+//! The firmware reads its compile-time config and the clock and passes them in, so everything
+//! here builds for the host and is covered by `cargo test` (see `just test`).
+
+pub mod atmosphere;
+pub mod change;
+pub mod delivery;
+pub mod filters;
+pub mod gas_index;
+pub mod gzip;
+pub mod health;
+pub mod history;
+pub mod http;
+pub mod onewire;
+#[cfg(feature = "json")]
+pub mod payload;
+pub mod queue;
+pub mod rate_limit;
+pub mod redirect;
+pub mod schedule;
+pub mod sensirion;
+pub mod solar;
+pub mod stats;
+pub mod time;
+pub mod units;
+pub mod watchpoint;
@@ -0,0 +1,83 @@
+/// Length of the DS18B20 scratchpad, CRC byte included.
+pub const SCRATCHPAD_LEN: usize = 9;
+
+/// The temperature register's power-on value, 85 °C. It is what a read returns when no
+/// conversion has completed since the probe (re)powered, e.g. after a brownout on a long cable.
+const POWER_ON_RAW: i16 = 0x0550;
+
+/// Dallas/Maxim CRC-8 (polynomial x^8 + x^5 + x^4 + 1, LSB first).
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |mut crc, &byte| {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+        crc
+    })
+}
+
+/// The temperature in °C held in a DS18B20 scratchpad; `None` if the scratchpad fails its CRC or
+/// holds the 85 °C power-on value. A weather station never measures exactly 85 °C, so that value
+/// is always a conversion that didn't happen.
+pub fn ds18b20_celsius(scratchpad: &[u8; SCRATCHPAD_LEN]) -> Option<f32> {
+    // An idle (pulled-up) bus reads as all ones, which a CRC of 0xFF would otherwise accept
+    let valid = scratchpad.iter().any(|&byte| byte != 0xFF)
+        && crc8(&scratchpad[..SCRATCHPAD_LEN - 1]) == scratchpad[SCRATCHPAD_LEN - 1];
+    let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]);
+
+    (valid && raw != POWER_ON_RAW).then(|| f32::from(raw) / 16.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratchpad holding `raw`, with the default config bytes and a valid CRC.
+    fn scratchpad(raw: i16) -> [u8; SCRATCHPAD_LEN] {
+        let [low, high] = raw.to_le_bytes();
+        let mut scratchpad = [low, high, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0];
+        scratchpad[SCRATCHPAD_LEN - 1] = crc8(&scratchpad[..SCRATCHPAD_LEN - 1]);
+        scratchpad
+    }
+
+    #[test]
+    fn crc_matches_the_power_on_scratchpad() {
+        assert_eq!(
+            crc8(&[0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10]),
+            0x1C
+        );
+    }
+
+    #[test]
+    fn decodes_the_datasheet_temperatures() {
+        assert_eq!(ds18b20_celsius(&scratchpad(0x0191)), Some(25.0625));
+        assert_eq!(ds18b20_celsius(&scratchpad(0x0008)), Some(0.5));
+        assert_eq!(ds18b20_celsius(&scratchpad(0x0000)), Some(0.0));
+        assert_eq!(
+            ds18b20_celsius(&scratchpad(0xFF5Eu16 as i16)),
+            Some(-10.125)
+        );
+        assert_eq!(ds18b20_celsius(&scratchpad(0xFC90u16 as i16)), Some(-55.0));
+    }
+
+    #[test]
+    fn discards_the_power_on_value() {
+        let power_on = [0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0x1C];
+        assert_eq!(ds18b20_celsius(&power_on), None);
+        assert_eq!(ds18b20_celsius(&scratchpad(0x0551)), Some(85.0625));
+    }
+
+    #[test]
+    fn rejects_a_bad_crc_and_an_idle_bus() {
+        let mut corrupted = scratchpad(0x0191);
+        corrupted[0] ^= 0x01;
+        assert_eq!(ds18b20_celsius(&corrupted), None);
+
+        assert_eq!(ds18b20_celsius(&[0xFF; SCRATCHPAD_LEN]), None);
+    }
+}
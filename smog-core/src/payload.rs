@@ -0,0 +1,256 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// `PAYLOAD_ENVELOPE`: device metadata kept apart from the measurements, as
+/// `{"meta": {...}, "data": {...}}` with `data` being the usual flat object.
+#[derive(Serialize)]
+pub struct Envelope<'a, T> {
+    pub meta: Meta,
+    pub data: &'a T,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-keys", serde(rename_all = "camelCase"))]
+pub struct Meta {
+    pub device_id: &'static str,
+    pub fw_version: &'static str,
+    pub seq: u32,
+    /// When the sample was taken, not when it is sent.
+    pub uptime_s: i64,
+}
+
+/// `PAYLOAD_FORMAT=ndjson`: one `line` per sample, every line `\n`-terminated (a single sample is
+/// a one-line batch). `line` must not emit newlines itself, which compact JSON never does.
+pub fn to_ndjson<T, E>(
+    batch: &[T],
+    mut line: impl FnMut(&T) -> Result<Vec<u8>, E>,
+) -> Result<Vec<u8>, E> {
+    let mut body = Vec::new();
+    for sample in batch {
+        body.extend_from_slice(&line(sample)?);
+        body.push(b'\n');
+    }
+    Ok(body)
+}
+
+/// `PAYLOAD_FORMAT=form`: an `application/x-www-form-urlencoded` body with the same keys as the
+/// JSON payload, e.g. `temperature=-3.5&timestamp_unix_s=1736376930`. Nested objects are
+/// flattened into dotted keys (`labels.location=attic`, `stats.temperature.p90=21.5`), arrays by
+/// index, and absent values are left out rather than sent empty.
+///
+/// The value goes through its JSON text: `serde_json::to_value` widens `f32`s to `f64`, which
+/// would send a humidity of `45.1` as `45.099998474121094`.
+pub fn to_form_urlencoded<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    let value: Value = serde_json::from_slice(&serde_json::to_vec(value)?)?;
+    let mut pairs = Vec::new();
+    push_form_fields("", &value, &mut pairs);
+    Ok(pairs.join("&"))
+}
+
+fn push_form_fields(key: &str, value: &Value, pairs: &mut Vec<String>) {
+    let nested = |name: &str| {
+        if key.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", key, name)
+        }
+    };
+
+    match value {
+        Value::Null => {}
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                push_form_fields(&nested(name), field, pairs);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                push_form_fields(&nested(&index.to_string()), item, pairs);
+            }
+        }
+        Value::String(text) => {
+            pairs.push(format!("{}={}", form_urlencode(key), form_urlencode(text)))
+        }
+        other => pairs.push(format!(
+            "{}={}",
+            form_urlencode(key),
+            form_urlencode(&other.to_string())
+        )),
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters; spaces become `+`.
+fn form_urlencode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        temperature: f32,
+        voc: Option<u16>,
+    }
+
+    fn key(snake_case: &'static str, camel_case: &'static str) -> &'static str {
+        if cfg!(feature = "camel-case-keys") {
+            camel_case
+        } else {
+            snake_case
+        }
+    }
+
+    #[test]
+    fn envelope_separates_meta_from_data() {
+        let envelope = Envelope {
+            meta: Meta {
+                device_id: "smog-a1b2c3",
+                fw_version: "0.1.0",
+                seq: 7,
+                uptime_s: 42,
+            },
+            data: &Sample {
+                temperature: 21.5,
+                voc: Some(100),
+            },
+        };
+        let json = serde_json::to_value(&envelope).unwrap();
+
+        assert_eq!(json.as_object().unwrap().len(), 2);
+
+        let meta = &json["meta"];
+        assert_eq!(meta.as_object().unwrap().len(), 4);
+        assert_eq!(meta[key("device_id", "deviceId")], "smog-a1b2c3");
+        assert_eq!(meta[key("fw_version", "fwVersion")], "0.1.0");
+        assert_eq!(meta["seq"], 7);
+        assert_eq!(meta[key("uptime_s", "uptimeS")], 42);
+
+        // The data object is the sample as is, not flattened into the envelope
+        assert_eq!(json["data"], serde_json::to_value(envelope.data).unwrap());
+        assert_eq!(json["data"]["temperature"], 21.5);
+    }
+
+    #[test]
+    fn ndjson_is_one_sample_per_line() {
+        let batch = [
+            Sample {
+                temperature: 21.5,
+                voc: Some(100),
+            },
+            Sample {
+                temperature: -3.25,
+                voc: None,
+            },
+            Sample {
+                temperature: 0.0,
+                voc: Some(1),
+            },
+        ];
+        let body = String::from_utf8(to_ndjson(&batch, serde_json::to_vec).unwrap()).unwrap();
+
+        assert!(body.ends_with('\n'));
+        let lines: Vec<_> = body.lines().collect();
+        assert_eq!(lines.len(), batch.len());
+        for (line, sample) in lines.iter().zip(&batch) {
+            let parsed: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(parsed, serde_json::to_value(sample).unwrap());
+        }
+    }
+
+    #[test]
+    fn ndjson_of_one_sample_is_one_terminated_line() {
+        let sample = Sample {
+            temperature: 21.5,
+            voc: None,
+        };
+        let body = to_ndjson(std::slice::from_ref(&sample), serde_json::to_vec).unwrap();
+
+        assert_eq!(body, b"{\"temperature\":21.5,\"voc\":null}\n");
+    }
+
+    #[test]
+    fn ndjson_stops_at_the_first_failing_sample() {
+        let mut calls = 0;
+        let result = to_ndjson(&[1, 2, 3], |&n| {
+            calls += 1;
+            if n == 2 { Err(n) } else { Ok(vec![b'0' + n]) }
+        });
+
+        assert_eq!(result, Err(2));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn form_keeps_the_f32_digits() {
+        let sample = Sample {
+            temperature: 45.1,
+            voc: None,
+        };
+
+        assert_eq!(to_form_urlencoded(&sample).unwrap(), "temperature=45.1");
+    }
+
+    #[test]
+    fn form_flattens_nested_objects() {
+        #[derive(Serialize)]
+        struct Percentiles {
+            p50: f32,
+            max: f32,
+        }
+
+        #[derive(Serialize)]
+        struct Nested {
+            humidity: f32,
+            labels: std::collections::BTreeMap<&'static str, &'static str>,
+            stats: Option<Percentiles>,
+            pressure: Option<f32>,
+        }
+
+        let sample = Nested {
+            humidity: 61.3,
+            labels: [("location", "attic"), ("room", "kid's room")].into(),
+            stats: Some(Percentiles {
+                p50: 20.5,
+                max: 22.7,
+            }),
+            pressure: None,
+        };
+
+        assert_eq!(
+            to_form_urlencoded(&sample).unwrap(),
+            "humidity=61.3&labels.location=attic&labels.room=kid%27s+room\
+             &stats.max=22.7&stats.p50=20.5"
+        );
+    }
+
+    #[test]
+    fn form_flattens_arrays_by_index_and_encodes_keys() {
+        #[derive(Serialize)]
+        struct Listed {
+            #[serde(rename = "a&b")]
+            values: [u16; 2],
+            unit: &'static str,
+        }
+
+        assert_eq!(
+            to_form_urlencoded(&Listed {
+                values: [1, 2],
+                unit: "°C",
+            })
+            .unwrap(),
+            "a%26b.0=1&a%26b.1=2&unit=%C2%B0C"
+        );
+    }
+}
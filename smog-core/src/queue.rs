@@ -0,0 +1,111 @@
+/// What happens to samples when a transport channel is full.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelFullPolicy {
+    /// Keep the queued samples and discard the new one.
+    DropNewest,
+    /// Evict the oldest queued sample to make room for the new one.
+    DropOldest,
+}
+
+/// Outcome of [`push_with_policy`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pushed {
+    /// There was room for the new item.
+    Queued,
+    /// The queue was full; the oldest item was discarded and the new one queued.
+    EvictedOldest,
+    /// The queue was full and the new item was discarded.
+    Dropped,
+}
+
+/// Queues `item` without blocking, applying `policy` when the queue is full. The queue itself is
+/// reached through `try_push`, which hands the item back when full, and `pop_oldest`.
+pub fn push_with_policy<T>(
+    item: T,
+    policy: ChannelFullPolicy,
+    mut try_push: impl FnMut(T) -> Result<(), T>,
+    mut pop_oldest: impl FnMut() -> Option<T>,
+) -> Pushed {
+    let Err(item) = try_push(item) else {
+        return Pushed::Queued;
+    };
+
+    match policy {
+        ChannelFullPolicy::DropNewest => Pushed::Dropped,
+        ChannelFullPolicy::DropOldest => {
+            // Make room by discarding the stalest item; the freshest reading matters most
+            let _ = pop_oldest();
+            match try_push(item) {
+                Ok(()) => Pushed::EvictedOldest,
+                // Another producer took the freed slot first
+                Err(_) => Pushed::Dropped,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    const CAPACITY: usize = 2;
+
+    fn push(queue: &RefCell<VecDeque<u32>>, item: u32, policy: ChannelFullPolicy) -> Pushed {
+        push_with_policy(
+            item,
+            policy,
+            |item| {
+                let mut queue = queue.borrow_mut();
+                if queue.len() < CAPACITY {
+                    queue.push_back(item);
+                    Ok(())
+                } else {
+                    Err(item)
+                }
+            },
+            || queue.borrow_mut().pop_front(),
+        )
+    }
+
+    #[test]
+    fn queues_while_there_is_room() {
+        for policy in [ChannelFullPolicy::DropNewest, ChannelFullPolicy::DropOldest] {
+            let queue = RefCell::new(VecDeque::new());
+            assert_eq!(push(&queue, 1, policy), Pushed::Queued);
+            assert_eq!(push(&queue, 2, policy), Pushed::Queued);
+            assert_eq!(queue.into_inner(), [1, 2]);
+        }
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_queued_items() {
+        let queue = RefCell::new(VecDeque::from([1, 2]));
+        assert_eq!(
+            push(&queue, 3, ChannelFullPolicy::DropNewest),
+            Pushed::Dropped
+        );
+        assert_eq!(queue.into_inner(), [1, 2]);
+    }
+
+    #[test]
+    fn drop_oldest_evicts_the_front_and_keeps_the_order() {
+        let queue = RefCell::new(VecDeque::from([1, 2]));
+        assert_eq!(
+            push(&queue, 3, ChannelFullPolicy::DropOldest),
+            Pushed::EvictedOldest
+        );
+        assert_eq!(
+            push(&queue, 4, ChannelFullPolicy::DropOldest),
+            Pushed::EvictedOldest
+        );
+        assert_eq!(queue.into_inner(), [3, 4]);
+    }
+
+    #[test]
+    fn drop_oldest_drops_when_the_freed_slot_is_taken() {
+        let pushed = push_with_policy(1, ChannelFullPolicy::DropOldest, Err, || Some(0));
+        assert_eq!(pushed, Pushed::Dropped);
+    }
+}
@@ -0,0 +1,107 @@
+/// Starts full with `capacity` tokens and earns one back every `refill_ms`, up to `capacity`.
+/// Time spent full earns nothing, so a burst is never larger than `capacity`.
+pub struct TokenBucket {
+    capacity: u32,
+    refill_ms: u64,
+    tokens: u32,
+    /// When the last token was earned, or the bucket stopped being full.
+    last_refill_ms: u64,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, refill_ms: u64, now_ms: u64) -> Self {
+        Self {
+            capacity,
+            refill_ms: refill_ms.max(1),
+            tokens: capacity,
+            last_refill_ms: now_ms,
+        }
+    }
+
+    /// Takes a token at `now_ms`; `false` if the bucket is empty.
+    pub fn try_take(&mut self, now_ms: u64) -> bool {
+        self.refill(now_ms);
+        if self.tokens == 0 {
+            return false;
+        }
+
+        if self.tokens == self.capacity {
+            self.last_refill_ms = now_ms;
+        }
+        self.tokens -= 1;
+        true
+    }
+
+    fn refill(&mut self, now_ms: u64) {
+        let earned = now_ms.saturating_sub(self.last_refill_ms) / self.refill_ms;
+        if earned == 0 {
+            return;
+        }
+
+        let missing = u64::from(self.capacity - self.tokens);
+        if earned >= missing {
+            self.tokens = self.capacity;
+        } else {
+            self.tokens += earned as u32;
+            // Keep the time towards the next token
+            self.last_refill_ms += earned * self.refill_ms;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_burst_drains_the_bucket() {
+        let mut bucket = TokenBucket::new(3, 1000, 0);
+
+        assert!(bucket.try_take(0));
+        assert!(bucket.try_take(0));
+        assert!(bucket.try_take(10));
+        assert!(!bucket.try_take(20));
+    }
+
+    #[test]
+    fn earns_one_token_per_refill_period() {
+        let mut bucket = TokenBucket::new(2, 1000, 0);
+        assert!(bucket.try_take(0));
+        assert!(bucket.try_take(0));
+
+        assert!(!bucket.try_take(999));
+        assert!(bucket.try_take(1000));
+        assert!(!bucket.try_take(1500));
+        // The remainder towards the next token is kept
+        assert!(bucket.try_take(2000));
+    }
+
+    #[test]
+    fn refills_no_further_than_the_capacity() {
+        let mut bucket = TokenBucket::new(2, 1000, 0);
+        assert!(bucket.try_take(0));
+        assert!(bucket.try_take(0));
+
+        let later = 60_000;
+        assert!(bucket.try_take(later));
+        assert!(bucket.try_take(later));
+        assert!(!bucket.try_take(later));
+    }
+
+    #[test]
+    fn time_spent_full_earns_nothing() {
+        let mut bucket = TokenBucket::new(1, 1000, 0);
+
+        // Full for a long while, then the first take starts the refill clock
+        assert!(bucket.try_take(10_000));
+        assert!(!bucket.try_take(10_999));
+        assert!(bucket.try_take(11_000));
+    }
+
+    #[test]
+    fn a_zero_capacity_never_allows() {
+        let mut bucket = TokenBucket::new(0, 1000, 0);
+        assert!(!bucket.try_take(0));
+        assert!(!bucket.try_take(60_000));
+    }
+}
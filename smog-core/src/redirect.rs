@@ -0,0 +1,159 @@
+/// `Location` may be absolute, scheme-relative (`//host/path`), absolute-path (`/path`) or
+/// relative to the directory of `base`.
+pub fn resolve_location(base: &str, location: &str) -> String {
+    if has_prefix_ignore_case(location, "http://") || has_prefix_ignore_case(location, "https://") {
+        return location.to_string();
+    }
+
+    let (scheme, rest) = base.split_once("://").unwrap_or(("http", base));
+    if let Some(authority_and_path) = location.strip_prefix("//") {
+        return format!("{}://{}", scheme, authority_and_path);
+    }
+
+    let path_start = rest.find('/').unwrap_or(rest.len());
+    let origin = format!("{}://{}", scheme, &rest[..path_start]);
+    if location.starts_with('/') {
+        return format!("{}{}", origin, location);
+    }
+
+    let path = rest[path_start..].split(['?', '#']).next().unwrap_or("");
+    let directory = path.rfind('/').map_or("/", |slash| &path[..=slash]);
+    format!("{}{}{}", origin, directory, location)
+}
+
+/// Why a redirect is not followed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RedirectRefusal {
+    /// `https://` to `http://`: the body and headers would go out in the clear.
+    Downgrade,
+    /// To another host, which would be handed the signature headers and the client certificate.
+    CrossHost,
+}
+
+/// Checks a redirect from `from` to `to` (both absolute, see `resolve_location`). Moving to
+/// another path, port or from `http://` to `https://` is fine.
+pub fn check_redirect(from: &str, to: &str) -> Result<(), RedirectRefusal> {
+    if is_https(from) && !is_https(to) {
+        return Err(RedirectRefusal::Downgrade);
+    }
+    if !host(from).eq_ignore_ascii_case(host(to)) {
+        return Err(RedirectRefusal::CrossHost);
+    }
+    Ok(())
+}
+
+fn is_https(url: &str) -> bool {
+    has_prefix_ignore_case(url, "https://")
+}
+
+/// URL schemes are case-insensitive: `HTTPS://` is as absolute as `https://`.
+fn has_prefix_ignore_case(url: &str, prefix: &str) -> bool {
+    url.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// The host of an absolute URL, without user info or port; IPv6 literals keep their brackets.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host_and_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+
+    if host_and_port.starts_with('[') {
+        return host_and_port
+            .find(']')
+            .map_or(host_and_port, |end| &host_and_port[..=end]);
+    }
+    host_and_port
+        .split_once(':')
+        .map_or(host_and_port, |(host, _)| host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "https://api.example.com/v1/ingest?key=1";
+
+    #[test]
+    fn resolves_every_location_form() {
+        assert_eq!(
+            resolve_location(BASE, "https://eu.example.com/in"),
+            "https://eu.example.com/in"
+        );
+        assert_eq!(
+            resolve_location(BASE, "//eu.example.com/in"),
+            "https://eu.example.com/in"
+        );
+        assert_eq!(
+            resolve_location(BASE, "/v2/ingest"),
+            "https://api.example.com/v2/ingest"
+        );
+        assert_eq!(
+            resolve_location(BASE, "batch"),
+            "https://api.example.com/v1/batch"
+        );
+        assert_eq!(
+            resolve_location("http://host:8080", "in"),
+            "http://host:8080/in"
+        );
+    }
+
+    #[test]
+    fn upper_case_schemes_are_absolute() {
+        assert_eq!(
+            resolve_location(BASE, "HTTPS://eu.example.com/in"),
+            "HTTPS://eu.example.com/in"
+        );
+        assert_eq!(
+            resolve_location(BASE, "Http://api.example.com/v1"),
+            "Http://api.example.com/v1"
+        );
+    }
+
+    #[test]
+    fn same_host_redirects_are_followed() {
+        assert_eq!(check_redirect(BASE, "https://api.example.com/v2"), Ok(()));
+        assert_eq!(
+            check_redirect(BASE, "https://API.example.com:8443/v2"),
+            Ok(())
+        );
+        assert_eq!(
+            check_redirect("http://api.example.com/in", "https://api.example.com/in"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn downgrades_are_refused() {
+        assert_eq!(
+            check_redirect(BASE, "http://api.example.com/v1/ingest"),
+            Err(RedirectRefusal::Downgrade)
+        );
+        assert_eq!(
+            check_redirect("HTTPS://api.example.com/", "http://api.example.com/"),
+            Err(RedirectRefusal::Downgrade)
+        );
+    }
+
+    #[test]
+    fn other_hosts_are_refused() {
+        assert_eq!(
+            check_redirect(BASE, "https://eu.example.com/v1/ingest"),
+            Err(RedirectRefusal::CrossHost)
+        );
+        assert_eq!(
+            check_redirect(BASE, "https://api.example.com@evil.example/"),
+            Err(RedirectRefusal::CrossHost)
+        );
+        assert_eq!(
+            check_redirect("http://[::1]:8080/in", "http://[::2]:8080/in"),
+            Err(RedirectRefusal::CrossHost)
+        );
+        assert_eq!(
+            check_redirect("http://[::1]:8080/in", "http://[::1]/in"),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use crate::change::{ChangeDeltas, Reading, change_in_deltas, has_significant_change};
+
+/// How a transport decides whether a sample is due, following the runtime `SendMode`.
+#[derive(Clone, Copy, Debug)]
+pub enum SendPolicy {
+    /// Every `interval_ms`.
+    Interval,
+    /// At most every `interval_ms`, and only if a field moved past its delta since the last
+    /// sample sent, or `heartbeat_ms` passed without a send.
+    OnChange {
+        heartbeat_ms: u64,
+        deltas: ChangeDeltas,
+    },
+    /// Never.
+    Paused,
+}
+
+/// Decides whether `current` should be queued for a transport sending every `interval_ms`, which
+/// last sent `last_sent` `since_last_send_ms` ago.
+pub fn is_send_due(
+    policy: SendPolicy,
+    interval_ms: u64,
+    since_last_send_ms: u64,
+    last_sent: Option<&Reading>,
+    current: &Reading,
+) -> bool {
+    match policy {
+        SendPolicy::Interval => since_last_send_ms >= interval_ms,
+        SendPolicy::OnChange {
+            heartbeat_ms,
+            deltas,
+        } => {
+            since_last_send_ms >= interval_ms
+                && (since_last_send_ms >= heartbeat_ms
+                    || last_sent.is_none_or(|last| has_significant_change(last, current, &deltas)))
+        }
+        SendPolicy::Paused => false,
+    }
+}
+
+/// The sinks samples are queued for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sink {
+    Http,
+    Mqtt,
+}
+
+/// The sink whose sends close the interval that the accumulated values (rain total, VOC average,
+/// interval stats) cover: HTTP while it is sending, MQTT otherwise. Only one can, at different
+/// cadences the faster would cut the slower one's intervals short; samples for the other sink
+/// carry the values accumulated so far.
+pub fn interval_owner(http_sending: bool) -> Sink {
+    if http_sending { Sink::Http } else { Sink::Mqtt }
+}
+
+/// The send schedule of one sink: when it was last handed a sample, which one, and the `seq` of
+/// the next, so each sink runs at its own cadence off the same readings and numbers its samples
+/// without the gaps its consumer would read as losses. Times are ms since boot.
+pub struct SinkSchedule {
+    last_send_ms: u64,
+    last_sent: Option<Reading>,
+    seq: u32,
+}
+
+impl SinkSchedule {
+    /// Starts as if a sample had been sent at `now_ms`, so the first is due an interval later.
+    pub fn new(now_ms: u64) -> Self {
+        Self {
+            last_send_ms: now_ms,
+            last_sent: None,
+            seq: 0,
+        }
+    }
+
+    /// Whether `current`, read at `now_ms`, should go to this sink, sending every `interval_ms`
+    /// and running as if the last send happened `jitter_ms` later. Alerts are always due.
+    pub fn is_due(
+        &self,
+        policy: SendPolicy,
+        interval_ms: u64,
+        jitter_ms: u64,
+        now_ms: u64,
+        current: &Reading,
+        alert: bool,
+    ) -> bool {
+        let jittered_send_ms = self.last_send_ms.saturating_add(jitter_ms);
+
+        alert
+            || (now_ms >= jittered_send_ms
+                && is_send_due(
+                    policy,
+                    interval_ms,
+                    now_ms - jittered_send_ms,
+                    self.last_sent.as_ref(),
+                    current,
+                ))
+    }
+
+    /// The `seq` of the next sample handed to this sink.
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// A sample was handed to this sink at `now_ms`.
+    pub fn mark_sent(&mut self, now_ms: u64, reading: Reading) {
+        self.restart(now_ms);
+        self.last_sent = Some(reading);
+        self.seq = self.seq.wrapping_add(1);
+    }
+
+    /// Starts the next interval at `now_ms` without a sample having been sent.
+    pub fn restart(&mut self, now_ms: u64) {
+        self.last_send_ms = now_ms;
+    }
+}
+
+/// Loop delay that doubles on every consecutive failure, up to `max_ms`, and snaps back to the
+/// base delay on success.
+pub struct AdaptiveDelay {
+    base_ms: u64,
+    current_ms: u64,
+    max_ms: u64,
+}
+
+impl AdaptiveDelay {
+    pub fn new(base_ms: u64, max_ms: u64) -> Self {
+        Self {
+            base_ms,
+            current_ms: base_ms,
+            max_ms: max_ms.max(base_ms),
+        }
+    }
+
+    /// Follows a retuned base delay without losing a backoff that is in progress.
+    pub fn set_base(&mut self, base_ms: u64) {
+        let backing_off = self.current_ms != self.base_ms;
+        self.base_ms = base_ms;
+        self.current_ms = if backing_off {
+            self.current_ms.clamp(base_ms, self.max_ms.max(base_ms))
+        } else {
+            base_ms
+        };
+    }
+
+    pub fn on_success(&mut self) {
+        self.current_ms = self.base_ms;
+    }
+
+    pub fn on_failure(&mut self) {
+        self.current_ms = self
+            .current_ms
+            .saturating_mul(2)
+            .min(self.max_ms.max(self.base_ms));
+    }
+
+    pub fn current(&self) -> u64 {
+        self.current_ms
+    }
+}
+
+/// A send interval that follows the rate of change of the readings. Every `window_ms` the latest
+/// reading is compared with the one that opened the window; a fast-moving window shrinks the
+/// interval right away, a calm one lets it grow back by at most a doubling per window, so a short
+/// lull in a transient doesn't drop straight to the max.
+pub struct AdaptiveInterval {
+    min_ms: u64,
+    max_ms: u64,
+    window_ms: u64,
+    /// Scales the measured rate: at `1.0` one delta per minute already means `min_ms`.
+    sensitivity: f32,
+    deltas: ChangeDeltas,
+    current_ms: u64,
+    window_start: Option<(u64, Reading)>,
+}
+
+impl AdaptiveInterval {
+    pub fn new(
+        (min_ms, max_ms): (u64, u64),
+        initial_ms: u64,
+        window_ms: u64,
+        sensitivity: f32,
+        deltas: ChangeDeltas,
+    ) -> Self {
+        let max_ms = max_ms.max(min_ms);
+        Self {
+            min_ms,
+            max_ms,
+            window_ms,
+            sensitivity,
+            deltas,
+            current_ms: initial_ms.clamp(min_ms, max_ms),
+            window_start: None,
+        }
+    }
+
+    /// Feeds a reading taken at `now_ms`. Returns the measured rate (deltas per minute) when the
+    /// interval changed.
+    pub fn observe(&mut self, now_ms: u64, reading: Reading) -> Option<f32> {
+        let Some((started_ms, first)) = &self.window_start else {
+            self.window_start = Some((now_ms, reading));
+            return None;
+        };
+        let elapsed_ms = now_ms.saturating_sub(*started_ms);
+        if elapsed_ms < self.window_ms.max(1) {
+            return None;
+        }
+
+        let rate = change_in_deltas(first, &reading, &self.deltas) * 60_000.0 / elapsed_ms as f32;
+        let target_ms = interval_for_rate(self.min_ms, self.max_ms, rate * self.sensitivity);
+        let next_ms = if target_ms < self.current_ms {
+            target_ms
+        } else {
+            target_ms.min(self.current_ms.saturating_mul(2))
+        };
+        self.window_start = Some((now_ms, reading));
+
+        (next_ms != self.current_ms).then(|| {
+            self.current_ms = next_ms;
+            rate
+        })
+    }
+
+    pub fn current(&self) -> u64 {
+        self.current_ms
+    }
+}
+
+/// Linear between `max_ms` at rest and `min_ms` from a (scaled) rate of one delta a minute.
+pub fn interval_for_rate(min_ms: u64, max_ms: u64, rate: f32) -> u64 {
+    let speed = rate.clamp(0.0, 1.0);
+    max_ms - ((max_ms - min_ms) as f32 * speed) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::change::tests::{DELTAS, READING};
+
+    const ON_CHANGE: SendPolicy = SendPolicy::OnChange {
+        heartbeat_ms: 600_000,
+        deltas: DELTAS,
+    };
+
+    /// `is_send_due` for a one-minute interval.
+    fn due(policy: SendPolicy, since_ms: u64, last: Option<&Reading>, current: &Reading) -> bool {
+        is_send_due(policy, 60_000, since_ms, last, current)
+    }
+
+    #[test]
+    fn interval_mode_only_waits_for_the_interval() {
+        assert!(!due(SendPolicy::Interval, 59_999, None, &READING));
+        assert!(due(SendPolicy::Interval, 60_000, Some(&READING), &READING));
+    }
+
+    #[test]
+    fn paused_mode_is_never_due() {
+        assert!(!due(SendPolicy::Paused, u64::MAX, None, &READING));
+    }
+
+    #[test]
+    fn on_change_skips_unchanged_samples_until_the_heartbeat() {
+        assert!(!due(ON_CHANGE, 60_000, Some(&READING), &READING));
+        assert!(!due(ON_CHANGE, 599_999, Some(&READING), &READING));
+        assert!(due(ON_CHANGE, 600_000, Some(&READING), &READING));
+    }
+
+    #[test]
+    fn on_change_sends_a_change_once_the_interval_passed() {
+        let changed = Reading {
+            temperature: READING.temperature.map(|t| t + 0.5),
+            ..READING
+        };
+        assert!(!due(ON_CHANGE, 30_000, Some(&READING), &changed));
+        assert!(due(ON_CHANGE, 60_000, Some(&READING), &changed));
+    }
+
+    #[test]
+    fn on_change_sends_the_first_sample() {
+        assert!(due(ON_CHANGE, 60_000, None, &READING));
+    }
+
+    /// The `(time, seq)` of each sink's sends, and when the interval accumulators were reset.
+    #[derive(Default)]
+    struct Sends {
+        http: Vec<(u64, u32)>,
+        mqtt: Vec<(u64, u32)>,
+        resets: Vec<u64>,
+    }
+
+    /// Runs an HTTP and an MQTT schedule off the same readings, one every 10 s for `duration_ms`,
+    /// the way `sensor_task` does.
+    fn run_sinks(http_interval_ms: Option<u64>, mqtt_interval_ms: u64, duration_ms: u64) -> Sends {
+        let mut http = SinkSchedule::new(0);
+        let mut mqtt = SinkSchedule::new(0);
+        let mut sends = Sends::default();
+
+        for now_ms in (10_000..=duration_ms).step_by(10_000) {
+            let http_due = http_interval_ms.is_some_and(|interval_ms| {
+                http.is_due(
+                    SendPolicy::Interval,
+                    interval_ms,
+                    0,
+                    now_ms,
+                    &READING,
+                    false,
+                )
+            });
+            let mqtt_due = mqtt.is_due(
+                SendPolicy::Interval,
+                mqtt_interval_ms,
+                0,
+                now_ms,
+                &READING,
+                false,
+            );
+
+            if http_due {
+                sends.http.push((now_ms, http.seq()));
+                http.mark_sent(now_ms, READING);
+            }
+            if mqtt_due {
+                sends.mqtt.push((now_ms, mqtt.seq()));
+                mqtt.mark_sent(now_ms, READING);
+            }
+            let interval_done = match interval_owner(http_interval_ms.is_some()) {
+                Sink::Http => http_due,
+                Sink::Mqtt => mqtt_due,
+            };
+            if interval_done {
+                sends.resets.push(now_ms);
+            }
+        }
+        sends
+    }
+
+    fn times(sent: &[(u64, u32)]) -> Vec<u64> {
+        sent.iter().map(|&(time, _)| time).collect()
+    }
+
+    #[test]
+    fn sinks_keep_their_own_cadence_and_seq() {
+        let sends = run_sinks(Some(60_000), 20_000, 120_000);
+
+        assert_eq!(sends.http, [(60_000, 0), (120_000, 1)]);
+        assert_eq!(
+            sends.mqtt,
+            [
+                (20_000, 0),
+                (40_000, 1),
+                (60_000, 2),
+                (80_000, 3),
+                (100_000, 4),
+                (120_000, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn http_owns_the_interval_while_sending() {
+        let sends = run_sinks(Some(60_000), 20_000, 180_000);
+
+        assert_eq!(sends.resets, times(&sends.http));
+    }
+
+    #[test]
+    fn mqtt_owns_the_interval_without_http() {
+        let sends = run_sinks(None, 30_000, 90_000);
+
+        assert!(sends.http.is_empty());
+        assert_eq!(sends.resets, times(&sends.mqtt));
+    }
+
+    #[test]
+    fn jitter_holds_back_the_first_send() {
+        let schedule = SinkSchedule::new(0);
+        let due =
+            |now_ms| schedule.is_due(SendPolicy::Interval, 60_000, 5_000, now_ms, &READING, false);
+
+        assert!(!due(60_000));
+        assert!(!due(64_999));
+        assert!(due(65_000));
+    }
+
+    #[test]
+    fn alerts_are_always_due() {
+        let schedule = SinkSchedule::new(0);
+
+        assert!(schedule.is_due(SendPolicy::Paused, 60_000, 0, 1, &READING, true));
+    }
+
+    #[test]
+    fn restart_delays_without_consuming_a_seq() {
+        let mut schedule = SinkSchedule::new(0);
+        schedule.restart(60_000);
+
+        assert!(!schedule.is_due(SendPolicy::Interval, 60_000, 0, 90_000, &READING, false));
+        assert!(schedule.is_due(SendPolicy::Interval, 60_000, 0, 120_000, &READING, false));
+        assert_eq!(schedule.seq(), 0);
+    }
+
+    #[test]
+    fn on_change_compares_against_the_last_sample_of_its_own_sink() {
+        let mut schedule = SinkSchedule::new(0);
+        schedule.mark_sent(0, READING);
+        let changed = Reading {
+            temperature: READING.temperature.map(|t| t + 0.5),
+            ..READING
+        };
+
+        assert!(!schedule.is_due(ON_CHANGE, 60_000, 0, 60_000, &READING, false));
+        assert!(schedule.is_due(ON_CHANGE, 60_000, 0, 60_000, &changed, false));
+    }
+
+    #[test]
+    fn delay_doubles_on_failure_up_to_the_cap() {
+        let mut delay = AdaptiveDelay::new(1000, 5000);
+        let mut delays = Vec::new();
+        for _ in 0..4 {
+            delay.on_failure();
+            delays.push(delay.current());
+        }
+        assert_eq!(delays, [2000, 4000, 5000, 5000]);
+    }
+
+    #[test]
+    fn delay_snaps_back_on_success() {
+        let mut delay = AdaptiveDelay::new(1000, 30_000);
+        delay.on_failure();
+        delay.on_failure();
+        delay.on_success();
+        assert_eq!(delay.current(), 1000);
+    }
+
+    #[test]
+    fn new_base_applies_at_once_when_not_backing_off() {
+        let mut delay = AdaptiveDelay::new(1000, 30_000);
+        delay.set_base(2500);
+        assert_eq!(delay.current(), 2500);
+    }
+
+    #[test]
+    fn new_base_keeps_a_backoff_in_progress() {
+        let mut delay = AdaptiveDelay::new(1000, 30_000);
+        delay.on_failure();
+        delay.on_failure();
+        delay.set_base(2000);
+        assert_eq!(delay.current(), 4000);
+        delay.set_base(8000);
+        assert_eq!(delay.current(), 8000);
+        delay.on_success();
+        assert_eq!(delay.current(), 8000);
+    }
+
+    #[test]
+    fn cap_below_the_base_is_raised_to_it() {
+        let mut delay = AdaptiveDelay::new(5000, 1000);
+        delay.on_failure();
+        assert_eq!(delay.current(), 5000);
+    }
+
+    #[test]
+    fn interval_for_rate_is_linear_between_the_bounds() {
+        assert_eq!(interval_for_rate(10_000, 110_000, 0.0), 110_000);
+        assert_eq!(interval_for_rate(10_000, 110_000, 0.5), 60_000);
+        assert_eq!(interval_for_rate(10_000, 110_000, 1.0), 10_000);
+        assert_eq!(interval_for_rate(10_000, 110_000, 4.0), 10_000);
+        assert_eq!(interval_for_rate(10_000, 110_000, -1.0), 110_000);
+    }
+
+    fn adaptive(initial_ms: u64) -> AdaptiveInterval {
+        AdaptiveInterval::new((10_000, 160_000), initial_ms, 60_000, 1.0, DELTAS)
+    }
+
+    /// `READING` with the temperature moved by `deltas` temperature deltas.
+    fn warmer(deltas: f32) -> Reading {
+        Reading {
+            temperature: READING.temperature.map(|t| t + deltas * DELTAS.temperature),
+            ..READING
+        }
+    }
+
+    #[test]
+    fn waits_for_a_full_window() {
+        let mut interval = adaptive(40_000);
+
+        assert_eq!(interval.observe(0, READING), None);
+        assert_eq!(interval.observe(59_999, warmer(5.0)), None);
+        assert_eq!(interval.current(), 40_000);
+    }
+
+    #[test]
+    fn a_fast_window_shrinks_the_interval_at_once() {
+        let mut interval = adaptive(160_000);
+        interval.observe(0, READING);
+
+        assert!(interval.observe(60_000, warmer(1.0)).is_some());
+        assert_eq!(interval.current(), 10_000);
+    }
+
+    #[test]
+    fn a_calm_window_grows_the_interval_by_at_most_a_doubling() {
+        let mut interval = adaptive(10_000);
+        interval.observe(0, READING);
+
+        let mut intervals = Vec::new();
+        for window in 1..=5 {
+            interval.observe(window * 60_000, READING);
+            intervals.push(interval.current());
+        }
+        assert_eq!(intervals, [20_000, 40_000, 80_000, 160_000, 160_000]);
+    }
+
+    #[test]
+    fn the_rate_is_measured_per_minute_and_scaled() {
+        // Half a delta over a minute is halfway
+        let mut interval = adaptive(160_000);
+        interval.observe(0, READING);
+        let rate = interval.observe(60_000, warmer(0.5)).unwrap();
+        assert!((rate - 0.5).abs() < 1e-4, "{rate}");
+        assert_eq!(interval.current(), 85_000);
+
+        // The same move over two minutes is half the rate; doubling the sensitivity makes up for it
+        let mut interval = AdaptiveInterval::new((10_000, 160_000), 160_000, 60_000, 2.0, DELTAS);
+        interval.observe(0, READING);
+        interval.observe(120_000, warmer(0.5));
+        assert_eq!(interval.current(), 85_000);
+    }
+
+    #[test]
+    fn the_initial_interval_is_clamped_to_the_bounds() {
+        assert_eq!(adaptive(1_000).current(), 10_000);
+        assert_eq!(adaptive(1_000_000).current(), 160_000);
+        // A max below the min is raised to it
+        let interval = AdaptiveInterval::new((30_000, 10_000), 0, 60_000, 1.0, DELTAS);
+        assert_eq!(interval.current(), 30_000);
+    }
+}
@@ -0,0 +1,42 @@
+/// Sensirion CRC-8, sent after every 16-bit word: polynomial 0x31, initial value 0xFF.
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0xFF, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Humidity (%) and temperature (°C) as the SGP4x's 16-bit compensation ticks, clamped to its
+/// compensation range.
+pub fn compensation_ticks(humidity: f32, temperature: f32) -> [u16; 2] {
+    let rh_ticks = humidity.clamp(0.0, 100.0) * 65_535.0 / 100.0;
+    let t_ticks = (temperature.clamp(-45.0, 130.0) + 45.0) * 65_535.0 / 175.0;
+    [rh_ticks.round() as u16, t_ticks.round() as u16]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc8_datasheet_example() {
+        assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+    }
+
+    #[test]
+    fn default_compensation_is_50_percent_at_25_degrees() {
+        // The datasheet's defaults, sent when no humidity sensor is present
+        assert_eq!(compensation_ticks(50.0, 25.0), [0x8000, 0x6666]);
+    }
+
+    #[test]
+    fn compensation_clamps_to_range() {
+        assert_eq!(compensation_ticks(-5.0, -60.0), [0, 0]);
+        assert_eq!(compensation_ticks(120.0, 150.0), [u16::MAX, u16::MAX]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Sun position from the low-precision formulas of the Astronomical Almanac (accurate to ~0.01°
+//! between 1950 and 2050), which is plenty to tell day from night.
+
+/// Sun elevation at which the upper limb touches the horizon, accounting for refraction.
+const SUNRISE_ELEVATION_DEG: f64 = -0.833;
+
+const UNIX_EPOCH_TO_J2000_DAYS: f64 = 10_957.5;
+
+/// Whether the sun is above the horizon at `unix_s` for the given location (degrees, north and
+/// east positive).
+pub fn is_daytime(lat: f64, lon: f64, unix_s: i64) -> bool {
+    sun_elevation_deg(lat, lon, unix_s) > SUNRISE_ELEVATION_DEG
+}
+
+fn sun_elevation_deg(lat: f64, lon: f64, unix_s: i64) -> f64 {
+    let days = unix_s as f64 / 86_400.0 - UNIX_EPOCH_TO_J2000_DAYS;
+
+    let mean_longitude = 280.460 + 0.985_647_4 * days;
+    let mean_anomaly = (357.528 + 0.985_600_3 * days).to_radians();
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_4 * days).to_radians();
+
+    let right_ascension =
+        (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    let sidereal_time_deg = 280.460_618_37 + 360.985_647_366_29 * days + lon;
+    let hour_angle = sidereal_time_deg.to_radians() - right_ascension;
+
+    let lat = lat.to_radians();
+    (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos())
+        .asin()
+        .to_degrees()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONDON: (f64, f64) = (51.51, -0.13);
+    const TROMSO: (f64, f64) = (69.65, 18.96);
+
+    // 2024-06-21 (June solstice) and 2024-12-21 (December solstice), UTC
+    const JUNE_21_0330: i64 = 1_718_940_600;
+    const JUNE_21_0400: i64 = 1_718_942_400;
+    const JUNE_21_1200: i64 = 1_718_971_200;
+    const JUNE_21_2300: i64 = 1_719_010_800;
+    const DEC_21_0000: i64 = 1_734_739_200;
+    const DEC_21_1100: i64 = 1_734_778_800;
+    // 2024-03-20 12:00 UTC, hours after the March equinox
+    const MARCH_20_1200: i64 = 1_710_936_000;
+
+    #[test]
+    fn noon_is_day_and_midnight_is_night() {
+        assert!(is_daytime(LONDON.0, LONDON.1, JUNE_21_1200));
+        assert!(!is_daytime(LONDON.0, LONDON.1, DEC_21_0000));
+    }
+
+    #[test]
+    fn sunrise_in_london_on_the_june_solstice() {
+        // Sunrise is at 03:43 UTC
+        assert!(!is_daytime(LONDON.0, LONDON.1, JUNE_21_0330));
+        assert!(is_daytime(LONDON.0, LONDON.1, JUNE_21_0400));
+    }
+
+    #[test]
+    fn sun_is_overhead_at_the_equator_on_the_equinox() {
+        assert!(sun_elevation_deg(0.0, 0.0, MARCH_20_1200) > 88.0);
+    }
+
+    #[test]
+    fn polar_day_and_night_above_the_arctic_circle() {
+        assert!(is_daytime(TROMSO.0, TROMSO.1, JUNE_21_2300));
+        assert!(!is_daytime(TROMSO.0, TROMSO.1, DEC_21_1100));
+    }
+}
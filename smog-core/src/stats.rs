@@ -0,0 +1,198 @@
+/// Running mean/variance using Welford's online algorithm: numerically stable, O(1) memory.
+#[derive(Default)]
+pub struct RunningStats {
+    count: u32,
+    mean: f32,
+    m2: f32,
+}
+
+impl RunningStats {
+    pub fn update(&mut self, value: f32) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    /// Sample variance; zero until at least two values have been seen.
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f32
+        }
+    }
+
+    /// How many standard deviations `value` lies from the mean, `None` while the spread is zero.
+    pub fn zscore(&self, value: f32) -> Option<f32> {
+        let std_dev = self.variance().sqrt();
+        (std_dev > 0.0).then(|| (value - self.mean) / std_dev)
+    }
+}
+
+/// Flags samples where any metric deviates more than `zscore_threshold` standard deviations from
+/// its history. Each value is scored before being folded into the stats, and nothing is flagged
+/// until `min_samples` values have been collected.
+pub struct AnomalyDetector {
+    min_samples: u32,
+    zscore_threshold: f32,
+    temperature: RunningStats,
+    humidity: RunningStats,
+    pressure: RunningStats,
+    voc: RunningStats,
+}
+
+impl AnomalyDetector {
+    pub fn new(min_samples: u32, zscore_threshold: f32) -> Self {
+        Self {
+            min_samples,
+            zscore_threshold,
+            temperature: RunningStats::default(),
+            humidity: RunningStats::default(),
+            pressure: RunningStats::default(),
+            voc: RunningStats::default(),
+        }
+    }
+
+    pub fn check(
+        &mut self,
+        temperature: Option<f32>,
+        humidity: Option<f32>,
+        pressure: Option<f32>,
+        voc: Option<u16>,
+    ) -> bool {
+        let (min_samples, threshold) = (self.min_samples, self.zscore_threshold);
+        let check_field = |stats: &mut RunningStats, value: f32| {
+            let anomaly = stats.count() >= min_samples
+                && stats.zscore(value).is_some_and(|z| z.abs() > threshold);
+
+            stats.update(value);
+            anomaly
+        };
+
+        let mut anomaly = false;
+        if let Some(temperature) = temperature {
+            anomaly |= check_field(&mut self.temperature, temperature);
+        }
+        if let Some(humidity) = humidity {
+            anomaly |= check_field(&mut self.humidity, humidity);
+        }
+        if let Some(pressure) = pressure {
+            anomaly |= check_field(&mut self.pressure, pressure);
+        }
+        if let Some(voc) = voc {
+            anomaly |= check_field(&mut self.voc, f32::from(voc));
+        }
+
+        anomaly
+    }
+}
+
+/// The `p`-th percentile (`0.0..=100.0`) of an ascending slice, linearly interpolated between the
+/// two closest ranks (NumPy's default), so p50 of `[1, 2, 3, 4]` is `2.5`. `None` when empty.
+pub fn percentile(sorted: &[f32], p: f32) -> Option<f32> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = p.clamp(0.0, 100.0) / 100.0 * last as f32;
+    let below = rank.floor() as usize;
+    let above = (below + 1).min(last);
+    let weight = rank - below as f32;
+
+    Some(sorted[below] + (sorted[above] - sorted[below]) * weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_stats_match_the_two_pass_result() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let mut stats = RunningStats::default();
+        values.iter().for_each(|&v| stats.update(v));
+
+        assert_eq!(stats.count(), 8);
+        assert!((stats.mean() - 5.0).abs() < 1e-6);
+        // Sum of squared deviations is 32, over n - 1
+        assert!((stats.variance() - 32.0 / 7.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zscore_is_none_without_spread() {
+        let mut stats = RunningStats::default();
+        assert_eq!(stats.zscore(1.0), None);
+        stats.update(3.0);
+        stats.update(3.0);
+        assert_eq!(stats.zscore(10.0), None);
+    }
+
+    /// Feeds `count` temperatures alternating around 20 °C with a spread of 1.
+    fn warmed_up(detector: &mut AnomalyDetector, count: u32) {
+        for i in 0..count {
+            let temperature = if i % 2 == 0 { 19.0 } else { 21.0 };
+            assert!(!detector.check(Some(temperature), Some(50.0), None, None));
+        }
+    }
+
+    #[test]
+    fn nothing_is_flagged_before_min_samples() {
+        let mut detector = AnomalyDetector::new(10, 3.0);
+        warmed_up(&mut detector, 9);
+        assert!(!detector.check(Some(80.0), None, None, None));
+    }
+
+    #[test]
+    fn outlier_is_flagged_once_warmed_up() {
+        let mut detector = AnomalyDetector::new(10, 3.0);
+        warmed_up(&mut detector, 10);
+        assert!(!detector.check(Some(21.5), None, None, None));
+        assert!(detector.check(Some(30.0), None, None, None));
+    }
+
+    #[test]
+    fn missing_fields_are_neither_scored_nor_counted() {
+        let mut detector = AnomalyDetector::new(10, 3.0);
+        warmed_up(&mut detector, 10);
+        // Humidity never varied, so it has no spread to score against
+        assert!(!detector.check(Some(20.0), Some(90.0), None, None));
+        // Pressure has no history at all
+        assert!(!detector.check(Some(20.0), None, Some(500.0), Some(400)));
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 50.0), Some(2.5));
+
+        let one_to_ten: Vec<f32> = (1..=10).map(|i| i as f32).collect();
+        let p90 = percentile(&one_to_ten, 90.0).unwrap();
+        assert!((p90 - 9.1).abs() < 1e-5, "{p90}");
+        assert_eq!(percentile(&one_to_ten, 50.0), Some(5.5));
+    }
+
+    #[test]
+    fn percentile_ends_are_min_and_max() {
+        let sorted = [-3.0, 0.5, 7.0];
+        assert_eq!(percentile(&sorted, 0.0), Some(-3.0));
+        assert_eq!(percentile(&sorted, 100.0), Some(7.0));
+        // Out-of-range percentiles are clamped
+        assert_eq!(percentile(&sorted, 150.0), Some(7.0));
+        assert_eq!(percentile(&sorted, -10.0), Some(-3.0));
+    }
+
+    #[test]
+    fn percentile_of_one_value_is_that_value() {
+        assert_eq!(percentile(&[42.0], 90.0), Some(42.0));
+    }
+
+    #[test]
+    fn percentile_of_nothing_is_none() {
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(feature = "tz")]
+use chrono::{DateTime, TimeZone};
+
+/// Whether `hour` (0-23, local time) falls inside the quiet hours window `start..end`.
+///
+/// `start == end` disables the window; `start > end` wraps midnight, e.g. 22 -> 6.
+pub fn in_quiet_hours(start: u32, end: u32, hour: u32) -> bool {
+    if start == end {
+        false
+    } else if start < end {
+        (start..end).contains(&hour)
+    } else {
+        !(end..start).contains(&hour)
+    }
+}
+
+/// Time left until the next local midnight in `now`'s timezone.
+///
+/// The target is resolved through the zone's rules rather than assuming 24h, so DST days (23h/25h)
+/// come out right. Where a DST gap swallows midnight itself, the first valid local time after it
+/// is used.
+#[cfg(feature = "tz")]
+pub fn until_next_local_midnight<Z: TimeZone>(now: DateTime<Z>) -> chrono::Duration {
+    let timezone = now.timezone();
+    let tomorrow = now.date_naive() + chrono::Days::new(1);
+
+    let next_midnight = (0..24)
+        .filter_map(|hour| {
+            timezone
+                .from_local_datetime(&tomorrow.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+        })
+        .next();
+
+    match next_midnight {
+        Some(midnight) => midnight - now,
+        // Unreachable for real timezones; don't spin if it ever happens
+        None => chrono::Duration::hours(24),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_bounds_disable_quiet_hours() {
+        assert!((0..24).all(|hour| !in_quiet_hours(0, 0, hour)));
+        assert!((0..24).all(|hour| !in_quiet_hours(7, 7, hour)));
+    }
+
+    #[test]
+    fn daytime_window_includes_start_and_excludes_end() {
+        assert!(!in_quiet_hours(9, 17, 8));
+        assert!(in_quiet_hours(9, 17, 9));
+        assert!(in_quiet_hours(9, 17, 16));
+        assert!(!in_quiet_hours(9, 17, 17));
+    }
+
+    #[test]
+    fn window_wrapping_midnight() {
+        assert!(!in_quiet_hours(22, 6, 21));
+        assert!(in_quiet_hours(22, 6, 22));
+        assert!(in_quiet_hours(22, 6, 23));
+        assert!(in_quiet_hours(22, 6, 0));
+        assert!(in_quiet_hours(22, 6, 5));
+        assert!(!in_quiet_hours(22, 6, 6));
+    }
+
+    #[test]
+    fn window_starting_at_midnight() {
+        assert!(in_quiet_hours(0, 6, 0));
+        assert!(!in_quiet_hours(0, 6, 6));
+        assert!(!in_quiet_hours(0, 6, 23));
+    }
+
+    #[cfg(feature = "tz")]
+    mod midnight {
+        use super::super::until_next_local_midnight;
+        use chrono::{Duration, TimeZone};
+        use chrono_tz::America::Santiago;
+        use chrono_tz::Europe::Warsaw;
+
+        #[test]
+        fn regular_day() {
+            let now = Warsaw.with_ymd_and_hms(2024, 7, 10, 22, 30, 0).unwrap();
+            assert_eq!(until_next_local_midnight(now), Duration::minutes(90));
+        }
+
+        #[test]
+        fn spring_forward_day_has_23_hours() {
+            let now = Warsaw.with_ymd_and_hms(2024, 3, 31, 0, 0, 0).unwrap();
+            assert_eq!(until_next_local_midnight(now), Duration::hours(23));
+        }
+
+        #[test]
+        fn fall_back_day_has_25_hours() {
+            let now = Warsaw.with_ymd_and_hms(2024, 10, 27, 0, 0, 0).unwrap();
+            assert_eq!(until_next_local_midnight(now), Duration::hours(25));
+        }
+
+        #[test]
+        fn midnight_in_a_dst_gap_rolls_over_at_the_first_valid_hour() {
+            // Chile springs forward from 2024-09-08 00:00 straight to 01:00
+            let now = Santiago.with_ymd_and_hms(2024, 9, 7, 12, 0, 0).unwrap();
+            assert_eq!(until_next_local_midnight(now), Duration::hours(12));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+/// Rounds half away from zero to `decimals` places. Done in f64, so values like `1.005` that f32
+/// can't represent exactly don't pick up extra error from the scaling.
+pub fn round_to(value: f32, decimals: u32) -> f32 {
+    let factor = 10f64.powi(decimals as i32);
+    ((f64::from(value) * factor).round() / factor) as f32
+}
+
+/// Standard inch of mercury (at 0 °C), in hPa.
+const HPA_PER_INHG: f32 = 33.863_89;
+
+/// A pressure in inches of mercury, for the `DUAL_UNITS` field. An inHg is ~34 hPa, so it takes
+/// two more decimal places than hPa for the same resolution.
+pub fn hpa_to_inhg(hpa: f32) -> f32 {
+    hpa / HPA_PER_INHG
+}
+
+/// Temperature unit on the wire; the device works in °C.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn convert(self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+}
+
+/// Pressure unit on the wire; the device works in hPa.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PressureUnit {
+    Hectopascal,
+    Pascal,
+}
+
+impl PressureUnit {
+    pub fn convert(self, hpa: f32) -> f32 {
+        match self {
+            PressureUnit::Hectopascal => hpa,
+            PressureUnit::Pascal => hpa * 100.0,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PressureUnit::Hectopascal => "hPa",
+            PressureUnit::Pascal => "Pa",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(round_to(2.5, 0), 3.0);
+        assert_eq!(round_to(-2.5, 0), -3.0);
+        assert_eq!(round_to(0.125, 2), 0.13);
+        assert_eq!(round_to(-0.125, 2), -0.13);
+        assert_eq!(round_to(1013.25, 1), 1013.3);
+    }
+
+    #[test]
+    fn rounds_to_the_nearest_f32_of_the_decimal() {
+        assert_eq!(round_to(21.456, 1), 21.5);
+        assert_eq!(round_to(21.44, 1), 21.4);
+        assert_eq!(round_to(45.09, 1), 45.1);
+        assert_eq!(round_to(1013.2, 0), 1013.0);
+    }
+
+    #[test]
+    fn already_rounded_values_are_unchanged() {
+        for value in [0.0, 45.0, 45.1, -7.3, 1013.2] {
+            assert_eq!(round_to(value, 1), value);
+        }
+    }
+
+    #[test]
+    fn temperature_conversions() {
+        let fahrenheit = TemperatureUnit::Fahrenheit;
+        assert_eq!(fahrenheit.convert(0.0), 32.0);
+        assert_eq!(fahrenheit.convert(100.0), 212.0);
+        assert_eq!(fahrenheit.convert(-40.0), -40.0);
+        assert_eq!(round_to(fahrenheit.convert(21.5), 1), 70.7);
+        assert_eq!(TemperatureUnit::Celsius.convert(21.5), 21.5);
+    }
+
+    #[test]
+    fn celsius_to_fahrenheit_and_back() {
+        let fahrenheit = TemperatureUnit::Fahrenheit;
+        for (celsius, expected) in [(-20.0, -4.0), (-17.5, 0.5), (37.0, 98.6), (25.0, 77.0)] {
+            assert_eq!(round_to(fahrenheit.convert(celsius), 1), expected);
+            // And back: (°F - 32) * 5/9 lands on the same °C
+            assert_eq!(round_to((expected - 32.0) * 5.0 / 9.0, 1), celsius);
+        }
+    }
+
+    #[test]
+    fn pressure_conversions() {
+        assert_eq!(PressureUnit::Hectopascal.convert(1013.25), 1013.25);
+        assert_eq!(PressureUnit::Pascal.convert(1013.25), 101_325.0);
+    }
+
+    #[test]
+    fn hpa_to_inhg_and_back() {
+        // Standard atmosphere, and the usual range of a barometer dial
+        for (hpa, inhg) in [(1013.25, 29.921), (948.2, 28.0), (1050.0, 31.006)] {
+            assert_eq!(round_to(hpa_to_inhg(hpa), 3), inhg);
+            assert!((inhg * HPA_PER_INHG - hpa).abs() < 0.02, "{inhg} inHg");
+        }
+    }
+
+    #[test]
+    fn units_have_their_symbols() {
+        assert_eq!(TemperatureUnit::Fahrenheit.symbol(), "°F");
+        assert_eq!(PressureUnit::Pascal.symbol(), "Pa");
+    }
+}
@@ -0,0 +1,108 @@
+/// A field to watch and its alert band, see `Hysteresis`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub field: WatchField,
+    pub threshold: Threshold,
+    /// In the field's units.
+    pub hysteresis: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchField {
+    Temperature,
+    Humidity,
+    Pressure,
+    Voc,
+}
+
+/// Which side of the value is the alert band.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    Above(f32),
+    Below(f32),
+}
+
+/// Two-state threshold with a dead band: enters the alert band past the threshold and only leaves
+/// it once the value is back by more than `hysteresis`, so a value hovering at the threshold
+/// doesn't flap.
+pub struct Hysteresis {
+    threshold: Threshold,
+    hysteresis: f32,
+    active: bool,
+}
+
+impl Hysteresis {
+    pub fn new(threshold: Threshold, hysteresis: f32) -> Self {
+        Self {
+            threshold,
+            hysteresis: hysteresis.max(0.0),
+            active: false,
+        }
+    }
+
+    /// The new state on a transition (`true` = entered the alert band), `None` otherwise.
+    pub fn update(&mut self, value: f32) -> Option<bool> {
+        let active = match (self.threshold, self.active) {
+            (Threshold::Above(limit), false) => value > limit,
+            (Threshold::Above(limit), true) => value >= limit - self.hysteresis,
+            (Threshold::Below(limit), false) => value < limit,
+            (Threshold::Below(limit), true) => value <= limit + self.hysteresis,
+        };
+
+        (active != self.active).then(|| {
+            self.active = active;
+            active
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transitions(threshold: Threshold, hysteresis: f32, values: &[f32]) -> Vec<Option<bool>> {
+        let mut state = Hysteresis::new(threshold, hysteresis);
+        values.iter().map(|&value| state.update(value)).collect()
+    }
+
+    #[test]
+    fn above_enters_past_the_threshold_and_leaves_below_the_band() {
+        assert_eq!(
+            transitions(
+                Threshold::Above(70.0),
+                3.0,
+                &[69.0, 70.0, 70.5, 68.0, 67.0, 66.9]
+            ),
+            [None, None, Some(true), None, None, Some(false)]
+        );
+    }
+
+    #[test]
+    fn below_enters_past_the_threshold_and_leaves_above_the_band() {
+        assert_eq!(
+            transitions(Threshold::Below(2.0), 0.5, &[3.0, 1.9, 2.4, 2.5, 2.6]),
+            [None, Some(true), None, None, Some(false)]
+        );
+    }
+
+    #[test]
+    fn a_value_hovering_at_the_threshold_reports_once() {
+        assert_eq!(
+            transitions(Threshold::Above(70.0), 1.0, &[70.1, 69.9, 70.1, 69.5, 70.2]),
+            [Some(true), None, None, None, None]
+        );
+    }
+
+    #[test]
+    fn zero_hysteresis_leaves_at_the_threshold() {
+        assert_eq!(
+            transitions(Threshold::Above(70.0), 0.0, &[70.1, 70.0, 69.9]),
+            [Some(true), None, Some(false)]
+        );
+        // A negative band is treated as none
+        assert_eq!(
+            transitions(Threshold::Above(70.0), -5.0, &[70.1, 69.9]),
+            [Some(true), Some(false)]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::config::{LOCAL_ALERT_BUZZER_HZ, LocalAlertStyle, local_alert_style};
+use crate::led::StatusLed;
+use anyhow::Context;
+use esp_idf_svc::hal::gpio::AnyOutputPin;
+use esp_idf_svc::hal::ledc::config::TimerConfig;
+use esp_idf_svc::hal::ledc::{CHANNEL0, LedcDriver, LedcTimerDriver, TIMER0};
+use esp_idf_svc::hal::units::Hertz;
+
+/// Local alarm for a station nobody watches remotely (`LOCAL_ALERT_GPIO`): a passive piezo
+/// buzzer fed a square wave by the LEDC peripheral, or a plain LED.
+pub(crate) enum Alarm {
+    Led(StatusLed),
+    Buzzer {
+        channel: LedcDriver<'static>,
+        // Dropping the timer driver would stop the tone
+        _timer: LedcTimerDriver<'static, TIMER0>,
+    },
+}
+
+impl Alarm {
+    /// # Safety
+    ///
+    /// `gpio` must be a valid output pin that no other driver uses (I2C is on 6/7, the lighthouse
+    /// on 8, wind/rain on 2/3).
+    pub(crate) unsafe fn new(gpio: i32, timer: TIMER0, channel: CHANNEL0) -> anyhow::Result<Self> {
+        match local_alert_style() {
+            LocalAlertStyle::Led => Ok(Alarm::Led(unsafe { StatusLed::new(gpio) }?)),
+            LocalAlertStyle::Buzzer => {
+                let timer = LedcTimerDriver::new(
+                    timer,
+                    &TimerConfig::default().frequency(Hertz(LOCAL_ALERT_BUZZER_HZ).into()),
+                )
+                .context("‼️ Failed to init buzzer timer")?;
+                let mut channel =
+                    LedcDriver::new(channel, &timer, unsafe { AnyOutputPin::new(gpio) })
+                        .context("‼️ Failed to init buzzer GPIO")?;
+                channel.set_duty(0)?;
+                Ok(Alarm::Buzzer {
+                    channel,
+                    _timer: timer,
+                })
+            }
+        }
+    }
+
+    pub(crate) fn set(&mut self, on: bool) {
+        match self {
+            Alarm::Led(led) => led.set(on),
+            Alarm::Buzzer { channel, .. } => {
+                // A 50 % duty cycle is the loudest tone; like the LED, a failed write isn't
+                // worth surfacing
+                let duty = if on { channel.get_max_duty() / 2 } else { 0 };
+                let _ = channel.set_duty(duty);
+            }
+        }
+    }
+}
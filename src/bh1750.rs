@@ -0,0 +1,33 @@
+use crate::I2cBusDevice;
+use embedded_hal::i2c::I2c;
+
+const POWER_ON: u8 = 0x01;
+const CONTINUOUS_HIGH_RES_MODE: u8 = 0x10;
+const RAW_TO_LUX_DIVISOR: f32 = 1.2;
+
+/// Minimal BH1750 ambient light driver: powered on once, then left in continuous high-res mode
+/// (1 lx resolution, ~120 ms per conversion), so a read only fetches the latest result.
+pub(crate) struct Bh1750 {
+    i2c: I2cBusDevice,
+    address: u8,
+}
+
+impl Bh1750 {
+    pub(crate) fn new(mut i2c: I2cBusDevice, address: u8) -> anyhow::Result<Self> {
+        i2c.write(address, &[POWER_ON])
+            .map_err(|e| anyhow::anyhow!("‼️BH1750 power on failed: {:?}", e))?;
+        i2c.write(address, &[CONTINUOUS_HIGH_RES_MODE])
+            .map_err(|e| anyhow::anyhow!("‼️BH1750 mode setup failed: {:?}", e))?;
+
+        Ok(Self { i2c, address })
+    }
+
+    pub(crate) fn read_lux(&mut self) -> anyhow::Result<f32> {
+        let mut buffer = [0u8; 2];
+        self.i2c
+            .read(self.address, &mut buffer)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+
+        Ok(u16::from_be_bytes(buffer) as f32 / RAW_TO_LUX_DIVISOR)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::config::DEVICE_HOSTNAME;
+use embassy_sync::once_lock::OnceLock;
+use esp_idf_svc::sys::esp_efuse_mac_get_default;
+
+/// Factory-programmed base MAC address, read from eFuse.
+pub(crate) fn mac_address() -> [u8; 6] {
+    let mut mac = [0u8; 6];
+    unsafe { esp_efuse_mac_get_default(mac.as_mut_ptr()) };
+    mac
+}
+
+/// MAC hashed with FNV-1a: a stable, non-zero per-device seed (e.g. for the send jitter).
+#[cfg(any(feature = "http", feature = "mqtt"))]
+pub(crate) fn mac_seed() -> u32 {
+    mac_address()
+        .iter()
+        .fold(0x811c_9dc5, |hash, &byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+        .max(1)
+}
+
+/// Stable per-device identifier derived from the last three MAC bytes, e.g. `smog-a1b2c3`.
+pub(crate) fn device_id() -> &'static str {
+    static DEVICE_ID: OnceLock<String> = OnceLock::new();
+    DEVICE_ID.get_or_init(|| {
+        let mac = mac_address();
+        format!("smog-{:02x}{:02x}{:02x}", mac[3], mac[4], mac[5])
+    })
+}
+
+/// `DEVICE_HOSTNAME`, or the device id when none is configured.
+pub(crate) fn hostname() -> &'static str {
+    if DEVICE_HOSTNAME.is_empty() {
+        device_id()
+    } else {
+        DEVICE_HOSTNAME
+    }
+}
@@ -0,0 +1,113 @@
+use crate::I2cBusDevice;
+use crate::models::WeatherData;
+use anyhow::anyhow;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::iso_8859_1::FONT_6X10;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+use ssd1306::mode::BufferedGraphicsMode;
+use ssd1306::prelude::*;
+use ssd1306::{I2CDisplayInterface, Ssd1306};
+use std::cell::RefCell;
+
+const LINE_HEIGHT: i32 = 12;
+
+type Panel =
+    Ssd1306<I2CInterface<I2cBusDevice>, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>;
+
+/// The most recent reading, written by the `record_latest_reading` observer and picked up by
+/// `display_task`, so rendering never runs inside the sensor loop.
+static LATEST_READING: Mutex<CriticalSectionRawMutex, RefCell<Option<WeatherData>>> =
+    Mutex::new(RefCell::new(None));
+static READING_UPDATED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 128x64 SSD1306 OLED on the shared I2C bus, redrawn from a frame buffer on every reading.
+pub(crate) struct Display {
+    panel: Panel,
+}
+
+impl Display {
+    /// Fails when nothing acknowledges at `address`, so a missing panel is just skipped.
+    pub(crate) fn new(i2c: I2cBusDevice, address: u8) -> anyhow::Result<Self> {
+        let interface = I2CDisplayInterface::new_custom_address(i2c, address);
+        let mut panel = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+            .into_buffered_graphics_mode();
+
+        panel
+            .init()
+            .map_err(|e| anyhow!("‼️ SSD1306 init failed: {:?}", e))?;
+
+        Ok(Self { panel })
+    }
+
+    /// Draws the reading plus a WiFi / time-sync status line and pushes the frame to the panel.
+    pub(crate) fn render(
+        &mut self,
+        data: &WeatherData,
+        wifi_connected: bool,
+        time_synced: bool,
+    ) -> anyhow::Result<()> {
+        let lines = [
+            format!(
+                "WiFi {}  NTP {}",
+                if wifi_connected { "up" } else { "--" },
+                if time_synced { "ok" } else { "--" }
+            ),
+            match data.temperature {
+                Some(temperature) => format!("Temp  {:.1} °C", temperature),
+                None => "Temp  -".to_string(),
+            },
+            match data.humidity {
+                Some(humidity) => format!("Hum   {:.1} %", humidity),
+                None => "Hum   -".to_string(),
+            },
+            match data.pressure {
+                Some(pressure) => format!("Pres  {:.1} hPa", pressure),
+                None => "Pres  -".to_string(),
+            },
+            match data.voc {
+                Some(voc) => format!("VOC   {}", voc),
+                None => "VOC   -".to_string(),
+            },
+        ];
+
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+
+        self.panel.clear_buffer();
+        for (row, line) in lines.iter().enumerate() {
+            Text::with_baseline(
+                line,
+                Point::new(0, row as i32 * LINE_HEIGHT),
+                style,
+                Baseline::Top,
+            )
+            .draw(&mut self.panel)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        }
+
+        self.panel
+            .flush()
+            .map_err(|e| anyhow!("‼️ SSD1306 flush failed: {:?}", e))
+    }
+}
+
+/// Reading observer: hands the reading to `display_task`.
+pub(crate) fn record_latest_reading(data: &WeatherData) {
+    LATEST_READING.lock(|latest| *latest.borrow_mut() = Some(data.clone()));
+    READING_UPDATED.signal(());
+}
+
+/// Waits for a reading newer than the last one returned.
+pub(crate) async fn next_reading() -> WeatherData {
+    loop {
+        READING_UPDATED.wait().await;
+
+        if let Some(data) = LATEST_READING.lock(|latest| latest.borrow_mut().take()) {
+            return data;
+        }
+    }
+}
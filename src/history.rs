@@ -0,0 +1,114 @@
+use crate::config::{HISTORY_CAPACITY, HISTORY_COMPACT};
+use crate::models::WeatherData;
+use crate::time_utils::effective_timezone_name;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use smog_core::atmosphere::absolute_humidity;
+use smog_core::history::{CompactReading, DeltaHistory};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// Hard upper bound on `HISTORY_CAPACITY`: one sample is ~130 bytes of RAM.
+const MAX_HISTORY_CAPACITY: usize = 512;
+/// The same bound with `HISTORY_COMPACT`, where one sample is 20 bytes.
+const MAX_COMPACT_HISTORY_CAPACITY: usize = 4096;
+
+/// The last readings, oldest at the front. Allocated lazily on the first sample.
+static HISTORY: Mutex<CriticalSectionRawMutex, RefCell<VecDeque<WeatherData>>> =
+    Mutex::new(RefCell::new(VecDeque::new()));
+static COMPACT_HISTORY: Mutex<CriticalSectionRawMutex, RefCell<DeltaHistory>> =
+    Mutex::new(RefCell::new(DeltaHistory::new()));
+
+fn capacity() -> usize {
+    if HISTORY_COMPACT {
+        HISTORY_CAPACITY.min(MAX_COMPACT_HISTORY_CAPACITY)
+    } else {
+        HISTORY_CAPACITY.min(MAX_HISTORY_CAPACITY)
+    }
+}
+
+/// Reading observer: appends to the ring buffer, evicting the oldest sample once it is full.
+pub(crate) fn record(data: &WeatherData) {
+    let capacity = capacity();
+    if capacity == 0 {
+        return;
+    }
+
+    if HISTORY_COMPACT {
+        let reading = to_compact_reading(data);
+        COMPACT_HISTORY.lock(|history| history.borrow_mut().push(&reading, capacity));
+        return;
+    }
+
+    HISTORY.lock(|history| {
+        let mut history = history.borrow_mut();
+        if history.capacity() == 0 {
+            history.reserve_exact(capacity);
+        }
+        if history.len() >= capacity {
+            history.pop_front();
+        }
+        history.push_back(data.clone());
+    });
+}
+
+/// Up to `limit` stored readings, newest first. Only the readings asked for are copied out of the
+/// lock; compact ones are decoded one at a time as the caller consumes them.
+pub(crate) fn newest_first(limit: usize) -> Box<dyn Iterator<Item = WeatherData>> {
+    if HISTORY_COMPACT {
+        let readings = COMPACT_HISTORY.lock(|history| history.borrow().newest_first(limit));
+        return Box::new(readings.map(to_weather_data));
+    }
+
+    let readings: Vec<WeatherData> =
+        HISTORY.lock(|history| history.borrow().iter().rev().take(limit).cloned().collect());
+    Box::new(readings.into_iter())
+}
+
+fn to_compact_reading(data: &WeatherData) -> CompactReading {
+    CompactReading {
+        temperature: data.temperature,
+        humidity: data.humidity,
+        pressure: data.pressure,
+        voc: data.voc,
+        time_synced: data.time_synced,
+        timestamp_unix_s: data.timestamp_unix_s,
+        uptime_us: data.uptime_us,
+    }
+}
+
+/// Only the core fields survive the compact history; everything else reads as absent.
+fn to_weather_data(reading: CompactReading) -> WeatherData {
+    WeatherData {
+        temperature: reading.temperature,
+        humidity: reading.humidity,
+        absolute_humidity_gm3: reading
+            .temperature
+            .zip(reading.humidity)
+            .map(|(temperature, humidity)| absolute_humidity(temperature, humidity)),
+        pressure: reading.pressure,
+        pressure_qnh_hpa: None,
+        temperature_raw: None,
+        humidity_raw: None,
+        pressure_raw: None,
+        temperature_probe_c: None,
+        temperature_ambient_c: None,
+        gas_ppm: None,
+        voc: reading.voc,
+        voc_raw: None,
+        nox_index: None,
+        lux: None,
+        wind_speed_kmh: None,
+        rain_mm: None,
+        is_daytime: None,
+        wifi: None,
+        anomaly: false,
+        alert: false,
+        seq: 0,
+        interval_stats: None,
+        time_synced: reading.time_synced,
+        timestamp_unix_s: reading.timestamp_unix_s,
+        uptime_us: reading.uptime_us,
+        timezone: effective_timezone_name(),
+    }
+}
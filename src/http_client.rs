@@ -0,0 +1,520 @@
+use crate::config::{
+    CONNECTIVITY_PROBE_EXPECTED_STATUS, CONNECTIVITY_PROBE_URL, DELIVERY_VERIFY_FIELD,
+    HTTP_CONSUMER_ENDPOINT_URL, HTTP_MAX_REDIRECTS, LOG_PAYLOAD, PayloadFormat, is_proxy_enabled,
+    payload_format,
+};
+use crate::device::device_id;
+use crate::logging::log_payload;
+use crate::models::WeatherData;
+use crate::output::{json_payload, transform_for_output};
+use crate::proxy;
+use crate::signing::{PayloadSigner, signature_headers};
+use crate::time_utils::timestamp_unix_s;
+use crate::tls::{client_identity, pinned_server_certificate};
+use anyhow::Result;
+use embedded_svc::http::Method;
+use embedded_svc::http::client::Client as HttpClientImpl;
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use esp_idf_svc::io::EspIOError;
+use log::{info, warn};
+use smog_core::delivery::Poster;
+use smog_core::payload::{to_form_urlencoded, to_ndjson};
+use smog_core::redirect::{check_redirect, resolve_location};
+
+const MAX_CONFIG_BODY_LEN: usize = 1024;
+const MAX_STATUS_BODY_LEN: usize = 512;
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+/// The redirects that keep the method and body; a 303 asks for a GET, which delivers nothing.
+const REDIRECT_STATUSES: [u16; 4] = [301, 302, 307, 308];
+
+/// Checks that the network actually reaches the internet: a captive portal lets the station
+/// associate but swallows every request, which otherwise looks like a silent server.
+/// Only warns; the device keeps running either way.
+pub(crate) fn probe_connectivity() {
+    match probe_status(CONNECTIVITY_PROBE_URL) {
+        Ok((status, _)) if status == CONNECTIVITY_PROBE_EXPECTED_STATUS => {
+            info!("📶 Connectivity check passed (Status {})", status);
+        }
+        Ok((status, location)) if (300..400).contains(&status) => warn!(
+            "‼️📶 Captive portal suspected: connectivity check redirected (Status {}) to {}",
+            status,
+            location.as_deref().unwrap_or("?")
+        ),
+        Ok((status, _)) => warn!(
+            "‼️📶 Captive portal suspected: connectivity check returned Status {}, expected {}",
+            status, CONNECTIVITY_PROBE_EXPECTED_STATUS
+        ),
+        Err(e) => warn!("‼️📶 Connectivity check failed: {:?}", e),
+    }
+}
+
+/// GETs `url` without following redirects; returns the status and any `Location` header.
+fn probe_status(url: &str) -> Result<(u16, Option<String>)> {
+    if is_proxy_enabled() {
+        let response = proxy::get(url, &[], 0)?;
+        return Ok((
+            response.status(),
+            response.header("Location").map(str::to_string),
+        ));
+    }
+
+    let config = Configuration {
+        use_global_ca_store: true,
+        crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
+        ..Default::default()
+    };
+    let mut client = HttpClientImpl::wrap(EspHttpConnection::new(&config)?);
+
+    let mut response = client.get(url)?.submit()?;
+    let status = response.status();
+    let location = response.header("Location").map(str::to_string);
+    drain_body(&mut response)?;
+
+    Ok((status, location))
+}
+
+/// Outcome of a remote config fetch.
+pub(crate) enum ConfigResponse {
+    /// 304: the stored ETag still matches, keep the current config.
+    NotModified,
+    Updated {
+        body: Vec<u8>,
+        /// `None` when the server sent no `ETag`; the next fetch is then unconditional.
+        etag: Option<String>,
+    },
+    Failed(u16),
+}
+
+impl ConfigResponse {
+    /// `body` and `etag` only count for a 2xx.
+    fn new(status: u16, body: Vec<u8>, etag: Option<String>) -> Self {
+        match status {
+            304 => Self::NotModified,
+            200..=299 => Self::Updated { body, etag },
+            _ => Self::Failed(status),
+        }
+    }
+}
+
+pub(crate) struct HttpClient {
+    client: HttpClientImpl<EspHttpConnection>,
+    /// Cleared when a response couldn't be read to its end, see `drain_accepted`.
+    reusable: bool,
+}
+
+impl HttpClient {
+    pub(crate) fn new() -> Result<Self> {
+        let (client_certificate, private_key) = client_identity()?.unzip();
+        let server_certificate = pinned_server_certificate()?;
+        // A pinned certificate replaces the CA bundle as the sole trust anchor
+        let trust_bundle = server_certificate.is_none();
+
+        let config = Configuration {
+            use_global_ca_store: trust_bundle,
+            crt_bundle_attach: trust_bundle.then_some(esp_idf_svc::sys::esp_crt_bundle_attach),
+            server_certificate,
+            client_certificate,
+            private_key,
+            ..Default::default()
+        };
+
+        let connection = EspHttpConnection::new(&config)?;
+
+        let client = HttpClientImpl::wrap(connection);
+
+        Ok(Self {
+            client,
+            reusable: true,
+        })
+    }
+
+    /// `false` once the connection was left mid-response; the next request needs a new client.
+    pub(crate) fn is_reusable(&self) -> bool {
+        self.reusable
+    }
+
+    /// Posts a one-off device event such as `scheduled_reboot` to the sample endpoint, as
+    /// `{"event": ..., "device_id": ..., "timestamp_unix_s": ...}`. Always JSON, whatever the
+    /// `PAYLOAD_FORMAT`, so the server can tell it apart from a sample by the `event` key.
+    pub(crate) fn post_event(&mut self, url: &str, event: &str) -> Result<u16> {
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "event": event,
+            "device_id": device_id(),
+            "timestamp_unix_s": timestamp_unix_s(),
+        }))?;
+
+        if LOG_PAYLOAD {
+            log_payload(&payload);
+        }
+
+        self.post_body(url, &payload, "application/json", None)
+    }
+
+    /// Posts a ready-made JSON body as is: no signature, no `PAYLOAD_FORMAT`. For third-party APIs
+    /// such as Telegram.
+    pub(crate) fn post_json(&mut self, url: &str, payload: &[u8]) -> Result<u16> {
+        let len = payload.len().to_string();
+        let headers = [
+            ("Content-Type", "application/json"),
+            ("Content-Length", len.as_str()),
+        ];
+
+        if is_proxy_enabled() {
+            return proxy::post(url, &headers, payload).map(|(status, _)| status);
+        }
+
+        let mut request = self.client.post(url, &headers)?;
+        request.write_all(payload)?;
+
+        let mut response = request.submit()?;
+
+        let status = response.status();
+        drain_accepted(&mut response, status, &mut self.reusable)?;
+        Ok(status)
+    }
+
+    /// GETs the remote config, conditional on `etag` (`If-None-Match`) when one is stored.
+    pub(crate) fn fetch_config(&mut self, url: &str, etag: Option<&str>) -> Result<ConfigResponse> {
+        let headers: &[(&str, &str)] = match etag {
+            Some(etag) => &[("Accept", "application/json"), ("If-None-Match", etag)],
+            None => &[("Accept", "application/json")],
+        };
+
+        if is_proxy_enabled() {
+            let response = proxy::get(url, headers, MAX_CONFIG_BODY_LEN)?;
+            let etag = response.header("ETag").map(str::to_string);
+            return Ok(ConfigResponse::new(response.status(), response.body, etag));
+        }
+
+        let mut response = self.client.request(Method::Get, url, headers)?.submit()?;
+        let status = response.status();
+
+        if !(200..300).contains(&status) {
+            drain_body(&mut response)?;
+            return Ok(ConfigResponse::new(status, Vec::new(), None));
+        }
+
+        let etag = response.header("ETag").map(str::to_string);
+        let body = read_body(&mut response, MAX_CONFIG_BODY_LEN)?;
+
+        Ok(ConfigResponse::new(status, body, etag))
+    }
+
+    /// GETs the server's delivery status and checks that its `DELIVERY_VERIFY_FIELD` timestamp
+    /// has caught up with `sent_timestamp_unix_s`. `Ok(false)` means the server answered but
+    /// hasn't seen the sample.
+    pub(crate) fn verify_delivery(
+        &mut self,
+        url: &str,
+        sent_timestamp_unix_s: i64,
+    ) -> Result<bool> {
+        let headers = [("Accept", "application/json")];
+        let (status, body) = if is_proxy_enabled() {
+            let response = proxy::get(url, &headers, MAX_STATUS_BODY_LEN)?;
+            (response.status(), response.body)
+        } else {
+            let mut response = self.client.request(Method::Get, url, &headers)?.submit()?;
+            let status = response.status();
+            if (200..300).contains(&status) {
+                (status, read_body(&mut response, MAX_STATUS_BODY_LEN)?)
+            } else {
+                drain_body(&mut response)?;
+                (status, Vec::new())
+            }
+        };
+
+        if !(200..300).contains(&status) {
+            anyhow::bail!("‼️ Delivery status returned Status {}", status);
+        }
+
+        let last_seen = serde_json::from_slice::<serde_json::Value>(&body)?
+            .get(DELIVERY_VERIFY_FIELD)
+            .and_then(serde_json::Value::as_i64)
+            .ok_or_else(|| {
+                anyhow::anyhow!("‼️ Delivery status has no `{}`", DELIVERY_VERIFY_FIELD)
+            })?;
+
+        Ok(last_seen >= sent_timestamp_unix_s)
+    }
+
+    /// Posts a body that is complete in memory, signed over the bytes as sent (i.e. compressed).
+    fn post_body(
+        &mut self,
+        url: &str,
+        payload: &[u8],
+        content_type: &str,
+        content_encoding: Option<&str>,
+    ) -> Result<u16> {
+        let len = payload.len().to_string();
+        let signature = signature_headers(payload)?;
+        let mut headers = vec![
+            ("Content-Type", content_type),
+            ("Content-Length", len.as_str()),
+        ];
+        if let Some(content_encoding) = content_encoding {
+            headers.push(("Content-Encoding", content_encoding));
+        }
+        let headers = with_signature(headers, &signature);
+
+        follow_redirects(url, |url| {
+            if is_proxy_enabled() {
+                return proxy::post(url, &headers, payload);
+            }
+
+            let mut request = self.client.post(url, &headers)?;
+
+            request.write_all(payload)?;
+
+            let mut response = request.submit()?;
+
+            let status = response.status();
+            let location = response.header("Location").map(str::to_string);
+            drain_accepted(&mut response, status, &mut self.reusable)?;
+            Ok((status, location))
+        })
+    }
+
+    /// One streamed `post_batch` request; a redirect streams the batch again from the start.
+    fn stream_batch(
+        &mut self,
+        url: &str,
+        headers: &[(&str, &str)],
+        batch: &[WeatherData],
+    ) -> Result<(u16, Option<String>)> {
+        let mut request = self.client.post(url, headers)?;
+
+        write_chunk(&mut request, b"[")?;
+        for (index, data) in batch.iter().enumerate() {
+            if index > 0 {
+                write_chunk(&mut request, b",")?;
+            }
+            let element = json_payload(data)?;
+            if LOG_PAYLOAD {
+                log_payload(&element);
+            }
+            write_chunk(&mut request, &element)?;
+        }
+        write_chunk(&mut request, b"]")?;
+
+        // Zero-length chunk terminates the body
+        request.write_all(b"0\r\n\r\n")?;
+
+        let mut response = request.submit()?;
+
+        let status = response.status();
+        let location = response.header("Location").map(str::to_string);
+        drain_accepted(&mut response, status, &mut self.reusable)?;
+        Ok((status, location))
+    }
+
+    /// `PAYLOAD_FORMAT=ndjson`, gzip'd with the `gzip` feature.
+    fn post_ndjson(&mut self, url: &str, batch: &[WeatherData]) -> Result<u16> {
+        let body = to_ndjson(batch, json_payload)?;
+        if LOG_PAYLOAD {
+            log_payload(&body);
+        }
+
+        #[cfg(feature = "gzip")]
+        let (body, content_encoding) = (smog_core::gzip::compress(&body), Some("gzip"));
+        #[cfg(not(feature = "gzip"))]
+        let content_encoding = None;
+
+        self.post_body(url, &body, NDJSON_CONTENT_TYPE, content_encoding)
+    }
+}
+
+/// The delivery logic in `smog_core::delivery` posts to `HTTP_CONSUMER_ENDPOINT_URL`.
+impl Poster<WeatherData> for HttpClient {
+    type Error = anyhow::Error;
+
+    fn post_one(&mut self, data: &WeatherData) -> Result<u16> {
+        self.post_data(HTTP_CONSUMER_ENDPOINT_URL, data)
+    }
+
+    fn post_batch(&mut self, batch: &[WeatherData]) -> Result<u16> {
+        HttpClient::post_batch(self, HTTP_CONSUMER_ENDPOINT_URL, batch)
+    }
+}
+
+impl HttpClient {
+    /// Posts one sample as JSON or, with `PAYLOAD_FORMAT=form`, as a urlencoded form
+    /// (`ndjson`: a one-line batch).
+    fn post_data(&mut self, url: &str, data: &WeatherData) -> Result<u16> {
+        let (payload, content_type) = match payload_format() {
+            PayloadFormat::Json => (json_payload(data)?, "application/json"),
+            PayloadFormat::Form => (
+                to_form_urlencoded(&transform_for_output(data))?.into_bytes(),
+                "application/x-www-form-urlencoded",
+            ),
+            PayloadFormat::Ndjson => return self.post_ndjson(url, std::slice::from_ref(data)),
+        };
+
+        if LOG_PAYLOAD {
+            log_payload(&payload);
+        }
+
+        self.post_body(url, &payload, content_type, None)
+    }
+
+    /// Posts several samples as one JSON array, streamed element by element with chunked
+    /// transfer encoding, so memory use stays bounded by a single sample regardless of batch size.
+    /// NDJSON batches are built in memory instead, they may have to be compressed as a whole.
+    ///
+    /// Without a `Content-Length` header the ESP-IDF client opens the request as
+    /// `Transfer-Encoding: chunked`; the chunk framing itself has to be written here.
+    ///
+    /// A form can't carry several samples, so with `PAYLOAD_FORMAT=form` they are posted one by
+    /// one instead, stopping at the first that isn't accepted. The whole batch is retried in that
+    /// case, so samples accepted before it may arrive twice. The same goes for `HTTP_PROXY`,
+    /// whose hand-written requests don't do chunked encoding.
+    fn post_batch(&mut self, url: &str, batch: &[WeatherData]) -> Result<u16> {
+        if let PayloadFormat::Ndjson = payload_format() {
+            return self.post_ndjson(url, batch);
+        }
+
+        if matches!(payload_format(), PayloadFormat::Form) || is_proxy_enabled() {
+            let mut status = 0;
+            for data in batch {
+                status = self.post_data(url, data)?;
+                if !(200..300).contains(&status) {
+                    break;
+                }
+            }
+            return Ok(status);
+        }
+
+        // The signature header goes out before the streamed body, so signing takes a first pass
+        // over the serialized elements
+        let signature = match PayloadSigner::new()? {
+            Some(mut signer) => {
+                signer.update(b"[");
+                for (index, data) in batch.iter().enumerate() {
+                    if index > 0 {
+                        signer.update(b",");
+                    }
+                    signer.update(&json_payload(data)?);
+                }
+                signer.update(b"]");
+                signer.into_headers().to_vec()
+            }
+            None => Vec::new(),
+        };
+        let headers = with_signature(vec![("Content-Type", "application/json")], &signature);
+
+        follow_redirects(url, |url| self.stream_batch(url, &headers, batch))
+    }
+}
+
+/// Makes a request through `send`, which returns the response status and `Location`, and with
+/// `HTTP_MAX_REDIRECTS` set repeats it, same body and headers, against the target of each
+/// 301/302/307/308. A target visited before is a loop and fails right away, as does one redirect
+/// too many, one from `https://` to `http://` or one to another host (see `check_redirect`); a
+/// redirect without a `Location` is returned as is.
+fn follow_redirects(
+    url: &str,
+    mut send: impl FnMut(&str) -> Result<(u16, Option<String>)>,
+) -> Result<u16> {
+    let mut target = url.to_string();
+    let mut visited = Vec::new();
+
+    loop {
+        let (status, location) = send(&target)?;
+        let Some(location) =
+            location.filter(|_| HTTP_MAX_REDIRECTS > 0 && REDIRECT_STATUSES.contains(&status))
+        else {
+            return Ok(status);
+        };
+
+        if visited.len() >= usize::from(HTTP_MAX_REDIRECTS) {
+            anyhow::bail!(
+                "‼️ Too many redirects posting to {} (HTTP_MAX_REDIRECTS = {})",
+                url,
+                HTTP_MAX_REDIRECTS
+            );
+        }
+        let next = resolve_location(&target, &location);
+        if next == target || visited.contains(&next) {
+            anyhow::bail!("‼️ Redirect loop posting to {}: back at {}", url, next);
+        }
+        if let Err(refusal) = check_redirect(&target, &next) {
+            anyhow::bail!(
+                "‼️ Redirect from {} to {} not followed: {:?}",
+                target,
+                next,
+                refusal
+            );
+        }
+
+        info!(
+            "↪️ Status {} from {}, re-posting to {}",
+            status, target, next
+        );
+        visited.push(std::mem::replace(&mut target, next));
+    }
+}
+
+/// Appends the (possibly empty) signature headers to the fixed ones.
+fn with_signature<'a>(
+    mut headers: Vec<(&'a str, &'a str)>,
+    signature: &'a [(&'static str, String)],
+) -> Vec<(&'a str, &'a str)> {
+    headers.extend(
+        signature
+            .iter()
+            .map(|(name, value)| (*name, value.as_str())),
+    );
+    headers
+}
+
+/// Reads the whole response body, failing once it grows past `max_len`.
+fn read_body(reader: &mut impl Read<Error = EspIOError>, max_len: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut buf = [0u8; 128];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            return Ok(body);
+        }
+        if body.len() + read > max_len {
+            anyhow::bail!("‼️ Response body exceeds {} bytes", max_len);
+        }
+        body.extend_from_slice(&buf[..read]);
+    }
+}
+
+/// Reads the rest of the response so the connection is back in its initial state and can carry the
+/// next request; leftover body bytes are what trips "connection is not in the initial phase".
+fn drain_body(reader: &mut impl Read<Error = EspIOError>) -> Result<()> {
+    let mut buf = [0u8; 64];
+    while reader.read(&mut buf)? > 0 {}
+    Ok(())
+}
+
+/// `drain_body` after a post. A 2xx means the server has the request, so failing to read the rest
+/// of its response doesn't fail the post (and have it delivered again); the connection is only
+/// marked as not `reusable`. Any other status fails as before.
+fn drain_accepted(
+    reader: &mut impl Read<Error = EspIOError>,
+    status: u16,
+    reusable: &mut bool,
+) -> Result<()> {
+    match drain_body(reader) {
+        Err(e) if (200..300).contains(&status) => {
+            warn!(
+                "‼️ Status {} response not read to its end, dropping the connection: {:?}",
+                status, e
+            );
+            *reusable = false;
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn write_chunk(writer: &mut impl Write<Error = EspIOError>, data: &[u8]) -> Result<()> {
+    writer.write_all(format!("{:X}\r\n", data.len()).as_bytes())?;
+    writer.write_all(data)?;
+    writer.write_all(b"\r\n")?;
+    Ok(())
+}
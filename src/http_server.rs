@@ -0,0 +1,130 @@
+use crate::config::{CONFIG_TOKEN, SCHEMA_VERSION};
+use crate::history;
+use crate::output::transform_for_output;
+use crate::settings::{self, SettingsPatch};
+use crate::time_utils::uptime_us;
+use anyhow::Result;
+use embedded_svc::http::Headers;
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::http::server::{Configuration, EspHttpConnection, EspHttpServer, Request};
+use log::{info, warn};
+
+/// Also advertised over mDNS with the `mdns` feature.
+pub(crate) const PORT: u16 = 80;
+const MAX_BODY_LEN: usize = 512;
+/// `/history` readings without a `limit`, and the most one request can ask for: each one is
+/// copied out of the buffer before streaming.
+const DEFAULT_HISTORY_LIMIT: usize = 60;
+const MAX_HISTORY_LIMIT: usize = 256;
+const JSON_HEADERS: [(&str, &str); 1] = [("Content-Type", "application/json")];
+
+/// Local HTTP server. The returned server must be kept alive for the handlers to stay registered.
+///
+/// - `GET /config` returns the current runtime settings as JSON
+/// - `POST /config` applies a partial update (validated, persisted to NVS)
+/// - `GET /history[?limit=N]` returns the newest `N` buffered readings (default 60, at most
+///   256) as a JSON array, newest first
+/// - `GET /healthz` returns a liveness answer with the firmware and payload schema versions
+///
+/// The `/config` endpoints require the `X-Config-Token` header to match `CONFIG_TOKEN`; with an
+/// empty token they are locked. `/history` and `/healthz` only expose readings and versions and
+/// are open.
+pub(crate) fn start() -> Result<EspHttpServer<'static>> {
+    let mut server = EspHttpServer::new(&Configuration {
+        http_port: PORT,
+        ..Default::default()
+    })?;
+
+    server.fn_handler("/history", Method::Get, |req| -> Result<()> {
+        let limit = query_param(req.uri(), "limit")
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .min(MAX_HISTORY_LIMIT);
+        let readings = history::newest_first(limit);
+
+        // Streamed element by element: a full buffer serialized at once would need tens of KB
+        let mut response = req.into_response(200, None, &JSON_HEADERS)?;
+        response.write_all(b"[")?;
+        for (index, data) in readings.enumerate() {
+            if index > 0 {
+                response.write_all(b",")?;
+            }
+            response.write_all(&serde_json::to_vec(&transform_for_output(&data))?)?;
+        }
+        response.write_all(b"]")?;
+        Ok(())
+    })?;
+
+    server.fn_handler("/healthz", Method::Get, |req| -> Result<()> {
+        let health = serde_json::json!({
+            "status": "ok",
+            "fw_version": env!("CARGO_PKG_VERSION"),
+            "schema_version": SCHEMA_VERSION,
+            "uptime_s": uptime_us() / 1_000_000,
+        });
+        respond(req, 200, &serde_json::to_vec(&health)?)
+    })?;
+
+    server.fn_handler("/config", Method::Get, |req| -> Result<()> {
+        if !is_authorized(&req) {
+            return respond(req, 401, b"{\"error\":\"unauthorized\"}");
+        }
+
+        respond(req, 200, &serde_json::to_vec(&settings::current())?)
+    })?;
+
+    server.fn_handler("/config", Method::Post, |mut req| -> Result<()> {
+        if !is_authorized(&req) {
+            return respond(req, 401, b"{\"error\":\"unauthorized\"}");
+        }
+
+        let len = req.content_len().unwrap_or(0) as usize;
+        if len == 0 || len > MAX_BODY_LEN {
+            return respond(req, 400, b"{\"error\":\"invalid body length\"}");
+        }
+
+        let mut body = vec![0u8; len];
+        req.read_exact(&mut body)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+
+        let result = serde_json::from_slice::<SettingsPatch>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(settings::apply);
+
+        match result {
+            Ok(updated) => respond(req, 200, &serde_json::to_vec(&updated)?),
+            Err(e) => {
+                warn!("🌐 Rejected config update: {}", e);
+                let error = serde_json::json!({ "error": e.to_string() });
+                respond(req, 400, &serde_json::to_vec(&error)?)
+            }
+        }
+    })?;
+
+    if CONFIG_TOKEN.is_empty() {
+        warn!("🌐 HTTP server: CONFIG_TOKEN is empty, /config is locked");
+    }
+    info!("🌐 HTTP server listening on port {}", PORT);
+
+    Ok(server)
+}
+
+fn is_authorized(req: &Request<&mut EspHttpConnection>) -> bool {
+    !CONFIG_TOKEN.is_empty() && req.header("X-Config-Token") == Some(CONFIG_TOKEN)
+}
+
+/// Value of `key` in the query string of `uri`, e.g. `limit` in `/history?limit=10`.
+fn query_param<'a>(uri: &'a str, key: &str) -> Option<&'a str> {
+    let (_, query) = uri.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(name, value)| (name == key).then_some(value))
+}
+
+fn respond(req: Request<&mut EspHttpConnection>, status: u16, body: &[u8]) -> Result<()> {
+    let mut response = req.into_response(status, None, &JSON_HEADERS)?;
+    response.write_all(body)?;
+    Ok(())
+}
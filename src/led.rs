@@ -0,0 +1,146 @@
+use crate::config::AQ_LED_BRIGHTNESS;
+use crate::models::{VocCategory, WeatherData};
+use anyhow::Context;
+use esp_idf_svc::hal::gpio::{AnyOutputPin, Output, PinDriver};
+use esp_idf_svc::hal::rmt::config::TransmitConfig;
+use esp_idf_svc::hal::rmt::{CHANNEL0, FixedLengthSignal, PinState, Pulse, TxRmtDriver};
+use log::warn;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+// WS2812 bit timings
+const T0H: Duration = Duration::from_nanos(350);
+const T0L: Duration = Duration::from_nanos(800);
+const T1H: Duration = Duration::from_nanos(700);
+const T1L: Duration = Duration::from_nanos(600);
+
+// A std (FreeRTOS) mutex rather than a critical section: the RMT transfer waits on an interrupt.
+static AIR_QUALITY_LED: OnceLock<Mutex<AirQualityLed>> = OnceLock::new();
+
+/// What the status LED shows.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum LedPattern {
+    /// Quiet hours: the station is not talking to anyone.
+    Off,
+    /// Running normally.
+    SlowBlink,
+    /// WiFi down or the last delivery failed.
+    FastBlink,
+}
+
+impl LedPattern {
+    /// (on, off) durations of one blink cycle in milliseconds.
+    pub(crate) fn timing_ms(self) -> (u64, u64) {
+        match self {
+            LedPattern::Off => (0, 1000),
+            LedPattern::SlowBlink => (100, 1900),
+            LedPattern::FastBlink => (100, 100),
+        }
+    }
+}
+
+/// A plain active-high LED on a GPIO selected at build time (`STATUS_LED_GPIO`).
+pub(crate) struct StatusLed {
+    pin: PinDriver<'static, AnyOutputPin, Output>,
+}
+
+impl StatusLed {
+    /// # Safety
+    ///
+    /// `gpio` must be a valid output pin that no other driver uses (I2C is on 6/7, the
+    /// lighthouse on 8, wind/rain on 2/3).
+    pub(crate) unsafe fn new(gpio: i32) -> anyhow::Result<Self> {
+        let pin = PinDriver::output(unsafe { AnyOutputPin::new(gpio) })
+            .context("‼️ Failed to init status LED GPIO")?;
+        Ok(Self { pin })
+    }
+
+    pub(crate) fn set(&mut self, on: bool) {
+        // Writing an output level on a configured pin can't fail in practice; a status LED
+        // isn't worth surfacing an error for anyway.
+        let _ = if on {
+            self.pin.set_high()
+        } else {
+            self.pin.set_low()
+        };
+    }
+}
+
+/// A single WS2812 / NeoPixel driven by the RMT peripheral, colored by the VOC category.
+pub(crate) struct AirQualityLed {
+    tx: TxRmtDriver<'static>,
+}
+
+impl AirQualityLed {
+    /// # Safety
+    ///
+    /// Same as `StatusLed::new`: `gpio` must not be used by any other driver.
+    pub(crate) unsafe fn new(channel: CHANNEL0, gpio: i32) -> anyhow::Result<Self> {
+        let tx = TxRmtDriver::new(
+            channel,
+            unsafe { AnyOutputPin::new(gpio) },
+            &TransmitConfig::new().clock_divider(1),
+        )
+        .context("‼️ Failed to init air-quality LED (RMT)")?;
+        Ok(Self { tx })
+    }
+
+    pub(crate) fn show(&mut self, voc: Option<u16>) -> anyhow::Result<()> {
+        let (r, g, b) = match voc.map(VocCategory::from_index) {
+            Some(VocCategory::Good) => (0, 255, 0),
+            Some(VocCategory::Moderate) => (255, 180, 0),
+            Some(VocCategory::Poor) => (255, 80, 0),
+            Some(VocCategory::Bad) => (255, 0, 0),
+            None => (0, 0, 0),
+        };
+        self.write_rgb(dim(r), dim(g), dim(b))
+    }
+
+    fn write_rgb(&mut self, r: u8, g: u8, b: u8) -> anyhow::Result<()> {
+        // WS2812 expects green, red, blue, most significant bit first
+        let grb = (u32::from(g) << 16) | (u32::from(r) << 8) | u32::from(b);
+
+        let ticks_hz = self.tx.counter_clock()?;
+        let zero = (
+            Pulse::new_with_duration(ticks_hz, PinState::High, &T0H)?,
+            Pulse::new_with_duration(ticks_hz, PinState::Low, &T0L)?,
+        );
+        let one = (
+            Pulse::new_with_duration(ticks_hz, PinState::High, &T1H)?,
+            Pulse::new_with_duration(ticks_hz, PinState::Low, &T1L)?,
+        );
+
+        let mut signal = FixedLengthSignal::<24>::new();
+        for bit in 0..24 {
+            let is_set = grb & (1 << (23 - bit)) != 0;
+            signal.set(bit, if is_set { &one } else { &zero })?;
+        }
+
+        self.tx.start_blocking(&signal)?;
+        Ok(())
+    }
+}
+
+/// Hands the LED over to the `show_air_quality` observer.
+pub(crate) fn install_air_quality_led(led: AirQualityLed) {
+    let _ = AIR_QUALITY_LED.set(Mutex::new(led));
+}
+
+/// Reading observer: recolors the air-quality LED.
+pub(crate) fn show_air_quality(data: &WeatherData) {
+    let Some(led) = AIR_QUALITY_LED.get() else {
+        return;
+    };
+
+    let Ok(mut led) = led.lock() else {
+        return;
+    };
+
+    if let Err(e) = led.show(data.voc) {
+        warn!("⚠️ Air-quality LED update failed: {:?}", e);
+    }
+}
+
+fn dim(channel: u8) -> u8 {
+    (u16::from(channel) * u16::from(AQ_LED_BRIGHTNESS) / 255) as u8
+}
@@ -1,5 +1,11 @@
+use crate::device::device_id;
 use crate::models::WeatherData;
-use crate::time_utils::get_formatted_timestamp;
+use crate::sensors::AirSample;
+use crate::time_utils::{
+    effective_timezone_name, get_formatted_timestamp, timezone_offset_description,
+};
+#[cfg(feature = "http")]
+use log::debug;
 use log::{error, info, warn};
 
 const SPLASH_SCREEN: &str = r#"
@@ -10,6 +16,11 @@ const SPLASH_SCREEN: &str = r#"
  |____/|_| |_| |_|\___/ \__, |     |_| \_\___/
                         |___/                         "#;
 
+/// `CALIBRATION_MODE` columns. Keep the order stable and only append, so captures from different
+/// firmware versions line up in the same spreadsheet.
+const CALIBRATION_CSV_HEADER: &str =
+    "uptime_ms,temperature_c,humidity_pct,pressure_hpa,voc_index,nox_index";
+
 const BME280_EMPTY_SAMPLE_MSG: &str = "\x1b[38;5;11m 〇 BME280 returned empty or partial data";
 
 pub(crate) enum LogLevel {
@@ -18,16 +29,102 @@ pub(crate) enum LogLevel {
     Error,
 }
 
-pub(crate) fn print_splash_screen() {
+/// `boot_count` 0 means the NVS counter couldn't be read.
+pub(crate) fn print_splash_screen(boot_count: u32) {
     info!("{}", SPLASH_SCREEN);
+    info!(
+        "\x1b[38;5;27m v{} | {} | boot #{}\x1b[0m",
+        env!("CARGO_PKG_VERSION"),
+        device_id(),
+        boot_count
+    );
+}
+
+/// States once which timezone the log timestamps are in, so shared logs are self-describing.
+pub(crate) fn log_timezone_info() {
+    info!(
+        "\x1b[38;5;27m🕒 Log timestamps are in {} ({})\x1b[0m",
+        effective_timezone_name(),
+        timezone_offset_description()
+    );
+}
+
+/// Expected: BME280 at 0x76/0x77, SGP40 at 0x59 (and BH1750 at 0x23/0x5C if fitted).
+pub(crate) fn log_i2c_scan(addresses: &[u8]) {
+    if addresses.is_empty() {
+        warn!("\x1b[38;5;11m🔎 I2C scan: no devices responded, check the wiring\x1b[0m");
+        return;
+    }
+
+    let found: Vec<String> = addresses
+        .iter()
+        .map(|address| format!("0x{:02X}", address))
+        .collect();
+    info!("\x1b[38;5;27m🔎 I2C scan: {}\x1b[0m", found.join(", "));
+}
+
+/// Dumps an outgoing HTTP body at debug level (`LOG_PAYLOAD`); needs
+/// `CONFIG_LOG_DEFAULT_LEVEL_DEBUG` or higher in `sdkconfig.defaults` to show up. Text is logged
+/// as-is, anything else as hex.
+#[cfg(feature = "http")]
+pub(crate) fn log_payload(payload: &[u8]) {
+    match std::str::from_utf8(payload) {
+        Ok(text) => debug!("📦 Payload ({} bytes): {}", payload.len(), text),
+        Err(_) => {
+            let hex: String = payload.iter().map(|byte| format!("{:02X}", byte)).collect();
+            debug!("📦 Payload ({} bytes, binary): {}", payload.len(), hex)
+        }
+    }
+}
+
+/// Printed straight to the console rather than through the logger, so the CSV lines carry no log
+/// prefix and can be pasted as they are.
+pub(crate) fn print_calibration_header() {
+    println!("{}", CALIBRATION_CSV_HEADER);
+}
+
+/// One `CALIBRATION_CSV_HEADER` row; a missing channel, or a VOC/NOx index reused between
+/// SGP40/SGP41 polls, is an empty cell.
+pub(crate) fn print_calibration_row(uptime_ms: i64, sample: &AirSample) {
+    let cell = |value: Option<String>| value.unwrap_or_default();
+
+    println!(
+        "{},{},{},{},{},{}",
+        uptime_ms,
+        cell(
+            sample
+                .temperature
+                .map(|temperature| format!("{:.2}", temperature))
+        ),
+        cell(sample.humidity.map(|humidity| format!("{:.2}", humidity))),
+        cell(
+            sample
+                .pressure_pa
+                .map(|pressure| format!("{:.2}", pressure / 100.0))
+        ),
+        cell(
+            sample
+                .voc
+                .filter(|_| sample.voc_fresh)
+                .map(|voc| voc.to_string())
+        ),
+        cell(
+            sample
+                .nox
+                .filter(|_| sample.voc_fresh)
+                .map(|nox| nox.to_string())
+        ),
+    );
 }
 
 pub(crate) fn log_weather_data(data: &WeatherData) {
     let ts = get_formatted_timestamp();
 
     let env_msg = format!(
-        "[ 🌡️ Temp {:.2}C | 💧Humidity {:.2}% | ☁️ Pressure {:.2} hPa ]",
-        data.temperature, data.humidity, data.pressure
+        "[ 🌡️ Temp {}C | 💧Humidity {}% | ☁️ Pressure {} hPa ]",
+        format_optional(data.temperature),
+        format_optional(data.humidity),
+        format_optional(data.pressure)
     );
     log_message(LogLevel::Info, &env_msg, &ts);
 
@@ -35,6 +132,10 @@ pub(crate) fn log_weather_data(data: &WeatherData) {
         let voc_msg = format!("🍃 Indoor air quality (VOC) index: {}", voc);
         log_message(LogLevel::Info, &voc_msg, &ts);
     }
+    if let Some(nox) = data.nox_index {
+        let nox_msg = format!("🍃 NOx index: {}", nox);
+        log_message(LogLevel::Info, &nox_msg, &ts);
+    }
 }
 
 pub(crate) fn log_sensor_error(sensor_name: &str, error: impl std::fmt::Debug) {
@@ -53,6 +154,30 @@ pub(crate) fn log_empty_sample() {
     log_message(LogLevel::Warn, BME280_EMPTY_SAMPLE_MSG, &ts);
 }
 
+/// `ALLOW_PARTIAL_SAMPLES`: the sample is kept, but say which channels the BME280 left empty.
+pub(crate) fn log_partial_sample(humidity_missing: bool, pressure_missing: bool) {
+    let ts = get_formatted_timestamp();
+    let missing = match (humidity_missing, pressure_missing) {
+        (true, true) => "humidity and pressure",
+        (true, false) => "humidity",
+        _ => "pressure",
+    };
+
+    log_message(
+        LogLevel::Warn,
+        &format!(
+            "\x1b[38;5;11m ◐ BME280 sample without {}, sending the rest",
+            missing
+        ),
+        &ts,
+    );
+}
+
+/// Two decimals, or `-` for a channel missing from a partial sample.
+fn format_optional(value: Option<f32>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| format!("{:.2}", value))
+}
+
 fn log_message(level: LogLevel, message: &str, custom_ts: &str) {
     let uptime = crate::time_utils::get_uptime_string();
     let prefix = format!("{} [{}]", uptime, custom_ts);
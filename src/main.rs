@@ -1,81 +1,310 @@
+mod alarm;
+mod bh1750;
 mod config;
+mod device;
+mod display;
+#[cfg(feature = "http-server")]
+mod history;
+#[cfg(feature = "http")]
+mod http_client;
+#[cfg(feature = "http-server")]
+mod http_server;
+mod led;
 mod logging;
+#[cfg(feature = "mdns")]
+mod mdns;
 mod models;
+mod mq_sensor;
+#[cfg(feature = "mqtt")]
+mod mqtt;
 mod network;
+mod nvs;
+mod onewire;
+#[cfg(any(feature = "http", feature = "http-server", feature = "mqtt"))]
+mod output;
+mod provisioning;
+#[cfg(feature = "http")]
+mod proxy;
 mod sensors;
+#[cfg(feature = "serial-console")]
+mod serial_console;
+mod settings;
+#[cfg(feature = "sgp40")]
+mod sgp41;
+#[cfg(feature = "http")]
+mod signing;
+mod stats;
 mod tasks;
+#[cfg(feature = "http")]
+mod telegram;
 mod time_utils;
+#[cfg(feature = "http")]
+mod tls;
+mod watchpoints;
+mod wind_rain;
 
-use crate::config::I2C_BAUDRATE_HERTZ;
+use crate::alarm::Alarm;
+use crate::config::{
+    AQ_LED_GPIO, CALIBRATION_INTERVAL_MS, CALIBRATION_MODE, DISPLAY_ADDRESS, DISPLAY_ENABLED,
+    DS18B20_GPIO, I2C_BAUDRATE_HERTZ, I2C_SCAN_ON_BOOT, I2C_SCL_GPIO, I2C_SDA_GPIO,
+    LIGHTHOUSE_GPIO, LOCAL_ALERT_GPIO, MQ_SENSOR_ENABLED, STATUS_LED_GPIO, STRICT_BOOT,
+    STRICT_BOOT_TIMEOUT_MS, WIND_RAIN_ENABLED,
+};
+use crate::display::Display;
+use crate::led::{AirQualityLed, StatusLed};
+use crate::mq_sensor::MqSensor;
+use crate::onewire::Ds18b20;
 use crate::sensors::WeatherStation;
+use crate::wind_rain::WindRainGauge;
 use anyhow::{Context, anyhow};
 use embassy_executor::Spawner;
 use embassy_time::{Duration, Timer};
 use embedded_hal_bus::i2c::RefCellDevice;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
-use esp_idf_svc::hal::gpio::{Gpio8, Output, PinDriver};
-use esp_idf_svc::hal::i2c::{I2cConfig, I2cDriver};
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::gpio::{AnyIOPin, Gpio8, Output, PinDriver};
+use esp_idf_svc::hal::i2c::{I2C0, I2cConfig, I2cDriver};
 use esp_idf_svc::hal::peripherals::Peripherals;
 use esp_idf_svc::hal::units::Hertz;
 use esp_idf_svc::log::EspLogger;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use esp_idf_svc::sys::link_patches;
-use log::{error, info};
+use log::{error, info, warn};
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use smog_core::schedule::Sink;
 use std::cell::RefCell;
 
+const PANIC_REASON_NVS_KEY: &str = "last_panic";
+const MAX_PANIC_REASON_CHARS: usize = 256;
+
 type SharedI2cBus = RefCell<I2cDriver<'static>>;
 type I2cBusDevice = RefCellDevice<'static, I2cDriver<'static>>;
 
 async fn run(spawner: Spawner) -> anyhow::Result<()> {
-    logging::print_splash_screen();
-
     let peripherals = Peripherals::take().context("Failed to take Peripherals")?;
-    let _lighthouse_guard = disable_lighthouse(peripherals.pins.gpio8)?;
+    // The lighthouse is left to the air-quality indicator when that is wired to it
+    let _lighthouse_guard = if AQ_LED_GPIO == Some(LIGHTHOUSE_GPIO) {
+        None
+    } else {
+        Some(disable_lighthouse(peripherals.pins.gpio8)?)
+    };
 
     let system_event_loop = EspSystemEventLoop::take()?;
     let non_volatile_storage = EspDefaultNvsPartition::take()?;
 
+    nvs::init(non_volatile_storage.clone());
+    logging::print_splash_screen(nvs::increment_boot_count());
+    install_panic_handler();
+    report_previous_panic();
+    settings::load_from_nvs();
+
+    if CALIBRATION_MODE || nvs::take_calibration_boot_request() {
+        let i2c_bus = init_i2c_bus(peripherals.i2c0)?;
+        return run_calibration(i2c_bus).await;
+    }
+
     let _wifi_guard =
         network::setup_wifi(peripherals.modem, system_event_loop, non_volatile_storage).await?;
+    #[cfg(feature = "ntp")]
     let ntp_client = time_utils::setup_ntp().await?;
+    logging::log_timezone_info();
 
-    let i2c_controller = peripherals.i2c0;
-    let serial_data_pin = peripherals.pins.gpio6;
-    let serial_clock_pin = peripherals.pins.gpio7;
+    #[cfg(feature = "http-server")]
+    let _http_server = http_server::start().context("‼️ Failed to start HTTP server")?;
+    #[cfg(feature = "mdns")]
+    let _mdns = match mdns::start() {
+        Ok(mdns) => Some(mdns),
+        Err(e) => {
+            warn!(
+                "‼️ mDNS unavailable, the device is only reachable by IP: {:?}",
+                e
+            );
+            None
+        }
+    };
 
-    let i2c_driver = I2cDriver::new(
-        i2c_controller,
-        serial_data_pin,
-        serial_clock_pin,
-        &I2cConfig::new().baudrate(Hertz::from(I2C_BAUDRATE_HERTZ)),
-    )
-    .context("‼️ Failed to initialize I2C Driver")?;
+    let i2c_shared_bus = init_i2c_bus(peripherals.i2c0)?;
+
+    if I2C_SCAN_ON_BOOT {
+        logging::log_i2c_scan(&sensors::scan_i2c_bus(i2c_shared_bus));
+    }
+
+    let wind_rain = if WIND_RAIN_ENABLED {
+        let anemometer_pin = peripherals.pins.gpio2;
+        let rain_gauge_pin = peripherals.pins.gpio3;
+        let (gauge, reed_switches) = WindRainGauge::new(anemometer_pin, rain_gauge_pin)?;
+        for reed_switch in reed_switches {
+            spawner
+                .spawn(tasks::reed_switch_task(reed_switch))
+                .map_err(|_| anyhow!("‼️ Failed to spawn reed switch task"))?;
+        }
+        Some(gauge)
+    } else {
+        None
+    };
 
-    let i2c_shared_bus = Box::leak(Box::new(RefCell::new(i2c_driver)));
+    // SAFETY: `CLAIMED_GPIOS` in config.rs rejects a pin another driver takes at build time
+    let temperature_probe = DS18B20_GPIO.and_then(|gpio| match unsafe { Ds18b20::new(gpio) } {
+        Ok(probe) => {
+            info!("🌡️ DS18B20 probe detected on GPIO{}", gpio);
+            Some(probe)
+        }
+        Err(e) => {
+            warn!("🌡️ DS18B20 not detected, skipping probe readings: {:?}", e);
+            None
+        }
+    });
 
-    let station = WeatherStation::new(i2c_shared_bus).context("☔️ WS init error")?;
+    let gas_sensor = if MQ_SENSOR_ENABLED {
+        match MqSensor::new(peripherals.adc1, peripherals.pins.gpio1) {
+            Ok(gas_sensor) => {
+                info!("💨 MQ gas sensor on GPIO1, warming up");
+                Some(gas_sensor)
+            }
+            Err(e) => {
+                warn!(
+                    "💨 MQ gas sensor init failed, skipping gas readings: {:?}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let station = WeatherStation::new(i2c_shared_bus, wind_rain, temperature_probe, gas_sensor)
+        .context("☔️ WS init error")?;
     let static_station = Box::leak(Box::new(station));
 
     info!("\x1b[38;5;27m✅ Sensors initialized successfully!\x1b[0m");
 
     Timer::after(Duration::from_millis(1000)).await;
 
+    #[cfg(feature = "ntp")]
     spawner
         .spawn(tasks::ntp_watcher_task(ntp_client))
         .map_err(|_| anyhow!("‼️ Failed to spawn NTP watcher task"))?;
 
+    #[cfg(feature = "http")]
     spawner
         .spawn(tasks::network_task())
         .map_err(|_| anyhow!("‼️ Failed to spawn network task"))?;
+    #[cfg(feature = "http")]
+    tasks::register_sink(Sink::Http, tasks::queue_for_http);
+
+    #[cfg(feature = "mqtt")]
+    spawner
+        .spawn(tasks::mqtt_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn MQTT task"))?;
+    #[cfg(feature = "mqtt")]
+    tasks::register_sink(Sink::Mqtt, tasks::queue_for_mqtt);
+
+    tasks::register_observer(logging::log_weather_data);
+    tasks::register_observer(tasks::record_daily_summary);
+    if !config::WATCHPOINTS.is_empty() {
+        tasks::register_observer(watchpoints::check);
+    }
+    #[cfg(feature = "http-server")]
+    tasks::register_observer(history::record);
+
+    #[cfg(feature = "http")]
+    if telegram::is_enabled() {
+        tasks::register_observer(telegram::check_alert);
+        spawner
+            .spawn(tasks::telegram_task())
+            .map_err(|_| anyhow!("‼️ Failed to spawn Telegram task"))?;
+    }
+
+    if let Some(gpio) = AQ_LED_GPIO {
+        // SAFETY: `CLAIMED_GPIOS` in config.rs rejects a pin another driver takes at build time
+        let led = unsafe { AirQualityLed::new(peripherals.rmt.channel0, gpio) }?;
+        led::install_air_quality_led(led);
+        tasks::register_observer(led::show_air_quality);
+    }
+
+    if DISPLAY_ENABLED {
+        match Display::new(RefCellDevice::new(i2c_shared_bus), DISPLAY_ADDRESS) {
+            Ok(display) => {
+                info!("🖥️ SSD1306 display detected at 0x{:02X}", DISPLAY_ADDRESS);
+                tasks::register_observer(display::record_latest_reading);
+                spawner
+                    .spawn(tasks::display_task(display))
+                    .map_err(|_| anyhow!("‼️ Failed to spawn display task"))?;
+            }
+            Err(e) => warn!("🖥️ Display not detected, running without it: {:?}", e),
+        }
+    }
+
+    // The NTP watcher is already running, so a sync that missed the boot-time wait still counts
+    if STRICT_BOOT && !time_utils::is_time_synced() {
+        info!("⏳ Strict boot: holding the first reading until NTP has synced...");
+        if !time_utils::wait_first_sync(STRICT_BOOT_TIMEOUT_MS).await {
+            warn!(
+                "‼️ Strict boot: no NTP sync after {}s, starting the sensors anyway",
+                STRICT_BOOT_TIMEOUT_MS / 1000
+            );
+        }
+    }
 
     spawner
         .spawn(tasks::sensor_task(static_station))
         .map_err(|_| anyhow!("‼️ Failed to spawn sensor task"))?;
 
+    #[cfg(feature = "http")]
+    spawner
+        .spawn(tasks::network_silence_watchdog_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn network silence watchdog task"))?;
+
+    spawner
+        .spawn(tasks::health_report_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn health report task"))?;
+
+    spawner
+        .spawn(tasks::drop_summary_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn drop summary task"))?;
+
+    if let Some(gpio) = STATUS_LED_GPIO {
+        // SAFETY: `CLAIMED_GPIOS` in config.rs rejects a pin another driver takes at build time
+        let led = unsafe { StatusLed::new(gpio) }?;
+        spawner
+            .spawn(tasks::led_task(led))
+            .map_err(|_| anyhow!("‼️ Failed to spawn status LED task"))?;
+    }
+
+    if let Some(gpio) = LOCAL_ALERT_GPIO {
+        // SAFETY: `CLAIMED_GPIOS` in config.rs rejects a pin another driver takes at build time
+        let alarm =
+            unsafe { Alarm::new(gpio, peripherals.ledc.timer0, peripherals.ledc.channel0) }?;
+        spawner
+            .spawn(tasks::alarm_task(alarm))
+            .map_err(|_| anyhow!("‼️ Failed to spawn local alarm task"))?;
+    }
+
+    #[cfg(feature = "http")]
+    spawner
+        .spawn(tasks::remote_config_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn remote config task"))?;
+
+    spawner
+        .spawn(tasks::daily_rollover_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn daily rollover task"))?;
+
+    spawner
+        .spawn(tasks::scheduled_reboot_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn scheduled reboot task"))?;
+
+    spawner
+        .spawn(tasks::nvs_flush_task())
+        .map_err(|_| anyhow!("‼️ Failed to spawn NVS flush task"))?;
+
     spawner
         .spawn(tasks::reboot_supervisor_task())
         .map_err(|_| anyhow!("‼️ Failed to spawn reboot supervisor task"))?;
 
+    #[cfg(feature = "serial-console")]
+    serial_console::spawn()?;
+
     // IMPORTANT: The run function must not end immediately,
     // or the Wi-Fi/NTP resources might be dropped.
     loop {
@@ -83,6 +312,74 @@ async fn run(spawner: Spawner) -> anyhow::Result<()> {
     }
 }
 
+/// On `I2C_SDA_GPIO`/`I2C_SCL_GPIO`, the pins the bus recovery in sensors.rs drives by hand.
+fn init_i2c_bus(i2c_controller: I2C0) -> anyhow::Result<&'static SharedI2cBus> {
+    let i2c_driver = I2cDriver::new(
+        i2c_controller,
+        // SAFETY: build-time pin choices, nothing else takes these pins
+        unsafe { AnyIOPin::new(I2C_SDA_GPIO) },
+        unsafe { AnyIOPin::new(I2C_SCL_GPIO) },
+        &I2cConfig::new().baudrate(Hertz::from(I2C_BAUDRATE_HERTZ)),
+    )
+    .context("‼️ Failed to initialize I2C Driver")?;
+
+    Ok(Box::leak(Box::new(RefCell::new(i2c_driver))))
+}
+
+/// `CALIBRATION_MODE` (or the serial console's `calibrate`, for one boot): a tight loop printing
+/// raw readings as CSV in place of the regular tasks.
+/// Nothing touches the network, so nothing else needs to run; only returns on error.
+async fn run_calibration(i2c_bus: &'static SharedI2cBus) -> anyhow::Result<()> {
+    let mut station = WeatherStation::new(i2c_bus, None, None, None).context("☔️ WS init error")?;
+
+    warn!(
+        "📐 Calibration mode: WiFi off, raw readings every {} ms as CSV",
+        CALIBRATION_INTERVAL_MS
+    );
+    logging::print_calibration_header();
+
+    loop {
+        if let Some(sample) = station.read_raw_sample().await {
+            logging::print_calibration_row(time_utils::uptime_us() / 1000, &sample);
+        }
+        Timer::after(Duration::from_millis(CALIBRATION_INTERVAL_MS)).await;
+    }
+}
+
+/// Instead of halting on a panic, log it, persist the reason to NVS and reboot,
+/// mirroring what the reboot supervisor does for recoverable faults.
+fn install_panic_handler() {
+    std::panic::set_hook(Box::new(|panic_info| {
+        let reason: String = panic_info
+            .to_string()
+            .chars()
+            .take(MAX_PANIC_REASON_CHARS)
+            .collect();
+
+        error!("💥 Panic: {}", reason);
+
+        nvs::flush_all();
+        if let Err(e) = nvs::set_blob(PANIC_REASON_NVS_KEY, reason.as_bytes()) {
+            error!("💥 Failed to store panic reason: {:?}", e);
+        }
+
+        // Give the UART a moment to flush the log before restarting
+        FreeRtos::delay_ms(200);
+
+        unsafe { esp_idf_svc::sys::esp_restart() }
+    }));
+}
+
+fn report_previous_panic() {
+    if let Ok(Some(reason)) = nvs::get_blob(PANIC_REASON_NVS_KEY) {
+        warn!(
+            "💥 Previous boot ended in a panic: {}",
+            String::from_utf8_lossy(&reason)
+        );
+        let _ = nvs::remove(PANIC_REASON_NVS_KEY);
+    }
+}
+
 fn disable_lighthouse(gpio_pin: Gpio8) -> anyhow::Result<PinDriver<'static, Gpio8, Output>> {
     let mut led_data_pin_driver =
         PinDriver::output(gpio_pin).context("Failed to initialize PinDriver")?;
@@ -0,0 +1,46 @@
+use crate::config::MDNS_INSTANCE_NAME;
+use crate::device::hostname;
+#[cfg(feature = "http-server")]
+use crate::{device::device_id, http_server};
+use anyhow::{Context, Result};
+use esp_idf_svc::mdns::EspMdns;
+use log::info;
+
+/// `mdns`: answers for `<hostname>.local`, and with `http-server` advertises it as an `_http._tcp`
+/// service, so browsers and `dns-sd -B _http._tcp` find the device without its DHCP address. The
+/// returned responder must be kept alive for it to keep answering.
+///
+/// Multicast queries can go unanswered under `WIFI_POWER_SAVE=max`.
+pub(crate) fn start() -> Result<EspMdns> {
+    let mut mdns = EspMdns::take().context("‼️ Failed to start mDNS")?;
+    mdns.set_hostname(hostname())?;
+    mdns.set_instance_name(instance_name())?;
+
+    #[cfg(feature = "http-server")]
+    mdns.add_service(
+        None,
+        "_http",
+        "_tcp",
+        http_server::PORT,
+        &[
+            ("id", device_id()),
+            ("fw", env!("CARGO_PKG_VERSION")),
+            ("health", "/healthz"),
+        ],
+    )?;
+
+    info!(
+        "📡 mDNS: reachable as {}.local (\"{}\")",
+        hostname(),
+        instance_name()
+    );
+    Ok(mdns)
+}
+
+fn instance_name() -> &'static str {
+    if MDNS_INSTANCE_NAME.is_empty() {
+        hostname()
+    } else {
+        MDNS_INSTANCE_NAME
+    }
+}
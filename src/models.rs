@@ -1,12 +1,87 @@
-use serde::Serialize;
+use crate::network::WifiLink;
+use crate::stats::IntervalStats;
+use smog_core::change::Reading;
 
-#[derive(Serialize, Clone, Debug)]
+/// A single reading in device units (°C, %RH, hPa) at full precision. It never goes on the wire
+/// as is: `output::transform_for_output` applies units, rounding and the `SEND_*` selection.
+///
+/// `humidity`, `pressure` and `absolute_humidity_gm3` are `None` with `ALLOW_PARTIAL_SAMPLES`,
+/// for a BME280 sample missing that channel. A VOC-only sample, taken while the BME280 is
+/// unavailable, has none of them and no `temperature` either.
+#[derive(Clone, Debug)]
 pub(crate) struct WeatherData {
-    pub(crate) temperature: f32,
-    pub(crate) humidity: f32,
-    pub(crate) pressure: f32,
+    pub(crate) temperature: Option<f32>,
+    pub(crate) humidity: Option<f32>,
+    pub(crate) absolute_humidity_gm3: Option<f32>,
+    pub(crate) pressure: Option<f32>,
+    /// Sea-level pressure (QNH) for `STATION_ALTITUDE_M` (`SEND_PRESSURE_QNH`).
+    pub(crate) pressure_qnh_hpa: Option<f32>,
+    /// Straight from the BME280 sample, before any filtering (`SEND_RAW`); pressure only converted
+    /// to hPa.
+    pub(crate) temperature_raw: Option<f32>,
+    pub(crate) humidity_raw: Option<f32>,
+    pub(crate) pressure_raw: Option<f32>,
+    /// External DS18B20 probe; `None` without one or after a failed (CRC) read.
+    pub(crate) temperature_probe_c: Option<f32>,
+    /// Ambient temperature fused from the BME280 and the probe (`TEMPERATURE_FUSION`).
+    pub(crate) temperature_ambient_c: Option<f32>,
+    /// MQ-series gas sensor, approximate ppm; `None` without one and during its heater warm-up.
+    pub(crate) gas_ppm: Option<f32>,
     pub(crate) voc: Option<u16>,
+    /// SGP40 raw signal (`SEND_VOC_RAW`), measured once per send interval.
+    pub(crate) voc_raw: Option<u16>,
+    /// SGP41 NOx index (`VOC_SENSOR=sgp41`); `None` with an SGP40 and while the SGP41 conditions.
+    pub(crate) nox_index: Option<u16>,
+    pub(crate) lux: Option<f32>,
+    pub(crate) wind_speed_kmh: Option<f32>,
+    pub(crate) rain_mm: Option<f32>,
+    /// Sun above the horizon at `LOCATION_LAT_LON`; `None` without a location or synced time.
+    pub(crate) is_daytime: Option<bool>,
+    /// Current AP link (`SEND_WIFI_DIAGNOSTICS`); `None` when disabled or disconnected.
+    pub(crate) wifi: Option<WifiLink>,
+    pub(crate) anomaly: bool,
+    /// Sent out of band on a VOC jump (`VOC_ALERT_DELTA`) rather than on the regular schedule.
+    pub(crate) alert: bool,
+    /// Numbers the samples queued for sending since boot (set by `sensor_task`), so a gap on the
+    /// receiving end means lost samples and a reset means a reboot.
+    pub(crate) seq: u32,
+    /// Distribution over the send interval (`SEND_INTERVAL_PERCENTILES`), filled in when queued.
+    pub(crate) interval_stats: Option<IntervalStats>,
     pub(crate) time_synced: bool,
     pub(crate) timestamp_unix_s: i64,
+    /// Microseconds since boot (`esp_timer`), independent of NTP; restarts at 0 on every boot.
+    pub(crate) uptime_us: i64,
     pub(crate) timezone: &'static str,
 }
+
+impl WeatherData {
+    /// The core fields, as compared by the change detection.
+    pub(crate) fn reading(&self) -> Reading {
+        Reading {
+            temperature: self.temperature,
+            humidity: self.humidity,
+            pressure: self.pressure,
+            voc: self.voc,
+        }
+    }
+}
+
+/// Coarse air-quality bands of the Sensirion VOC index (100 is the learned average).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum VocCategory {
+    Good,
+    Moderate,
+    Poor,
+    Bad,
+}
+
+impl VocCategory {
+    pub(crate) fn from_index(voc: u16) -> Self {
+        match voc {
+            0..=150 => VocCategory::Good,
+            151..=250 => VocCategory::Moderate,
+            251..=400 => VocCategory::Poor,
+            _ => VocCategory::Bad,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::{
+    MQ_ADC_DIVIDER_RATIO, MQ_CURVE_A, MQ_CURVE_B, MQ_LOAD_RESISTANCE_KOHM, MQ_R0_KOHM,
+    MQ_SUPPLY_VOLTAGE, MQ_WARMUP_SECS,
+};
+use crate::sensors::Warmup;
+use anyhow::Context;
+use esp_idf_svc::hal::adc::ADC1;
+use esp_idf_svc::hal::adc::attenuation::DB_11;
+use esp_idf_svc::hal::adc::oneshot::config::AdcChannelConfig;
+use esp_idf_svc::hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+use esp_idf_svc::hal::gpio::Gpio1;
+
+/// Raw readings averaged per sample; the MQ output is noisy and one conversion is cheap.
+const OVERSAMPLING: u32 = 8;
+const ADC_MAX_RAW: f32 = 4095.0;
+/// Approximate full scale of the C3 ADC at 11 dB attenuation (uncalibrated).
+const ADC_FULL_SCALE_V: f32 = 2.5;
+
+type MqChannel = AdcChannelDriver<'static, Gpio1, AdcDriver<'static, ADC1>>;
+
+/// MQ-series gas sensor (e.g. MQ-135) on an ADC pin, converted to an approximate ppm.
+///
+/// The heater needs minutes to stabilize after power-up (and a ~24 h burn-in when new), so
+/// readings are withheld for `MQ_WARMUP_SECS`. The ppm figure follows the datasheet power-law
+/// curve `ppm = a * (Rs / R0)^b`: it is only as good as `MQ_R0_KOHM`, which has to be measured per
+/// sensor in clean air, and the sensor is cross-sensitive to most reducing gases and humidity.
+pub(crate) struct MqSensor {
+    channel: MqChannel,
+    warmup: Warmup,
+}
+
+impl MqSensor {
+    pub(crate) fn new(adc: ADC1, pin: Gpio1) -> anyhow::Result<Self> {
+        let adc = AdcDriver::new(adc).context("‼️ Failed to init ADC1")?;
+        let config = AdcChannelConfig {
+            attenuation: DB_11,
+            ..Default::default()
+        };
+        let channel =
+            AdcChannelDriver::new(adc, pin, &config).context("‼️ Failed to init MQ ADC channel")?;
+
+        Ok(Self {
+            channel,
+            warmup: Warmup::new(MQ_WARMUP_SECS),
+        })
+    }
+
+    /// `None` while warming up or when the ADC read fails.
+    pub(crate) fn read_ppm(&mut self) -> Option<f32> {
+        if !self.warmup.is_over() {
+            return None;
+        }
+
+        let mut sum = 0u32;
+        for _ in 0..OVERSAMPLING {
+            sum += u32::from(self.channel.read_raw().ok()?);
+        }
+        let adc_voltage = sum as f32 / OVERSAMPLING as f32 / ADC_MAX_RAW * ADC_FULL_SCALE_V;
+
+        ppm_from_voltage(adc_voltage * MQ_ADC_DIVIDER_RATIO)
+    }
+}
+
+/// Sensor output voltage (before the divider) to ppm; `None` at the rails where Rs is undefined.
+fn ppm_from_voltage(output_voltage: f32) -> Option<f32> {
+    if output_voltage <= 0.0 || output_voltage >= MQ_SUPPLY_VOLTAGE {
+        return None;
+    }
+
+    // Rs and RL form a divider across the supply: Vout = Vc * RL / (Rs + RL)
+    let rs_kohm = MQ_LOAD_RESISTANCE_KOHM * (MQ_SUPPLY_VOLTAGE - output_voltage) / output_voltage;
+    Some(MQ_CURVE_A * (rs_kohm / MQ_R0_KOHM).powf(MQ_CURVE_B))
+}
@@ -0,0 +1,190 @@
+use crate::config::{
+    HA_DISCOVERY_PREFIX, MQTT_AVAILABILITY_QOS, MQTT_BROKER_URL, MQTT_DISCOVERY_QOS, MQTT_PASSWORD,
+    MQTT_STATE_QOS, MQTT_STATE_RETAIN, MQTT_TOPIC_PREFIX, MQTT_USERNAME,
+};
+use crate::device::device_id;
+use crate::models::WeatherData;
+use crate::output::{
+    HUMIDITY_KEY, PRESSURE_KEY, TEMPERATURE_KEY, is_nox_sent, output_pressure_unit,
+    output_temperature_unit, transform_for_output,
+};
+use anyhow::Result;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use esp_idf_svc::mqtt::client::{
+    EspMqttClient, EventPayload, LwtConfiguration, MqttClientConfiguration, QoS,
+};
+use log::{info, warn};
+use serde_json::json;
+
+const AVAILABILITY_ONLINE: &[u8] = b"online";
+const AVAILABILITY_OFFLINE: &[u8] = b"offline";
+
+/// Raised from the MQTT event callback on every (re)connect, so the birth message can be
+/// re-published after the broker has sent our Last Will.
+pub(crate) static MQTT_CONNECTED_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// (object id, display name, device class, unit, `OutputData` field)
+type DiscoverySensor = (
+    &'static str,
+    &'static str,
+    Option<&'static str>,
+    Option<&'static str>,
+    &'static str,
+);
+
+/// Home Assistant sensors announced via MQTT discovery, with the units on the wire.
+fn discovery_sensors() -> [DiscoverySensor; 5] {
+    [
+        (
+            "temperature",
+            "Temperature",
+            Some("temperature"),
+            Some(output_temperature_unit().symbol()),
+            TEMPERATURE_KEY,
+        ),
+        (
+            "humidity",
+            "Humidity",
+            Some("humidity"),
+            Some("%"),
+            HUMIDITY_KEY,
+        ),
+        (
+            "pressure",
+            "Pressure",
+            Some("atmospheric_pressure"),
+            Some(output_pressure_unit().symbol()),
+            PRESSURE_KEY,
+        ),
+        // HA has no device class for a unitless VOC index
+        ("voc", "VOC Index", None, None, "voc"),
+        ("nox", "NOx Index", None, None, "nox"),
+    ]
+}
+
+pub(crate) struct MqttClient {
+    client: EspMqttClient<'static>,
+}
+
+impl MqttClient {
+    /// Connects with a retained Last Will of `offline` on the availability topic, so the broker
+    /// marks the device unavailable in Home Assistant if it drops off unexpectedly.
+    pub(crate) fn new() -> Result<Self> {
+        let availability_topic = availability_topic();
+        let config = MqttClientConfiguration {
+            client_id: Some(device_id()),
+            username: (!MQTT_USERNAME.is_empty()).then_some(MQTT_USERNAME),
+            password: (!MQTT_PASSWORD.is_empty()).then_some(MQTT_PASSWORD),
+            lwt: Some(LwtConfiguration {
+                topic: &availability_topic,
+                payload: AVAILABILITY_OFFLINE,
+                qos: qos(MQTT_AVAILABILITY_QOS),
+                retain: true,
+            }),
+            ..Default::default()
+        };
+
+        let client =
+            EspMqttClient::new_cb(MQTT_BROKER_URL, &config, |event| match event.payload() {
+                EventPayload::Connected(_) => {
+                    info!("📨 MQTT: Connected");
+                    MQTT_CONNECTED_SIGNAL.signal(());
+                }
+                EventPayload::Disconnected => warn!("📨 MQTT: Disconnected"),
+                EventPayload::Error(e) => warn!("📨 MQTT: Error {:?}", e),
+                _ => {}
+            })?;
+
+        Ok(Self { client })
+    }
+
+    /// Publishes retained Home Assistant discovery configs so the sensors appear without YAML.
+    ///
+    /// Messages are enqueued, so this is safe to call before the broker connection is up.
+    pub(crate) fn publish_discovery(&mut self) -> Result<()> {
+        let id = device_id();
+        let device = json!({
+            "identifiers": [id],
+            "name": format!("smog-rs {}", id),
+            "manufacturer": "smog-rs",
+            "model": "ESP32-C3",
+        });
+
+        for (object_id, name, device_class, unit, field) in discovery_sensors() {
+            // A build without the SGP40 never reports `voc`
+            if object_id == "voc" && !cfg!(feature = "sgp40") {
+                continue;
+            }
+            // Nor does anything but an SGP41 report `nox`
+            if object_id == "nox" && !is_nox_sent() {
+                continue;
+            }
+
+            let topic = format!("{}/sensor/{}/{}/config", HA_DISCOVERY_PREFIX, id, object_id);
+            let mut config = json!({
+                "name": name,
+                "unique_id": format!("{}_{}", id, object_id),
+                "state_topic": state_topic(),
+                "availability_topic": availability_topic(),
+                "value_template": format!("{{{{ value_json.{} }}}}", field),
+                "state_class": "measurement",
+                "device": device,
+            });
+            if let Some(device_class) = device_class {
+                config["device_class"] = device_class.into();
+            }
+            if let Some(unit) = unit {
+                config["unit_of_measurement"] = unit.into();
+            }
+
+            self.client.enqueue(
+                &topic,
+                qos(MQTT_DISCOVERY_QOS),
+                true,
+                &serde_json::to_vec(&config)?,
+            )?;
+        }
+
+        info!("📨 MQTT: Home Assistant discovery published");
+        Ok(())
+    }
+
+    /// Birth message: a retained `online` that overrides the Last Will after each (re)connect.
+    pub(crate) fn publish_online(&mut self) -> Result<()> {
+        self.client.enqueue(
+            &availability_topic(),
+            qos(MQTT_AVAILABILITY_QOS),
+            true,
+            AVAILABILITY_ONLINE,
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn publish_state(&mut self, data: &WeatherData) -> Result<()> {
+        let payload = serde_json::to_vec(&transform_for_output(data))?;
+        self.client.enqueue(
+            &state_topic(),
+            qos(MQTT_STATE_QOS),
+            MQTT_STATE_RETAIN,
+            &payload,
+        )?;
+        Ok(())
+    }
+}
+
+fn qos(level: u8) -> QoS {
+    match level {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+fn state_topic() -> String {
+    format!("{}/{}/state", MQTT_TOPIC_PREFIX, device_id())
+}
+
+fn availability_topic() -> String {
+    format!("{}/{}/availability", MQTT_TOPIC_PREFIX, device_id())
+}
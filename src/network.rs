@@ -1,15 +1,86 @@
-use crate::config::{WIFI_PASS, WIFI_SSID};
-use crate::models::WeatherData;
-use anyhow::Result;
-use embassy_time::Timer;
-use embedded_svc::http::client::Client as HttpClientImpl;
-use embedded_svc::io::Write;
+use crate::config::{
+    ALERT_RATE_LIMIT_BURST, ALERT_RATE_LIMIT_REFILL_MS, PROVISIONING_ENABLED,
+    PROVISIONING_FALLBACK_ATTEMPTS, WIFI_EAP_IDENTITY, WIFI_EAP_PASSWORD, WIFI_EAP_USERNAME,
+    WIFI_MAX_TX_POWER, WIFI_PASS, WIFI_SSID, WifiPowerSave, is_wifi_enterprise, wifi_power_save,
+};
+#[cfg(feature = "http")]
+use crate::config::{HTTP_LATENCY_BUCKETS_MS, HTTP_LATENCY_LOG_EVERY};
+use crate::device::hostname;
+use crate::provisioning;
+use anyhow::{Result, anyhow};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::{Instant, Timer};
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::modem::Modem;
-use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::{
+    esp, esp_eap_client_set_identity, esp_eap_client_set_password, esp_eap_client_set_username,
+    esp_wifi_get_max_tx_power, esp_wifi_get_ps, esp_wifi_set_max_tx_power, esp_wifi_set_ps,
+    esp_wifi_sta_enterprise_enable, esp_wifi_sta_get_ap_info, wifi_ap_record_t, wifi_auth_mode_t,
+    wifi_auth_mode_t_WIFI_AUTH_OPEN, wifi_auth_mode_t_WIFI_AUTH_WEP,
+    wifi_auth_mode_t_WIFI_AUTH_WPA_PSK, wifi_auth_mode_t_WIFI_AUTH_WPA_WPA2_PSK,
+    wifi_auth_mode_t_WIFI_AUTH_WPA2_ENTERPRISE, wifi_auth_mode_t_WIFI_AUTH_WPA2_PSK,
+    wifi_auth_mode_t_WIFI_AUTH_WPA2_WPA3_PSK, wifi_auth_mode_t_WIFI_AUTH_WPA3_PSK, wifi_ps_type_t,
+    wifi_ps_type_t_WIFI_PS_MAX_MODEM, wifi_ps_type_t_WIFI_PS_MIN_MODEM,
+    wifi_ps_type_t_WIFI_PS_NONE,
+};
 use esp_idf_svc::wifi::{AuthMethod, ClientConfiguration, Configuration as WifiConfig, EspWifi};
 use log::{info, warn};
+use smog_core::rate_limit::TokenBucket;
+use std::cell::{Cell, RefCell};
+
+const MAX_SSID_LEN: usize = 32;
+const MAX_PASSWORD_LEN: usize = 64;
+
+/// The AP the station is associated with, as last seen by `link_info`.
+static LAST_LINK: Mutex<CriticalSectionRawMutex, Cell<Option<WifiLink>>> =
+    Mutex::new(Cell::new(None));
+
+/// Response times of completed POSTs since boot, bucketed by `HTTP_LATENCY_BUCKETS_MS`.
+#[cfg(feature = "http")]
+static POST_LATENCY: Mutex<CriticalSectionRawMutex, RefCell<LatencyHistogram>> =
+    Mutex::new(RefCell::new(LatencyHistogram::new()));
+
+/// `ALERT_RATE_LIMIT_BURST`, shared by every out-of-band send path; built on first use.
+static OUT_OF_BAND_LIMIT: Mutex<CriticalSectionRawMutex, RefCell<Option<TokenBucket>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Association details of the current AP, for diagnosing roaming and interference.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WifiLink {
+    pub(crate) rssi_dbm: i8,
+    pub(crate) channel: u8,
+    pub(crate) bssid: [u8; 6],
+    pub(crate) auth_mode: &'static str,
+}
+
+impl WifiLink {
+    /// `aa:bb:cc:dd:ee:ff`
+    pub(crate) fn bssid_string(&self) -> String {
+        self.bssid
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+/// Takes a token for one out-of-band send (`ALERT_RATE_LIMIT_BURST`); `false` means the send
+/// should be skipped. Always `true` without a limit.
+pub(crate) fn allow_out_of_band_send() -> bool {
+    let Some(burst) = ALERT_RATE_LIMIT_BURST else {
+        return true;
+    };
+
+    let now_ms = Instant::now().as_millis();
+    OUT_OF_BAND_LIMIT.lock(|limit| {
+        limit
+            .borrow_mut()
+            .get_or_insert_with(|| TokenBucket::new(burst, ALERT_RATE_LIMIT_REFILL_MS, now_ms))
+            .try_take(now_ms)
+    })
+}
 
 pub(crate) async fn setup_wifi(
     modem: Modem,
@@ -18,17 +89,57 @@ pub(crate) async fn setup_wifi(
 ) -> Result<EspWifi<'static>> {
     let mut wifi = EspWifi::new(modem, sys_loop, Some(nvs))?;
 
-    wifi.set_configuration(&WifiConfig::Client(ClientConfiguration {
-        ssid: WIFI_SSID.try_into().expect("SSID is too long"),
-        password: WIFI_PASS.try_into().expect("Password is too long"),
-        auth_method: AuthMethod::WPA2Personal,
-        ..Default::default()
-    }))?;
+    // Sent with the DHCP request, so it has to be set before connecting
+    if let Err(e) = wifi.sta_netif_mut().set_hostname(hostname()) {
+        warn!("📶 Failed to set hostname {:?}: {:?}", hostname(), e);
+    }
+
+    // Credentials saved by provisioning win over the compiled-in ones
+    let stored = provisioning::stored_credentials();
+    if PROVISIONING_ENABLED && stored.is_none() {
+        info!("📶 No WiFi credentials stored, starting provisioning");
+        provisioning::run(&mut wifi).await?;
+    }
+    let fall_back_to_provisioning = PROVISIONING_ENABLED && stored.is_some();
+
+    if let Some(credentials) = stored {
+        info!("📶 Using the WiFi credentials stored in NVS");
+        wifi.set_configuration(&WifiConfig::Client(ClientConfiguration {
+            ssid: wifi_ssid(&credentials.ssid)?,
+            password: wifi_password(&credentials.password)?,
+            auth_method: if credentials.password.is_empty() {
+                AuthMethod::None
+            } else {
+                AuthMethod::WPA2Personal
+            },
+            ..Default::default()
+        }))?;
+    } else if is_wifi_enterprise() {
+        wifi.set_configuration(&WifiConfig::Client(ClientConfiguration {
+            ssid: wifi_ssid(WIFI_SSID)?,
+            auth_method: AuthMethod::WPA2Enterprise,
+            ..Default::default()
+        }))?;
+
+        configure_eap_credentials()?;
+    } else {
+        wifi.set_configuration(&WifiConfig::Client(ClientConfiguration {
+            ssid: wifi_ssid(WIFI_SSID)?,
+            password: wifi_password(WIFI_PASS)?,
+            auth_method: AuthMethod::WPA2Personal,
+            ..Default::default()
+        }))?;
+    }
 
     wifi.start()?;
 
     info!("📶 WiFi starting...");
 
+    if let Some(max_tx_power) = WIFI_MAX_TX_POWER {
+        apply_max_tx_power(max_tx_power)?;
+    }
+    apply_power_save(wifi_power_save())?;
+
     Timer::after_millis(500).await;
 
     let mut attempts = 0;
@@ -60,6 +171,14 @@ pub(crate) async fn setup_wifi(
             Err(e) => warn!("📶 Connect call failed: {:?}", e),
         }
 
+        if fall_back_to_provisioning && attempts >= PROVISIONING_FALLBACK_ATTEMPTS {
+            warn!(
+                "📶 Stored WiFi credentials failed {} connects, starting provisioning again",
+                attempts
+            );
+            provisioning::run(&mut wifi).await?;
+        }
+
         if attempts >= MAX_ATTEMPTS {
             anyhow::bail!("‼️📶 Failed to connect after {} attempts", attempts);
         }
@@ -71,45 +190,254 @@ pub(crate) async fn setup_wifi(
 
     let ip_info = wifi.sta_netif().get_ip_info()?;
     info!("📶 WiFi Connected! IP: {}", ip_info.ip);
+    link_info();
+
+    #[cfg(feature = "http")]
+    if !crate::config::CONNECTIVITY_PROBE_URL.is_empty() {
+        crate::http_client::probe_connectivity();
+    }
 
     Ok(wifi)
 }
 
-pub(crate) struct HttpClient {
-    client: HttpClientImpl<EspHttpConnection>,
+/// The driver stores credentials in fixed-size buffers; report an overflow instead of panicking.
+fn wifi_ssid<T: for<'a> TryFrom<&'a str>>(ssid: &str) -> Result<T> {
+    T::try_from(ssid).map_err(|_| {
+        anyhow!(
+            "‼️📶 WiFi SSID is {} bytes long, the maximum is {}",
+            ssid.len(),
+            MAX_SSID_LEN
+        )
+    })
 }
 
-impl HttpClient {
-    pub(crate) fn new() -> Result<Self> {
-        let config = Configuration {
-            use_global_ca_store: true,
-            crt_bundle_attach: Some(esp_idf_svc::sys::esp_crt_bundle_attach),
-            ..Default::default()
-        };
+fn wifi_password<T: for<'a> TryFrom<&'a str>>(password: &str) -> Result<T> {
+    T::try_from(password).map_err(|_| {
+        anyhow!(
+            "‼️📶 WiFi password is {} bytes long, the maximum is {}",
+            password.len(),
+            MAX_PASSWORD_LEN
+        )
+    })
+}
+
+/// Caps the radio TX power; only valid once the WiFi driver is started. The range is checked at
+/// build time, next to `WIFI_MAX_TX_POWER`.
+fn apply_max_tx_power(quarter_dbm: i8) -> Result<()> {
+    let mut effective = 0i8;
+    unsafe {
+        esp!(esp_wifi_set_max_tx_power(quarter_dbm))?;
+        esp!(esp_wifi_get_max_tx_power(&mut effective))?;
+    }
+
+    info!(
+        "📶 WiFi max TX power set to {:.2} dBm",
+        effective as f32 / 4.0
+    );
+    Ok(())
+}
+
+/// Sets the modem sleep mode; only valid once the WiFi driver is started.
+fn apply_power_save(mode: WifiPowerSave) -> Result<()> {
+    let requested = match mode {
+        WifiPowerSave::Off => wifi_ps_type_t_WIFI_PS_NONE,
+        WifiPowerSave::Min => wifi_ps_type_t_WIFI_PS_MIN_MODEM,
+        WifiPowerSave::Max => wifi_ps_type_t_WIFI_PS_MAX_MODEM,
+    };
 
-        let connection = EspHttpConnection::new(&config)?;
+    let mut effective: wifi_ps_type_t = 0;
+    unsafe {
+        esp!(esp_wifi_set_ps(requested))?;
+        esp!(esp_wifi_get_ps(&mut effective))?;
+    }
+
+    info!("📶 WiFi power save: {}", power_save_name(effective));
+    Ok(())
+}
+
+// The bindgen constants keep their C names
+#[allow(non_upper_case_globals)]
+fn power_save_name(mode: wifi_ps_type_t) -> &'static str {
+    match mode {
+        wifi_ps_type_t_WIFI_PS_NONE => "none",
+        wifi_ps_type_t_WIFI_PS_MIN_MODEM => "min (modem sleep per DTIM)",
+        wifi_ps_type_t_WIFI_PS_MAX_MODEM => "max (modem sleep per listen interval)",
+        _ => "other",
+    }
+}
+
+/// WPA2-Enterprise (PEAP): esp-idf-svc has no wrapper for the EAP client, so the credentials
+/// go straight to the supplicant. Must run before `wifi.start()`.
+fn configure_eap_credentials() -> Result<()> {
+    if WIFI_EAP_IDENTITY.is_empty() || WIFI_EAP_USERNAME.is_empty() || WIFI_EAP_PASSWORD.is_empty()
+    {
+        anyhow::bail!(
+            "‼️📶 WPA2-Enterprise selected but EAP identity/username/password is missing"
+        );
+    }
+
+    unsafe {
+        esp!(esp_eap_client_set_identity(
+            WIFI_EAP_IDENTITY.as_ptr(),
+            WIFI_EAP_IDENTITY.len() as i32
+        ))?;
+        esp!(esp_eap_client_set_username(
+            WIFI_EAP_USERNAME.as_ptr(),
+            WIFI_EAP_USERNAME.len() as i32
+        ))?;
+        esp!(esp_eap_client_set_password(
+            WIFI_EAP_PASSWORD.as_ptr(),
+            WIFI_EAP_PASSWORD.len() as i32
+        ))?;
+        esp!(esp_wifi_sta_enterprise_enable())?;
+    }
+
+    info!("📶 WPA2-Enterprise credentials configured");
+    Ok(())
+}
+
+/// The current AP's channel, BSSID, auth mode and signal, `None` while disconnected. Logs when
+/// the station has (re)connected to a different AP or channel since the last call, so roaming
+/// shows up in the log of whichever path polls it (boot, health report, payload).
+pub(crate) fn link_info() -> Option<WifiLink> {
+    let mut ap_info = wifi_ap_record_t::default();
+    esp!(unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) }).ok()?;
+
+    let link = WifiLink {
+        rssi_dbm: ap_info.rssi,
+        channel: ap_info.primary,
+        bssid: ap_info.bssid,
+        auth_mode: auth_mode_name(ap_info.authmode),
+    };
+
+    let previous = LAST_LINK.lock(|last| last.replace(Some(link)));
+    if previous
+        .is_none_or(|previous| previous.bssid != link.bssid || previous.channel != link.channel)
+    {
+        info!(
+            "📶 WiFi link: BSSID {} channel {} auth {} RSSI {} dBm",
+            link.bssid_string(),
+            link.channel,
+            link.auth_mode,
+            link.rssi_dbm
+        );
+    }
+
+    Some(link)
+}
 
-        let client = HttpClientImpl::wrap(connection);
+// The bindgen constants keep their C names
+#[allow(non_upper_case_globals)]
+fn auth_mode_name(auth_mode: wifi_auth_mode_t) -> &'static str {
+    match auth_mode {
+        wifi_auth_mode_t_WIFI_AUTH_OPEN => "open",
+        wifi_auth_mode_t_WIFI_AUTH_WEP => "wep",
+        wifi_auth_mode_t_WIFI_AUTH_WPA_PSK => "wpa_psk",
+        wifi_auth_mode_t_WIFI_AUTH_WPA2_PSK => "wpa2_psk",
+        wifi_auth_mode_t_WIFI_AUTH_WPA_WPA2_PSK => "wpa_wpa2_psk",
+        wifi_auth_mode_t_WIFI_AUTH_WPA2_ENTERPRISE => "wpa2_enterprise",
+        wifi_auth_mode_t_WIFI_AUTH_WPA3_PSK => "wpa3_psk",
+        wifi_auth_mode_t_WIFI_AUTH_WPA2_WPA3_PSK => "wpa2_wpa3_psk",
+        _ => "other",
+    }
+}
+
+/// Whether the station is currently associated with an AP, without needing the `EspWifi` handle.
+pub(crate) fn is_wifi_connected() -> bool {
+    let mut ap_info = wifi_ap_record_t::default();
+    esp!(unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) }).is_ok()
+}
 
-        Ok(Self { client })
+/// Counts per `HTTP_LATENCY_BUCKETS_MS` bucket plus one overflow bucket; a few bytes however many
+/// requests it has seen, at the cost of percentiles only as fine as the buckets.
+#[cfg(feature = "http")]
+struct LatencyHistogram {
+    counts: Vec<u32>,
+    total: u32,
+}
+
+#[cfg(feature = "http")]
+impl LatencyHistogram {
+    const fn new() -> Self {
+        Self {
+            counts: Vec::new(),
+            total: 0,
+        }
     }
 
-    pub(crate) fn post_data(&mut self, url: &str, data: &WeatherData) -> Result<u16> {
-        let payload = serde_json::to_vec(data)?;
-        let len = payload.len().to_string();
+    fn record(&mut self, elapsed_ms: u64) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; HTTP_LATENCY_BUCKETS_MS.len() + 1];
+        }
+        let bucket = HTTP_LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| elapsed_ms <= u64::from(bound))
+            .unwrap_or(HTTP_LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] = self.counts[bucket].saturating_add(1);
+        self.total = self.total.saturating_add(1);
+    }
 
-        let headers = [
-            ("Content-Type", "application/json"),
-            ("Content-Length", &len),
-        ];
+    /// The bucket holding the `p`-th percentile (0..=100) as `≤250ms`, or `>5000ms` for overflow.
+    fn percentile(&self, p: f32) -> Option<String> {
+        let rank = ((p / 100.0) * self.total as f32).ceil().max(1.0) as u32;
+        let mut seen = 0;
+        let bucket = self.counts.iter().position(|&count| {
+            seen += count;
+            seen >= rank
+        })?;
+        Some(bucket_label(bucket))
+    }
+}
 
-        let mut request = self.client.post(url, &headers)?;
+#[cfg(feature = "http")]
+fn bucket_label(bucket: usize) -> String {
+    match HTTP_LATENCY_BUCKETS_MS.get(bucket) {
+        Some(bound) => format!("≤{}ms", bound),
+        None => format!(
+            ">{}ms",
+            HTTP_LATENCY_BUCKETS_MS.last().copied().unwrap_or(0)
+        ),
+    }
+}
 
-        request.write_all(&payload)?;
+/// Adds a completed POST (connect, TLS handshake, upload and status) to the response-time
+/// histogram, logging the histogram every `HTTP_LATENCY_LOG_EVERY` requests.
+#[cfg(feature = "http")]
+pub(crate) fn record_post_latency(elapsed_ms: u64) {
+    let summary = POST_LATENCY.lock(|histogram| {
+        let mut histogram = histogram.borrow_mut();
+        histogram.record(elapsed_ms);
 
-        let response = request.submit()?;
+        let due = HTTP_LATENCY_LOG_EVERY > 0 && histogram.total % HTTP_LATENCY_LOG_EVERY == 0;
+        due.then(|| {
+            let buckets: Vec<String> = histogram
+                .counts
+                .iter()
+                .enumerate()
+                .map(|(bucket, count)| format!("{}:{}", bucket_label(bucket), count))
+                .collect();
+            (histogram.total, buckets.join(" "))
+        })
+    });
 
-        let status = response.status();
-        Ok(status)
+    if let Some((total, buckets)) = summary {
+        info!(
+            "⏱️ POST response times over {} requests: {} ({})",
+            total,
+            buckets,
+            post_latency_summary()
+        );
     }
 }
+
+/// `p50≤250ms p90≤1000ms`, or `-` before the first completed POST. For the health report.
+#[cfg(feature = "http")]
+pub(crate) fn post_latency_summary() -> String {
+    POST_LATENCY.lock(|histogram| {
+        let histogram = histogram.borrow();
+        match (histogram.percentile(50.0), histogram.percentile(90.0)) {
+            (Some(p50), Some(p90)) => format!("p50{} p90{}", p50, p90),
+            _ => "-".to_string(),
+        }
+    })
+}
@@ -0,0 +1,314 @@
+use crate::config::{NVS_MIN_FLUSH_INTERVAL_MS, NVS_RETRIES, NVS_RETRY_DELAY_MS};
+use anyhow::Context;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::once_lock::OnceLock;
+use embassy_time::{Duration, Instant};
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition};
+use esp_idf_svc::sys::{
+    ESP_ERR_FLASH_OP_FAIL, ESP_ERR_FLASH_OP_TIMEOUT, ESP_ERR_NVS_INVALID_STATE,
+    ESP_ERR_NVS_REMOVE_FAILED, ESP_ERR_TIMEOUT, ESP_FAIL, EspError, esp_err_t,
+};
+use log::warn;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const NAMESPACE: &str = "smog";
+const BOOT_COUNT_KEY: &str = "boot_count";
+const CALIBRATION_BOOT_KEY: &str = "calibrate";
+/// Errors another attempt can clear: the flash operation failed or timed out, or NVS marked its
+/// state inconsistent after one (reopening the handle, as every attempt does, is the recovery).
+const TRANSIENT_ERRORS: [esp_err_t; 6] = [
+    ESP_FAIL,
+    ESP_ERR_TIMEOUT as esp_err_t,
+    ESP_ERR_FLASH_OP_FAIL as esp_err_t,
+    ESP_ERR_FLASH_OP_TIMEOUT as esp_err_t,
+    ESP_ERR_NVS_INVALID_STATE as esp_err_t,
+    ESP_ERR_NVS_REMOVE_FAILED as esp_err_t,
+];
+
+static PARTITION: OnceLock<EspDefaultNvsPartition> = OnceLock::new();
+/// This boot's number, set once by `increment_boot_count`.
+static BOOT_COUNT: AtomicU32 = AtomicU32::new(0);
+/// Writes held in RAM until the next flush, one (the latest) value per key.
+static STAGED: Mutex<CriticalSectionRawMutex, RefCell<Vec<(&'static str, Staged)>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+/// When staged values were last written out; `None` until the first flush.
+static LAST_FLUSH: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    Mutex::new(Cell::new(None));
+
+/// A value waiting in `STAGED`.
+#[derive(Clone, PartialEq)]
+enum Staged {
+    /// The runtime settings, only patched over HTTP or the serial console.
+    #[cfg(any(feature = "http", feature = "http-server", feature = "serial-console"))]
+    Blob(Vec<u8>),
+    U32(u32),
+}
+
+/// Keeps a handle to the default NVS partition so any module can open the app namespace.
+pub(crate) fn init(partition: EspDefaultNvsPartition) {
+    let _ = PARTITION.init(partition);
+}
+
+// Every access below goes through `with_retries`, so callers get the retries and the failure log
+// for free. Setters leave an unchanged value alone: flash sectors wear with every erase, and most
+// writes (settings, ETags) repeat what is already stored. Getters see a staged value before the
+// stored one.
+
+pub(crate) fn get_blob(key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    #[cfg(any(feature = "http", feature = "http-server", feature = "serial-console"))]
+    if let Some(Staged::Blob(value)) = staged(key) {
+        return Ok(Some(value));
+    }
+    with_retries("read", key, |nvs| stored_blob(nvs, key))
+}
+
+pub(crate) fn set_blob(key: &str, value: &[u8]) -> anyhow::Result<()> {
+    with_retries("write", key, |nvs| {
+        if stored_blob(nvs, key)?.as_deref() != Some(value) {
+            nvs.set_blob(key, value)?;
+        }
+        Ok(())
+    })
+}
+
+pub(crate) fn get_u32(key: &str) -> anyhow::Result<Option<u32>> {
+    if let Some(Staged::U32(value)) = staged(key) {
+        return Ok(Some(value));
+    }
+    with_retries("read", key, |nvs| Ok(nvs.get_u32(key)?))
+}
+
+pub(crate) fn set_u32(key: &str, value: u32) -> anyhow::Result<()> {
+    with_retries("write", key, |nvs| {
+        if nvs.get_u32(key)? != Some(value) {
+            nvs.set_u32(key, value)?;
+        }
+        Ok(())
+    })
+}
+
+pub(crate) fn get_u8(key: &str) -> anyhow::Result<Option<u8>> {
+    with_retries("read", key, |nvs| Ok(nvs.get_u8(key)?))
+}
+
+#[cfg(feature = "serial-console")]
+pub(crate) fn set_u8(key: &str, value: u8) -> anyhow::Result<()> {
+    with_retries("write", key, |nvs| {
+        if nvs.get_u8(key)? != Some(value) {
+            nvs.set_u8(key, value)?;
+        }
+        Ok(())
+    })
+}
+
+/// Removing a key that isn't there is no write and no error. A staged value for it is dropped.
+pub(crate) fn remove(key: &str) -> anyhow::Result<()> {
+    STAGED.lock(|staged| {
+        staged
+            .borrow_mut()
+            .retain(|(staged_key, _)| *staged_key != key)
+    });
+    with_retries("remove", key, |nvs| {
+        nvs.remove(key)?;
+        Ok(())
+    })
+}
+
+// Values written often, or in bursts (a run of console `set`s), are staged rather than written:
+// they go to flash on the first write after a quiet `NVS_MIN_FLUSH_INTERVAL_MS`, then at most once
+// per interval (`nvs_flush_task`), and before every restart (`flush_all`). A power cut or a hard
+// reset loses what was staged since the last flush.
+
+/// Stages `value` for `key`, see above; a failed flush is logged and retried on the next one.
+#[cfg(any(feature = "http", feature = "http-server", feature = "serial-console"))]
+pub(crate) fn stage_blob(key: &'static str, value: &[u8]) {
+    stage(key, Staged::Blob(value.to_vec()));
+}
+
+pub(crate) fn stage_u32(key: &'static str, value: u32) {
+    stage(key, Staged::U32(value));
+}
+
+/// How long until what is staged is due for `flush_due`: the rest of the interval since the last
+/// flush, or a whole interval with nothing staged (staging a value flushes it if already due).
+pub(crate) fn until_flush_due() -> Duration {
+    let interval = Duration::from_millis(NVS_MIN_FLUSH_INTERVAL_MS);
+    if STAGED.lock(|staged| staged.borrow().is_empty()) {
+        return interval;
+    }
+
+    LAST_FLUSH.lock(Cell::get).map_or(Duration::MIN, |at| {
+        interval.checked_sub(at.elapsed()).unwrap_or(Duration::MIN)
+    })
+}
+
+/// Writes out what is staged, if the last flush is at least `NVS_MIN_FLUSH_INTERVAL_MS` ago.
+pub(crate) fn flush_due() {
+    let due = LAST_FLUSH.lock(|last| {
+        last.get()
+            .is_none_or(|at| at.elapsed() >= Duration::from_millis(NVS_MIN_FLUSH_INTERVAL_MS))
+    });
+    if due {
+        flush_all();
+    }
+}
+
+/// Writes out everything staged, right away. Called before every restart: by the reboot
+/// supervisor, the panic handler and provisioning.
+///
+/// The panic may have struck while `STAGED` was borrowed (in `stage`, say), so it is only ever
+/// `try_borrow`ed here: a list caught mid-update is skipped rather than panicking in the hook.
+pub(crate) fn flush_all() {
+    let staged = STAGED.lock(|staged| staged.try_borrow().map(|staged| staged.clone()));
+    let Ok(staged) = staged else {
+        warn!("‼️ NVS: staged values are being updated, not flushed");
+        return;
+    };
+    if staged.is_empty() {
+        return;
+    }
+    LAST_FLUSH.lock(|last| last.set(Some(Instant::now())));
+
+    for (key, value) in staged {
+        let result = match &value {
+            #[cfg(any(feature = "http", feature = "http-server", feature = "serial-console"))]
+            Staged::Blob(blob) => set_blob(key, blob),
+            Staged::U32(number) => set_u32(key, *number),
+        };
+        // A failure is logged by `with_retries` and the value kept for the next flush. A value
+        // staged anew while this one was written stays staged too.
+        if result.is_ok() {
+            STAGED.lock(|staged| {
+                if let Ok(mut staged) = staged.try_borrow_mut() {
+                    staged.retain(|(staged_key, staged_value)| {
+                        *staged_key != key || *staged_value != value
+                    });
+                }
+            });
+        }
+    }
+}
+
+/// Counts this boot in NVS and returns its number, the first boot being 1. A failed read is
+/// logged and yields 0, "unknown", rather than a count that would restart or repeat.
+///
+/// The count is staged, but being the first write of a boot it is flushed right away.
+pub(crate) fn increment_boot_count() -> u32 {
+    let result = get_u32(BOOT_COUNT_KEY).map(|count| {
+        let count = count.unwrap_or(0).wrapping_add(1);
+        stage_u32(BOOT_COUNT_KEY, count);
+        count
+    });
+
+    let count = result.unwrap_or_else(|e| {
+        warn!("‼️ Failed to read the boot counter: {:?}", e);
+        0
+    });
+    BOOT_COUNT.store(count, Ordering::Relaxed);
+    count
+}
+
+/// The number `increment_boot_count` gave this boot; 0 if unknown.
+pub(crate) fn boot_count() -> u32 {
+    BOOT_COUNT.load(Ordering::Relaxed)
+}
+
+/// Makes the next boot run the calibration stream, as if `CALIBRATION_MODE` were set.
+#[cfg(feature = "serial-console")]
+pub(crate) fn request_calibration_boot() -> anyhow::Result<()> {
+    set_u8(CALIBRATION_BOOT_KEY, 1)
+}
+
+/// Whether this boot was asked to calibrate; clears the request, so the boot after is regular.
+pub(crate) fn take_calibration_boot_request() -> bool {
+    match get_u8(CALIBRATION_BOOT_KEY) {
+        Ok(Some(_)) => {
+            let _ = remove(CALIBRATION_BOOT_KEY);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Runs `operation` on the app namespace, retrying up to `NVS_RETRIES` times after a transient
+/// failure (`TRANSIENT_ERRORS`) with a delay that starts at `NVS_RETRY_DELAY_MS` and doubles: a
+/// flash busy with a page erase or a concurrent commit usually answers on the next try. Anything
+/// else (NVS used before init, out of space, a bad key or length) fails the same way every time and
+/// is not retried. The final error is logged here, once, and returned for the caller to decide
+/// what it means.
+///
+/// Blocks for the delays (70 ms in total with the defaults), which NVS itself does anyway.
+fn with_retries<T>(
+    action: &str,
+    key: &str,
+    mut operation: impl FnMut(&mut EspDefaultNvs) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut retries_left = NVS_RETRIES;
+    let mut delay_ms = NVS_RETRY_DELAY_MS;
+    loop {
+        match open().and_then(|mut nvs| operation(&mut nvs)) {
+            Err(e) if retries_left > 0 && is_transient(&e) => {
+                retries_left -= 1;
+                FreeRtos::delay_ms(delay_ms);
+                delay_ms = delay_ms.saturating_mul(2);
+            }
+            Err(e) => {
+                warn!(
+                    "‼️ NVS {} of \"{}\" failed after {} attempts: {:?}",
+                    action,
+                    key,
+                    NVS_RETRIES - retries_left + 1,
+                    e
+                );
+                return Err(e);
+            }
+            ok => return ok,
+        }
+    }
+}
+
+fn is_transient(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<EspError>())
+        .any(|error| TRANSIENT_ERRORS.contains(&error.code()))
+}
+
+fn stage(key: &'static str, value: Staged) {
+    STAGED.lock(|staged| {
+        let mut staged = staged.borrow_mut();
+        match staged.iter_mut().find(|(staged_key, _)| *staged_key == key) {
+            Some((_, staged_value)) => *staged_value = value,
+            None => staged.push((key, value)),
+        }
+    });
+    flush_due();
+}
+
+fn staged(key: &str) -> Option<Staged> {
+    STAGED.lock(|staged| {
+        staged
+            .borrow()
+            .iter()
+            .find(|(staged_key, _)| *staged_key == key)
+            .map(|(_, value)| value.clone())
+    })
+}
+
+fn stored_blob(nvs: &EspDefaultNvs, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let Some(len) = nvs.blob_len(key)? else {
+        return Ok(None);
+    };
+
+    let mut buffer = vec![0u8; len];
+    Ok(nvs.get_blob(key, &mut buffer)?.map(|blob| blob.to_vec()))
+}
+
+fn open() -> anyhow::Result<EspDefaultNvs> {
+    let partition = PARTITION.try_get().context("‼️ NVS used before init")?;
+    EspDefaultNvs::new(partition.clone(), NAMESPACE, true)
+        .context("‼️ Failed to open NVS namespace")
+}
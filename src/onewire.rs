@@ -0,0 +1,129 @@
+use anyhow::Context;
+use esp_idf_svc::hal::delay::Ets;
+use esp_idf_svc::hal::gpio::{AnyIOPin, InputOutput, PinDriver, Pull};
+use esp_idf_svc::hal::interrupt;
+use smog_core::onewire::{SCRATCHPAD_LEN, ds18b20_celsius};
+
+const SKIP_ROM: u8 = 0xCC;
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xBE;
+
+/// Minimal DS18B20 driver for a single probe on a bit-banged 1-Wire bus (open drain, external
+/// 4.7 kΩ pull-up). With one device on the bus the ROM search is skipped entirely.
+///
+/// Conversions (750 ms at 12 bits) run between reads: each read fetches the result of the
+/// previous conversion and starts the next one, so the sensor loop never waits on the probe.
+/// Reads closer together than that return the previous result again.
+pub(crate) struct Ds18b20 {
+    pin: PinDriver<'static, AnyIOPin, InputOutput>,
+    converting: bool,
+}
+
+impl Ds18b20 {
+    /// Fails when no device answers the reset pulse.
+    ///
+    /// # Safety
+    ///
+    /// `gpio` must be a valid I/O pin that no other driver uses (I2C is on 6/7, the lighthouse on
+    /// 8, wind/rain on 2/3).
+    pub(crate) unsafe fn new(gpio: i32) -> anyhow::Result<Self> {
+        let mut pin = PinDriver::input_output_od(unsafe { AnyIOPin::new(gpio) })
+            .context("‼️ Failed to init DS18B20 GPIO")?;
+        // Only a fallback: the internal pull-up is too weak for anything but short cables
+        pin.set_pull(Pull::Up)?;
+        pin.set_high()?;
+
+        let mut probe = Self {
+            pin,
+            converting: false,
+        };
+        if !probe.reset() {
+            anyhow::bail!("‼️ No DS18B20 presence pulse on GPIO{}", gpio);
+        }
+        probe.start_conversion();
+
+        Ok(probe)
+    }
+
+    /// The last converted temperature in °C; `None` if the probe is gone, the scratchpad fails its
+    /// CRC, or no conversion has completed yet (which also shows as the 85 °C power-on value).
+    pub(crate) fn read_celsius(&mut self) -> Option<f32> {
+        let temperature = if self.converting {
+            self.read_scratchpad()
+                .and_then(|scratchpad| ds18b20_celsius(&scratchpad))
+        } else {
+            None
+        };
+
+        self.start_conversion();
+        temperature
+    }
+
+    fn start_conversion(&mut self) {
+        self.converting = self.reset();
+        if self.converting {
+            self.write_byte(SKIP_ROM);
+            self.write_byte(CONVERT_T);
+        }
+    }
+
+    fn read_scratchpad(&mut self) -> Option<[u8; SCRATCHPAD_LEN]> {
+        if !self.reset() {
+            return None;
+        }
+        self.write_byte(SKIP_ROM);
+        self.write_byte(READ_SCRATCHPAD);
+
+        let mut scratchpad = [0u8; SCRATCHPAD_LEN];
+        for byte in scratchpad.iter_mut() {
+            *byte = self.read_byte();
+        }
+        Some(scratchpad)
+    }
+
+    /// Reset pulse; returns whether a device answered with a presence pulse.
+    fn reset(&mut self) -> bool {
+        let _ = self.pin.set_low();
+        Ets::delay_us(480);
+        let present = interrupt::free(|| {
+            let _ = self.pin.set_high();
+            Ets::delay_us(70);
+            self.pin.is_low()
+        });
+        Ets::delay_us(410);
+        present
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        for bit in 0..8 {
+            self.write_bit(byte & (1 << bit) != 0);
+        }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        (0..8).fold(0, |byte, bit| byte | (u8::from(self.read_bit()) << bit))
+    }
+
+    // Each time slot is timed in microseconds, so it runs with interrupts masked.
+    fn write_bit(&mut self, bit: bool) {
+        let (low_us, high_us) = if bit { (6, 64) } else { (60, 10) };
+        interrupt::free(|| {
+            let _ = self.pin.set_low();
+            Ets::delay_us(low_us);
+            let _ = self.pin.set_high();
+        });
+        Ets::delay_us(high_us);
+    }
+
+    fn read_bit(&mut self) -> bool {
+        let bit = interrupt::free(|| {
+            let _ = self.pin.set_low();
+            Ets::delay_us(6);
+            let _ = self.pin.set_high();
+            Ets::delay_us(9);
+            self.pin.is_high()
+        });
+        Ets::delay_us(55);
+        bit
+    }
+}
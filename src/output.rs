@@ -0,0 +1,350 @@
+#[cfg(feature = "http")]
+use crate::config::PAYLOAD_ENVELOPE;
+use crate::config::{
+    DUAL_UNITS, HUMIDITY_DECIMALS, PRESSURE_DECIMALS, PressureUnit, SCHEMA_VERSION,
+    SEND_ABSOLUTE_HUMIDITY, SEND_ANOMALY, SEND_BOOT_COUNT, SEND_HUMIDITY, SEND_LUX, SEND_PRESSURE,
+    SEND_SCHEMA_VERSION, SEND_TIME_SYNCED, SEND_TIMEZONE, SEND_UNITS, SEND_VOC, SEND_WIND_RAIN,
+    TEMPERATURE_DECIMALS, TemperatureUnit, TimestampSource, UnsyncedPolicy, VOC_ALERT_DELTA,
+    VocSensorModel, data_labels, pressure_unit, temperature_unit, timestamp_source,
+    unsynced_policy, voc_sensor_model,
+};
+#[cfg(feature = "http")]
+use crate::device::device_id;
+use crate::models::WeatherData;
+use crate::nvs::boot_count;
+use crate::stats::IntervalStats;
+use serde::{Serialize, Serializer};
+#[cfg(feature = "http")]
+use smog_core::payload::{Envelope, Meta};
+use smog_core::units::{hpa_to_inhg, round_to};
+use std::sync::OnceLock;
+
+/// `DATA_LABELS`, parsed on first use.
+static LABELS: OnceLock<Vec<(&'static str, &'static str)>> = OnceLock::new();
+
+/// JSON keys of the core fields under the active key convention (for consumers like HA discovery
+/// templates that refer to fields by name).
+pub(crate) const TEMPERATURE_KEY: &str = if cfg!(feature = "short-keys") {
+    "temp_c"
+} else {
+    "temperature"
+};
+pub(crate) const HUMIDITY_KEY: &str = if cfg!(feature = "short-keys") {
+    "rh"
+} else {
+    "humidity"
+};
+pub(crate) const PRESSURE_KEY: &str = if cfg!(feature = "short-keys") {
+    "press_hpa"
+} else {
+    "pressure"
+};
+
+/// What the wire sees for one reading, built by [`transform_for_output`] for every transport
+/// (HTTP JSON and form, MQTT, `GET /history`).
+///
+/// `temperature` is present unless the sample is VOC-only (BME280 unavailable, see
+/// `WeatherStation`), and the timestamp picked by `TIMESTAMP_SOURCE` always is
+/// (`timestamp_unix_s`, `uptime_us` or both; only `uptime_us` for an unsynced sample under
+/// `UNSYNCED_POLICY=send_monotonic`); every other field can be dropped with its `SEND_*`
+/// flag in `config.rs` (omitted, not null). Any combination is valid, consumers should treat every
+/// optional key as possibly absent. `humidity`, `pressure` and `absolute_humidity_gm3` are also
+/// left out for a partial sample (`ALLOW_PARTIAL_SAMPLES`) missing that channel.
+///
+/// Temperatures and pressures are in `TEMPERATURE_UNIT` / `PRESSURE_UNIT`, except where the key
+/// names its unit (`temperature_probe_c`, `temperature_ambient_c`, `pressure_qnh_hpa`, the
+/// `DUAL_UNITS` fields, and the `temp_c` / `press_hpa` short keys). Temperature, humidity and
+/// pressure are rounded to `*_DECIMALS` places (two more for inHg); `WeatherData` keeps full
+/// precision for everything computed on the device.
+///
+/// JSON keys are snake_case field names by default. The `camel-case-keys` feature switches to
+/// camelCase (`timestampUnixS`) for JS consumers; `short-keys` maps the core fields onto the
+/// compact `temp_c` / `rh` / `press_hpa` schema (and wins over camelCase for those fields if both
+/// are enabled).
+///
+/// `Option<Option<_>>` fields are selected-but-unavailable when `Some(None)`: sent as `null`, as
+/// a missing VOC reading always has been.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "camel-case-keys", serde(rename_all = "camelCase"))]
+pub(crate) struct OutputData {
+    #[cfg_attr(feature = "short-keys", serde(rename = "temp_c"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<f32>,
+    #[cfg_attr(feature = "short-keys", serde(rename = "rh"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) humidity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) absolute_humidity_gm3: Option<f32>,
+    #[cfg_attr(feature = "short-keys", serde(rename = "press_hpa"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pressure: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pressure_qnh_hpa: Option<f32>,
+    /// `DUAL_UNITS`: `temperature` and `pressure` once more in each unit system.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature_c: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature_f: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pressure_hpa: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pressure_inhg: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature_raw: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) humidity_raw: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pressure_raw: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature_probe_c: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature_ambient_c: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) gas_ppm: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) voc: Option<Option<u16>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) voc_raw: Option<u16>,
+    /// SGP41 NOx index, sent along with `voc`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) nox: Option<Option<u16>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lux: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) wind_speed_kmh: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rain_mm: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) is_daytime: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) wifi_rssi_dbm: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) wifi_channel: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) wifi_bssid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) wifi_auth_mode: Option<&'static str>,
+    /// p50/p90/max over the send interval, in the same units and rounding as the fields above.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stats: Option<IntervalStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) anomaly: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alert: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) time_synced: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) timestamp_unix_s: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) uptime_us: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) timezone: Option<&'static str>,
+    /// `SEND_BOOT_COUNT`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) boot_count: Option<u32>,
+    /// `SEND_UNITS`: the unit of every core field, keyed like the fields themselves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) units: Option<Units>,
+    /// `DATA_LABELS`; absent when none are configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) labels: Option<Labels>,
+    /// `SCHEMA_VERSION`, with `SEND_SCHEMA_VERSION`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) schema_version: Option<u16>,
+}
+
+/// `TEMPERATURE_UNIT`, unless the `temp_c` short key pins it to °C: a key that names its unit
+/// keeps that unit whatever is configured.
+pub(crate) fn output_temperature_unit() -> TemperatureUnit {
+    if cfg!(feature = "short-keys") {
+        TemperatureUnit::Celsius
+    } else {
+        temperature_unit()
+    }
+}
+
+/// `PRESSURE_UNIT`, unless the `press_hpa` short key pins it to hPa.
+pub(crate) fn output_pressure_unit() -> PressureUnit {
+    if cfg!(feature = "short-keys") {
+        PressureUnit::Hectopascal
+    } else {
+        pressure_unit()
+    }
+}
+
+/// Applies the configured units, rounding and `SEND_*` field selection to a reading. The single
+/// place that decides what leaves the device.
+pub(crate) fn transform_for_output(data: &WeatherData) -> OutputData {
+    let temperature_unit = output_temperature_unit();
+    let pressure_unit = output_pressure_unit();
+    // Without NTP the wall clock counts from 1970, the uptime is the only usable time reference
+    let timestamp_source =
+        if !data.time_synced && matches!(unsynced_policy(), UnsyncedPolicy::SendMonotonic) {
+            TimestampSource::Monotonic
+        } else {
+            timestamp_source()
+        };
+
+    OutputData {
+        temperature: data
+            .temperature
+            .map(|temperature| in_temperature_unit(temperature, temperature_unit)),
+        humidity: data
+            .humidity
+            .filter(|_| SEND_HUMIDITY)
+            .map(|humidity| round_to(humidity, HUMIDITY_DECIMALS)),
+        absolute_humidity_gm3: data
+            .absolute_humidity_gm3
+            .filter(|_| SEND_ABSOLUTE_HUMIDITY),
+        pressure: data
+            .pressure
+            .filter(|_| SEND_PRESSURE)
+            .map(|pressure| in_pressure_unit(pressure, pressure_unit)),
+        pressure_qnh_hpa: data
+            .pressure_qnh_hpa
+            .map(|qnh| round_to(qnh, PRESSURE_DECIMALS)),
+        temperature_c: data
+            .temperature
+            .filter(|_| DUAL_UNITS)
+            .map(|temperature| in_temperature_unit(temperature, TemperatureUnit::Celsius)),
+        temperature_f: data
+            .temperature
+            .filter(|_| DUAL_UNITS)
+            .map(|temperature| in_temperature_unit(temperature, TemperatureUnit::Fahrenheit)),
+        pressure_hpa: data
+            .pressure
+            .filter(|_| DUAL_UNITS && SEND_PRESSURE)
+            .map(|pressure| in_pressure_unit(pressure, PressureUnit::Hectopascal)),
+        pressure_inhg: data
+            .pressure
+            .filter(|_| DUAL_UNITS && SEND_PRESSURE)
+            .map(|pressure| round_to(hpa_to_inhg(pressure), PRESSURE_DECIMALS + 2)),
+        temperature_raw: data
+            .temperature_raw
+            .map(|temperature| temperature_unit.convert(temperature)),
+        humidity_raw: data.humidity_raw,
+        pressure_raw: data
+            .pressure_raw
+            .map(|pressure| pressure_unit.convert(pressure)),
+        temperature_probe_c: data.temperature_probe_c,
+        temperature_ambient_c: data
+            .temperature_ambient_c
+            .map(|temperature| round_to(temperature, TEMPERATURE_DECIMALS)),
+        gas_ppm: data.gas_ppm,
+        voc: (SEND_VOC && cfg!(feature = "sgp40")).then_some(data.voc),
+        voc_raw: data.voc_raw,
+        nox: is_nox_sent().then_some(data.nox_index),
+        lux: SEND_LUX.then_some(data.lux),
+        wind_speed_kmh: SEND_WIND_RAIN.then_some(data.wind_speed_kmh),
+        rain_mm: SEND_WIND_RAIN.then_some(data.rain_mm),
+        is_daytime: data.is_daytime,
+        wifi_rssi_dbm: data.wifi.map(|link| link.rssi_dbm),
+        wifi_channel: data.wifi.map(|link| link.channel),
+        wifi_bssid: data.wifi.map(|link| link.bssid_string()),
+        wifi_auth_mode: data.wifi.map(|link| link.auth_mode),
+        stats: data.interval_stats.map(|stats| IntervalStats {
+            temperature: stats.temperature.map(|percentiles| {
+                percentiles.map(|temperature| in_temperature_unit(temperature, temperature_unit))
+            }),
+            humidity: stats.humidity.map(|percentiles| {
+                percentiles.map(|humidity| round_to(humidity, HUMIDITY_DECIMALS))
+            }),
+            pressure: stats.pressure.map(|percentiles| {
+                percentiles.map(|pressure| in_pressure_unit(pressure, pressure_unit))
+            }),
+            voc: stats
+                .voc
+                .map(|percentiles| percentiles.map(|voc| round_to(voc, 1))),
+        }),
+        anomaly: SEND_ANOMALY.then_some(data.anomaly),
+        alert: VOC_ALERT_DELTA.map(|_| data.alert),
+        time_synced: SEND_TIME_SYNCED.then_some(data.time_synced),
+        timestamp_unix_s: (!matches!(timestamp_source, TimestampSource::Monotonic))
+            .then_some(data.timestamp_unix_s),
+        uptime_us: (!matches!(timestamp_source, TimestampSource::Wallclock))
+            .then_some(data.uptime_us),
+        timezone: SEND_TIMEZONE.then_some(data.timezone),
+        boot_count: SEND_BOOT_COUNT.then(boot_count),
+        units: SEND_UNITS.then(|| Units {
+            temperature: temperature_unit.symbol(),
+            pressure: pressure_unit.symbol(),
+        }),
+        labels: Some(Labels(labels())).filter(|labels| !labels.0.is_empty()),
+        schema_version: SEND_SCHEMA_VERSION.then_some(SCHEMA_VERSION),
+    }
+}
+
+/// A temperature in °C, converted and rounded for the wire.
+fn in_temperature_unit(celsius: f32, unit: TemperatureUnit) -> f32 {
+    round_to(unit.convert(celsius), TEMPERATURE_DECIMALS)
+}
+
+/// A pressure in hPa, converted and rounded for the wire.
+fn in_pressure_unit(hpa: f32, unit: PressureUnit) -> f32 {
+    round_to(unit.convert(hpa), PRESSURE_DECIMALS)
+}
+
+/// Whether `nox` goes out: with `voc`, when the gas sensor is an SGP41.
+pub(crate) fn is_nox_sent() -> bool {
+    SEND_VOC && cfg!(feature = "sgp40") && matches!(voc_sensor_model(), VocSensorModel::Sgp41)
+}
+
+fn labels() -> &'static [(&'static str, &'static str)] {
+    LABELS.get_or_init(data_labels)
+}
+
+/// Serialized as e.g. `{"temperature": "°C", "humidity": "%", "pressure": "hPa", "voc": "index"}`,
+/// under the active key convention (`temp_c`, ... with `short-keys`). VOC (and NOx) are only listed
+/// while they are sent at all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Units {
+    temperature: &'static str,
+    pressure: &'static str,
+}
+
+impl Serialize for Units {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let voc = (SEND_VOC && cfg!(feature = "sgp40")).then_some(("voc", "index"));
+        let nox = is_nox_sent().then_some(("nox", "index"));
+        serializer.collect_map(
+            [
+                (TEMPERATURE_KEY, self.temperature),
+                (HUMIDITY_KEY, "%"),
+                (PRESSURE_KEY, self.pressure),
+            ]
+            .into_iter()
+            .chain(voc)
+            .chain(nox),
+        )
+    }
+}
+
+/// Static key/value labels, serialized as a `{"key": "value"}` object.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Labels(&'static [(&'static str, &'static str)]);
+
+impl Serialize for Labels {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().copied())
+    }
+}
+
+/// The JSON body of one sample: the flat `OutputData`, or wrapped in an envelope with
+/// `PAYLOAD_ENVELOPE`.
+#[cfg(feature = "http")]
+pub(crate) fn json_payload(data: &WeatherData) -> serde_json::Result<Vec<u8>> {
+    let output = transform_for_output(data);
+    if !PAYLOAD_ENVELOPE {
+        return serde_json::to_vec(&output);
+    }
+
+    serde_json::to_vec(&Envelope {
+        meta: Meta {
+            device_id: device_id(),
+            fw_version: env!("CARGO_PKG_VERSION"),
+            seq: data.seq,
+            uptime_s: data.uptime_us / 1_000_000,
+        },
+        data: &output,
+    })
+}
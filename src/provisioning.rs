@@ -0,0 +1,266 @@
+use crate::config::PROVISIONING_AP_PASSWORD;
+use crate::device::device_id;
+use crate::nvs;
+use anyhow::{Context, Result, anyhow};
+use embassy_time::Timer;
+use embedded_svc::http::Headers;
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::http::server::{Configuration, EspHttpConnection, EspHttpServer, Request};
+use esp_idf_svc::wifi::{
+    AccessPointConfiguration, AuthMethod, Configuration as WifiConfig, EspWifi,
+};
+use log::{info, warn};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const SSID_NVS_KEY: &str = "wifi_ssid";
+const PASSWORD_NVS_KEY: &str = "wifi_pass";
+const MAX_FORM_LEN: usize = 256;
+const DNS_PORT: u16 = 53;
+/// A DNS message over UDP is at most 512 bytes.
+const MAX_DNS_MESSAGE_LEN: usize = 512;
+
+const FORM_PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta name="viewport" content="width=device-width,initial-scale=1"><title>Smog-rs setup</title></head>
+<body style="font-family:sans-serif;max-width:24em;margin:2em auto">
+<h2>Smog-rs WiFi setup</h2>
+<form method="post" action="/">
+<p><label>Network name (SSID)<br><input name="ssid" maxlength="32" required></label></p>
+<p><label>Password<br><input name="password" type="password" maxlength="64"></label></p>
+<p><button type="submit">Save and connect</button></p>
+</form></body></html>"#;
+const SAVED_PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta name="viewport" content="width=device-width,initial-scale=1"><title>Smog-rs setup</title></head>
+<body style="font-family:sans-serif;max-width:24em;margin:2em auto">
+<h2>Saved</h2><p>The station is restarting and will join the network.</p></body></html>"#;
+const HTML_HEADERS: [(&str, &str); 1] = [("Content-Type", "text/html; charset=utf-8")];
+
+/// Set by the form handler once the credentials are in NVS; `run` then reboots.
+static SAVED: AtomicBool = AtomicBool::new(false);
+
+/// WiFi station credentials entered through the provisioning page.
+pub(crate) struct Credentials {
+    pub(crate) ssid: String,
+    pub(crate) password: String,
+}
+
+/// The credentials saved by provisioning, `None` until a device has been provisioned.
+pub(crate) fn stored_credentials() -> Option<Credentials> {
+    let read = |key: &str| match nvs::get_blob(key) {
+        Ok(value) => value.and_then(|value| String::from_utf8(value).ok()),
+        Err(e) => {
+            warn!("📶 Failed to read stored WiFi credentials: {:?}", e);
+            None
+        }
+    };
+
+    Some(Credentials {
+        ssid: read(SSID_NVS_KEY).filter(|ssid| !ssid.is_empty())?,
+        password: read(PASSWORD_NVS_KEY).unwrap_or_default(),
+    })
+}
+
+/// First-boot provisioning (`PROVISIONING_ENABLED`): opens a SoftAP named after the device id
+/// with a captive page asking for the SSID and password. Also the fallback once the stored
+/// credentials have failed `PROVISIONING_FALLBACK_ATTEMPTS` connects in a row. Every A query is
+/// answered with the AP's own address, so phones and laptops pop the page up on their own. Saving
+/// stores the credentials in NVS and reboots into station mode.
+///
+/// Only returns on error: a saved configuration restarts the MCU. Nothing else runs this early in
+/// boot, so the restart doesn't go through the reboot supervisor.
+pub(crate) async fn run(wifi: &mut EspWifi<'static>) -> Result<()> {
+    if wifi.is_started()? {
+        wifi.stop()?;
+    }
+
+    let ssid = device_id();
+    wifi.set_configuration(&WifiConfig::AccessPoint(AccessPointConfiguration {
+        ssid: ssid
+            .try_into()
+            .map_err(|_| anyhow!("‼️📶 SoftAP SSID {} is too long", ssid))?,
+        password: PROVISIONING_AP_PASSWORD
+            .try_into()
+            .map_err(|_| anyhow!("‼️📶 PROVISIONING_AP_PASSWORD is too long"))?,
+        auth_method: if PROVISIONING_AP_PASSWORD.is_empty() {
+            AuthMethod::None
+        } else {
+            AuthMethod::WPA2Personal
+        },
+        ..Default::default()
+    }))?;
+    wifi.start()?;
+
+    let ip = wifi.ap_netif().get_ip_info()?.ip;
+    spawn_dns_responder(ip)?;
+    let _server = start_server()?;
+
+    info!(
+        "📶 WiFi setup: join the \"{}\" network and open http://{}/ to enter the credentials",
+        ssid, ip
+    );
+
+    while !SAVED.load(Ordering::Relaxed) {
+        Timer::after_millis(500).await;
+    }
+
+    info!("📶 WiFi credentials saved, restarting into station mode");
+    // Let the confirmation page go out before the AP disappears
+    Timer::after_millis(1000).await;
+    nvs::flush_all();
+
+    unsafe { esp_idf_svc::sys::esp_restart() }
+}
+
+fn start_server() -> Result<EspHttpServer<'static>> {
+    let mut server = EspHttpServer::new(&Configuration {
+        uri_match_wildcard: true,
+        ..Default::default()
+    })?;
+
+    server.fn_handler("/", Method::Post, |mut req| -> Result<()> {
+        let len = req.content_len().unwrap_or(0) as usize;
+        if len == 0 || len > MAX_FORM_LEN {
+            return respond(req, 400, "Invalid form length");
+        }
+
+        let mut body = vec![0u8; len];
+        req.read_exact(&mut body).map_err(|e| anyhow!("{:?}", e))?;
+        let body = String::from_utf8_lossy(&body);
+
+        match save_credentials(&body) {
+            Ok(()) => {
+                respond(req, 200, SAVED_PAGE)?;
+                SAVED.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("📶 Rejected WiFi credentials: {}", e);
+                respond(req, 400, &e.to_string())
+            }
+        }
+    })?;
+
+    // Any other path, including the OS captive portal probes, gets the form
+    server.fn_handler("/*", Method::Get, |req| -> Result<()> {
+        respond(req, 200, FORM_PAGE)
+    })?;
+
+    Ok(server)
+}
+
+fn save_credentials(form: &str) -> Result<()> {
+    let ssid = form_value(form, "ssid").unwrap_or_default();
+    let password = form_value(form, "password").unwrap_or_default();
+
+    if ssid.is_empty() || ssid.len() > 32 {
+        anyhow::bail!("SSID must be 1 to 32 bytes long");
+    }
+    if !password.is_empty() && !(8..=64).contains(&password.len()) {
+        anyhow::bail!("Password must be empty (open network) or 8 to 64 characters long");
+    }
+
+    nvs::set_blob(SSID_NVS_KEY, ssid.as_bytes())?;
+    nvs::set_blob(PASSWORD_NVS_KEY, password.as_bytes())?;
+    Ok(())
+}
+
+fn respond(req: Request<&mut EspHttpConnection>, status: u16, body: &str) -> Result<()> {
+    let mut response = req.into_response(status, None, &HTML_HEADERS)?;
+    response.write_all(body.as_bytes())?;
+    Ok(())
+}
+
+/// Decoded value of `key` in an `application/x-www-form-urlencoded` body.
+fn form_value(form: &str, key: &str) -> Option<String> {
+    form.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(name, value)| (name == key).then(|| url_decode(value)))
+}
+
+/// `+` is a space and `%XX` a byte; malformed escapes are kept as they are.
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => match value
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    index += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Answers every A query with `ip`, which is what makes the page captive.
+fn spawn_dns_responder(ip: Ipv4Addr) -> Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DNS_PORT))
+        .context("‼️📶 Failed to bind the provisioning DNS responder")?;
+
+    std::thread::Builder::new()
+        .stack_size(4096)
+        .spawn(move || {
+            let mut buf = [0u8; MAX_DNS_MESSAGE_LEN];
+            loop {
+                let Ok((len, peer)) = socket.recv_from(&mut buf) else {
+                    continue;
+                };
+                if let Some(reply) = dns_reply(&buf[..len], ip) {
+                    let _ = socket.send_to(&reply, peer);
+                }
+            }
+        })
+        .context("‼️📶 Failed to spawn the provisioning DNS responder")?;
+
+    Ok(())
+}
+
+/// A reply to a single-question query pointing the queried name at `ip`; `None` for anything else.
+fn dns_reply(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    const HEADER_LEN: usize = 12;
+
+    let is_query = query.len() > HEADER_LEN && query[2] & 0x80 == 0;
+    let question_count = u16::from_be_bytes([*query.get(4)?, *query.get(5)?]);
+    if !is_query || question_count != 1 {
+        return None;
+    }
+
+    // The question is the name's labels up to the zero length byte, then type and class
+    let mut end = HEADER_LEN;
+    while *query.get(end)? != 0 {
+        end += 1 + usize::from(query[end]);
+    }
+    end += 1 + 4;
+    let question = query.get(HEADER_LEN..end)?;
+    // Anything but an A query (AAAA, HTTPS, ...) gets an empty NOERROR answer: the name exists,
+    // just without records of that type, so the client falls back to the A record
+    let is_a_query = question[question.len() - 4..question.len() - 2] == [0, 1];
+
+    let mut reply = Vec::with_capacity(end + 16);
+    reply.extend_from_slice(&query[..2]); // id
+    reply.extend_from_slice(&[0x81, 0x80]); // response, recursion desired + available, NOERROR
+    if !is_a_query {
+        reply.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // 1 question, no answers
+        reply.extend_from_slice(question);
+        return Some(reply);
+    }
+    reply.extend_from_slice(&[0, 1, 0, 1, 0, 0, 0, 0]); // 1 question, 1 answer
+    reply.extend_from_slice(question);
+    reply.extend_from_slice(&[0xC0, 0x0C]); // name: pointer to the question
+    reply.extend_from_slice(&[0, 1, 0, 1]); // type A, class IN
+    reply.extend_from_slice(&60u32.to_be_bytes()); // TTL
+    reply.extend_from_slice(&[0, 4]);
+    reply.extend_from_slice(&ip.octets());
+    Some(reply)
+}
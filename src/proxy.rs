@@ -0,0 +1,277 @@
+use crate::config::{HTTP_PROXY, HTTP_PROXY_AUTH_HEADER};
+use crate::tls::{client_identity, pinned_server_certificate};
+use anyhow::{Context, Result, anyhow};
+use esp_idf_svc::tls::{self, EspTls};
+use smog_core::http::{Endpoint, ResponseHead, decode_chunked};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const PROXY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Status line plus headers.
+const MAX_RESPONSE_HEAD_LEN: usize = 1024;
+
+/// The ESP-IDF HTTP client has no proxy support, so `HTTP_PROXY` requests are written by hand
+/// over a plain socket: `http://` targets with an absolute-URI request line, `https://` ones
+/// through a `CONNECT` tunnel with TLS negotiated on top of it. One connection per request
+/// (`Connection: close`), so `HTTP_KEEPALIVE_MS` doesn't apply.
+///
+/// Returns the response status and its `Location` header, for the caller to follow redirects.
+/// The response body is never read.
+pub(crate) fn post(
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> Result<(u16, Option<String>)> {
+    let response = request("POST", url, headers, body, 0)?;
+    Ok((
+        response.status(),
+        response.header("Location").map(str::to_string),
+    ))
+}
+
+/// GETs `url` through the proxy, as `post` does. The body of a 2xx response is read up to
+/// `max_body_len` bytes, more fails the request; any other status leaves it unread.
+pub(crate) fn get(url: &str, headers: &[(&str, &str)], max_body_len: usize) -> Result<Response> {
+    request("GET", url, headers, &[], max_body_len)
+}
+
+/// A response received through the proxy.
+pub(crate) struct Response {
+    head: ResponseHead,
+    /// Empty unless the caller asked for it and the status is a 2xx.
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    pub(crate) fn status(&self) -> u16 {
+        self.head.status
+    }
+
+    /// The first header named `name`, compared case-insensitively.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.head.header(name)
+    }
+}
+
+fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    max_body_len: usize,
+) -> Result<Response> {
+    let target = Endpoint::parse(url).map_err(|e| anyhow!("‼️ Invalid URL {}: {:?}", url, e))?;
+    let proxy = Endpoint::parse(HTTP_PROXY)
+        .map_err(|e| anyhow!("‼️ Invalid HTTP_PROXY {}: {:?}", HTTP_PROXY, e))?;
+    if proxy.https {
+        anyhow::bail!("‼️ HTTP_PROXY must be an http:// URL");
+    }
+
+    let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port))
+        .with_context(|| format!("‼️ Failed to connect to proxy {}", HTTP_PROXY))?;
+    stream.set_read_timeout(Some(PROXY_TIMEOUT))?;
+    stream.set_write_timeout(Some(PROXY_TIMEOUT))?;
+
+    if !target.https {
+        // The proxy reads the destination from the absolute URI and expects its own credentials
+        let absolute_uri = format!("http://{}{}", target.authority(), target.path);
+        let head = request_head(
+            method,
+            &absolute_uri,
+            &target.authority(),
+            headers,
+            true,
+            body.len(),
+        );
+        let response = exchange(&mut stream, &head, body)?;
+        return read_response(&mut stream, response, max_body_len);
+    }
+
+    open_tunnel(&mut stream, &target)?;
+
+    let (client_cert, client_key) = client_identity()?.unzip();
+    let ca_cert = pinned_server_certificate()?;
+    let config = tls::Config {
+        common_name: Some(target.host.as_str()),
+        // A pinned certificate replaces the CA bundle, as in `HttpClient::new`
+        use_crt_bundle_attach: ca_cert.is_none(),
+        ca_cert,
+        client_cert,
+        client_key,
+        timeout_ms: PROXY_TIMEOUT.as_millis() as u32,
+        ..Default::default()
+    };
+
+    let mut tls = EspTls::adopt(stream)?;
+    tls.negotiate(&target.host, &config).with_context(|| {
+        format!(
+            "‼️🔐 TLS handshake with {} through the proxy failed",
+            target.host
+        )
+    })?;
+
+    let head = request_head(
+        method,
+        &target.path,
+        &target.authority(),
+        headers,
+        false,
+        body.len(),
+    );
+    let response = exchange(&mut tls, &head, body)?;
+    read_response(&mut tls, response, max_body_len)
+}
+
+/// Asks the proxy for a raw TCP tunnel to `target`; anything but a 2xx leaves the socket unusable.
+fn open_tunnel(stream: &mut TcpStream, target: &Endpoint) -> Result<()> {
+    let authority = target.authority();
+    let head = request_head("CONNECT", &authority, &authority, &[], true, 0);
+    let status = exchange(stream, &head, &[])?.status();
+
+    if !(200..300).contains(&status) {
+        anyhow::bail!(
+            "‼️ Proxy refused the tunnel to {} (Status {})",
+            authority,
+            status
+        );
+    }
+    Ok(())
+}
+
+fn request_head(
+    method: &str,
+    target: &str,
+    host: &str,
+    headers: &[(&str, &str)],
+    to_proxy: bool,
+    content_len: usize,
+) -> String {
+    let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, target, host);
+    if to_proxy && !HTTP_PROXY_AUTH_HEADER.is_empty() {
+        head.push_str(&format!(
+            "Proxy-Authorization: {}\r\n",
+            HTTP_PROXY_AUTH_HEADER
+        ));
+    }
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if method != "CONNECT" {
+        if !headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+        {
+            head.push_str(&format!("Content-Length: {}\r\n", content_len));
+        }
+        head.push_str("Connection: close\r\n");
+    }
+    head.push_str("\r\n");
+    head
+}
+
+/// Sends one request and parses the head of its response, reading no further than its end: that
+/// leaves a `CONNECT` tunnel positioned at its first tunneled byte.
+fn exchange(stream: &mut impl Stream, head: &str, body: &[u8]) -> Result<Response> {
+    stream.send(head.as_bytes())?;
+    if !body.is_empty() {
+        stream.send(body)?;
+    }
+
+    let mut response = Vec::with_capacity(128);
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_HEAD_LEN {
+            anyhow::bail!(
+                "‼️ Proxy response head exceeds {} bytes",
+                MAX_RESPONSE_HEAD_LEN
+            );
+        }
+        if stream.receive(&mut byte)? == 0 {
+            anyhow::bail!("‼️ Proxy closed the connection before responding");
+        }
+        response.push(byte[0]);
+    }
+
+    let head = ResponseHead::parse(&response)
+        .ok_or_else(|| anyhow!("‼️ Malformed response status line through the proxy"))?;
+    Ok(Response {
+        head,
+        body: Vec::new(),
+    })
+}
+
+/// Reads the body after `exchange` when `max_body_len` asks for it and the status is a 2xx: up to
+/// the end of the connection, which `Connection: close` ends after the response, decoding
+/// `Transfer-Encoding: chunked`.
+fn read_response(
+    stream: &mut impl Stream,
+    mut response: Response,
+    max_body_len: usize,
+) -> Result<Response> {
+    if max_body_len == 0 || !(200..300).contains(&response.status()) {
+        return Ok(response);
+    }
+
+    let chunked = response.head.is_chunked();
+    // The chunk framing takes room of its own
+    let max_read_len = if chunked {
+        max_body_len * 2 + 64
+    } else {
+        max_body_len
+    };
+
+    let mut body = Vec::new();
+    let mut buf = [0u8; 128];
+    loop {
+        let read = stream.receive(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        if body.len() + read > max_read_len {
+            anyhow::bail!("‼️ Response body exceeds {} bytes", max_body_len);
+        }
+        body.extend_from_slice(&buf[..read]);
+    }
+
+    if chunked {
+        body = decode_chunked(&body)
+            .ok_or_else(|| anyhow!("‼️ Malformed chunked response body through the proxy"))?;
+    }
+    if body.len() > max_body_len {
+        anyhow::bail!("‼️ Response body exceeds {} bytes", max_body_len);
+    }
+
+    response.body = body;
+    Ok(response)
+}
+
+/// A plain socket towards the proxy, or TLS on top of it through a tunnel.
+trait Stream {
+    fn send(&mut self, data: &[u8]) -> Result<()>;
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize>;
+}
+
+impl Stream for TcpStream {
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        Ok(self.write_all(data)?)
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.read(buf)?)
+    }
+}
+
+impl Stream for EspTls<TcpStream> {
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        let mut sent = 0;
+        while sent < data.len() {
+            sent += self.write(&data[sent..])?;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.read(buf)?)
+    }
+}
@@ -1,76 +1,359 @@
-use crate::logging::{log_empty_sample, log_sensor_error};
+use crate::bh1750::Bh1750;
+use crate::config::{
+    ALLOW_PARTIAL_SAMPLES, ANOMALY_MIN_SAMPLES, ANOMALY_ZSCORE_THRESHOLD, BH1750_ADDRESS,
+    BH1750_ENABLED, FILTER_EMA_ALPHA, FILTER_MEDIAN_WINDOW, FUSION_OFFSET_ALPHA, HUMIDITY_RANGE,
+    I2C_RECOVERY_AFTER_ERRORS, I2C_SCL_GPIO, I2C_SDA_GPIO, LOCATION_LAT_LON, PRESSURE_RANGE_HPA,
+    PRESSURE_SPIKE_MAX_DELTA_HPA, SEND_INTERVAL_PERCENTILES, SEND_PRESSURE_QNH, SEND_RAW,
+    SEND_VOC_RAW, SEND_WIFI_DIAGNOSTICS, SENSOR_LOST_AFTER_ERRORS, SENSOR_READ_RETRIES,
+    SENSOR_READ_RETRY_DELAY_MS, SGP40_MEASURE_INTERVAL_MS, SGP40_STUCK_RAW_CROSS_CHECK,
+    SGP40_STUCK_RAW_TOLERANCE, STATION_ALTITUDE_M, TEMPERATURE_RANGE, TemperatureFusion,
+    VOC_AVERAGE_OVER_INTERVAL, VocSensorModel, is_sensor_simulated, pressure_spike_policy,
+    temperature_fusion, voc_sensor_model,
+};
+use crate::logging::{log_empty_sample, log_partial_sample, log_sensor_error};
 use crate::models::WeatherData;
-use crate::{I2cBusDevice, SharedI2cBus, time_utils};
+use crate::mq_sensor::MqSensor;
+use crate::onewire::Ds18b20;
+#[cfg(feature = "sgp40")]
+use crate::sgp41::Sgp41;
+use crate::stats::{IntervalAccumulator, IntervalStats};
+use crate::wind_rain::WindRainGauge;
+use crate::{I2cBusDevice, SharedI2cBus, network, time_utils};
 use anyhow::Context;
 use bme280_rs::{Bme280, Configuration, Oversampling, SensorMode};
 use embassy_time::{Delay, Duration, Instant, Timer};
+use embedded_hal::i2c::I2c;
 use embedded_hal_bus::i2c::RefCellDevice;
+use esp_idf_svc::hal::delay::Ets;
+use esp_idf_svc::sys::{
+    esp, esp_random, gpio_get_level, gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD, gpio_set_direction,
+    gpio_set_level, i2c_mode_t_I2C_MODE_MASTER, i2c_set_pin,
+};
+use log::{info, warn};
+#[cfg(feature = "sgp40")]
 use sgp40::Sgp40;
+use smog_core::atmosphere::{absolute_humidity, to_qnh};
+use smog_core::filters::{
+    EmaFilter, FilterChain, MedianFilter, RangeClamp, SampleFilter, SpikeGuard,
+};
+use smog_core::health::{PresenceCheck, PresenceWatchdog, StuckDetector};
+use smog_core::solar;
+use smog_core::stats::AnomalyDetector;
+use std::f32::consts::TAU;
 
+// `I2C0`, the controller main.rs gives the sensor bus
+const I2C_PORT: i32 = 0;
 const SGP_40_WARMUP_SECS: u64 = 60;
 const SGP_40_STUCK_AT_ONE_THRESHOLD: u16 = 20;
+// Only read for an SGP41, which needs the feature
+#[cfg(feature = "sgp40")]
+const SGP41_CONDITIONING_SECS: u64 = crate::sgp41::CONDITIONING_SECS;
+#[cfg(not(feature = "sgp40"))]
+const SGP41_CONDITIONING_SECS: u64 = 0;
+/// The SGP40's own default compensation humidity, used when a partial or VOC-only sample has none.
+const SGP40_DEFAULT_HUMIDITY: f32 = 50.0;
+/// Its default compensation temperature, used for VOC-only samples.
+const SGP40_DEFAULT_TEMPERATURE: f32 = 25.0;
+// The SGP40 VOC algorithm is tuned for 1 Hz sampling; slower polling still works but makes
+// the index react more sluggishly, and beyond ~10 s its internal time constants no longer fit.
+const _: () = assert!(
+    matches!(SGP40_MEASURE_INTERVAL_MS, 1000..=10_000),
+    "SGP40_MEASURE_INTERVAL_MS must be within 1000..=10000"
+);
+const SIMULATED_TEMPERATURE_PERIOD_SECS: f32 = 600.0;
 
+/// Sensors that don't answer at init are marked unavailable and skipped instead of failing the
+/// whole station. Without the BME280 the samples are VOC-only (`temperature` and the other
+/// climate fields empty), without both none are produced; a missing SGP40/SGP41 only leaves `voc`
+/// (and `nox_index`) empty.
 pub(crate) struct WeatherStation {
     bme280: Bme280<I2cBusDevice, Delay>,
-    sgp40: Sgp40<I2cBusDevice, Delay>,
+    #[cfg(feature = "sgp40")]
+    voc_sensor: VocSensor,
+    bme_available: bool,
+    sgp_available: bool,
     sgp40health: Sgp40Health,
+    bh1750: Option<Bh1750>,
+    wind_rain: Option<WindRainGauge>,
+    temperature_probe: Option<Ds18b20>,
+    ambient_fusion: AmbientFusion,
+    gas_sensor: Option<MqSensor>,
+    anomaly_detector: AnomalyDetector,
+    simulator: Option<SensorSimulator>,
+    temperature_filter: FilterChain,
+    humidity_filter: FilterChain,
+    pressure_filter: FilterChain,
+    last_voc: Option<u16>,
+    last_nox: Option<u16>,
+    last_voc_measurement: Option<Instant>,
+    voc_fresh: bool,
+    voc_average: VocAverage,
+    interval_stats: IntervalAccumulator,
+    /// Raw SGP40 signal, measured once per send interval next to a VOC index poll.
+    last_voc_raw: Option<u16>,
+    voc_raw_due: bool,
+    /// Raw signal next to a fresh VOC index of 1, for `SGP40_STUCK_RAW_CROSS_CHECK`.
+    stuck_check_raw: Option<u16>,
+    /// Failed BME280 reads in a row, towards `I2C_RECOVERY_AFTER_ERRORS`.
+    i2c_error_streak: u32,
+    bme280_presence: PresenceWatchdog,
+    sgp40_presence: PresenceWatchdog,
+    /// The sensor `SENSOR_LOST_AFTER_ERRORS` gave up on, for the sensor task to reboot over.
+    lost_sensor: Option<&'static str>,
+}
+
+/// One BME280 + SGP40 reading before any derived values are computed.
+pub(crate) struct AirSample {
+    /// `None` only for a VOC-only sample, taken while the BME280 is unavailable.
+    pub(crate) temperature: Option<f32>,
+    /// `None` with `ALLOW_PARTIAL_SAMPLES`, when the channel came back empty, or VOC-only.
+    pub(crate) humidity: Option<f32>,
+    pub(crate) pressure_pa: Option<f32>,
+    pub(crate) voc: Option<u16>,
+    /// `false` when `voc` is the previous measurement, reused between SGP40 polls.
+    pub(crate) voc_fresh: bool,
+    /// SGP41 only, measured and reused along with `voc`.
+    pub(crate) nox: Option<u16>,
+}
+
+/// The gas sensor picked by `VOC_SENSOR`; both answer at 0x59.
+#[cfg(feature = "sgp40")]
+enum VocSensor {
+    Sgp40(Sgp40<I2cBusDevice, Delay>),
+    Sgp41(Sgp41),
 }
 
 impl WeatherStation {
-    pub(crate) fn new(i2c_bus: &'static SharedI2cBus) -> anyhow::Result<Self> {
+    pub(crate) fn new(
+        i2c_bus: &'static SharedI2cBus,
+        wind_rain: Option<WindRainGauge>,
+        temperature_probe: Option<Ds18b20>,
+        gas_sensor: Option<MqSensor>,
+    ) -> anyhow::Result<Self> {
         let bme_i2c = RefCellDevice::new(i2c_bus);
-        let sgp_i2c = RefCellDevice::new(i2c_bus);
 
         let mut bme = Bme280::new(bme_i2c, Delay);
+        #[cfg(feature = "sgp40")]
+        let mut voc_sensor = match voc_sensor_model() {
+            VocSensorModel::Sgp40 => {
+                VocSensor::Sgp40(Sgp40::new(RefCellDevice::new(i2c_bus), 0x59, Delay))
+            }
+            VocSensorModel::Sgp41 => VocSensor::Sgp41(Sgp41::new(
+                RefCellDevice::new(i2c_bus),
+                SGP40_MEASURE_INTERVAL_MS as f32 / 1000.0,
+            )),
+        };
+        let sgp40health = Sgp40Health::new(voc_sensor_model().warmup_secs());
 
-        bme.init().context("‼️Failed to init BME280")?;
-
-        let bme_sampling_config = Configuration::default()
-            .with_humidity_oversampling(Oversampling::Oversample1)
-            .with_temperature_oversampling(Oversampling::Oversample1)
-            .with_pressure_oversampling(Oversampling::Oversample1)
-            .with_sensor_mode(SensorMode::Normal);
+        // In simulated mode the drivers are constructed but never touch the bus
+        let simulator = is_sensor_simulated().then(SensorSimulator::new);
 
-        bme.set_sampling_configuration(bme_sampling_config)
-            .context("‼️BME280 sensor configuration error")?;
+        let (bme_available, sgp_available, bh1750) = if simulator.is_some() {
+            warn!("🧪 SENSOR_MODE=simulated: readings are synthetic, I2C sensors are not used");
+            (true, cfg!(feature = "sgp40"), None)
+        } else {
+            (
+                init_bme280(&mut bme),
+                #[cfg(feature = "sgp40")]
+                probe_voc_sensor(&mut voc_sensor),
+                // Built without the `sgp40` feature
+                #[cfg(not(feature = "sgp40"))]
+                false,
+                init_bh1750(i2c_bus),
+            )
+        };
 
-        let sgp = Sgp40::new(sgp_i2c, 0x59, Delay);
-        let sgp40health = Sgp40Health::new();
+        info!(
+            "🔎 Sensors: BME280 {} | {} {} | BH1750 {}",
+            availability(bme_available),
+            voc_sensor_model().name(),
+            availability(sgp_available),
+            availability(bh1750.is_some())
+        );
+        if SEND_PRESSURE_QNH && STATION_ALTITUDE_M.is_none() {
+            warn!("‼️ SEND_PRESSURE_QNH is set but STATION_ALTITUDE_M is not, QNH will be omitted");
+        }
+        if !bme_available && sgp_available {
+            warn!("‼️ BME280 unavailable: VOC-only samples until it is back after a reboot");
+        } else if !bme_available {
+            warn!(
+                "‼️ BME280 unavailable: no samples will be produced until it is back after a reboot"
+            );
+        }
 
         Ok(Self {
             bme280: bme,
-            sgp40: sgp,
+            #[cfg(feature = "sgp40")]
+            voc_sensor,
+            bme_available,
+            sgp_available,
             sgp40health,
+            bh1750,
+            wind_rain,
+            temperature_probe,
+            ambient_fusion: AmbientFusion::default(),
+            gas_sensor,
+            anomaly_detector: AnomalyDetector::new(ANOMALY_MIN_SAMPLES, ANOMALY_ZSCORE_THRESHOLD),
+            simulator,
+            temperature_filter: build_filter_chain(TEMPERATURE_RANGE),
+            humidity_filter: build_filter_chain(HUMIDITY_RANGE),
+            pressure_filter: build_pressure_filter_chain(),
+            last_voc: None,
+            last_nox: None,
+            last_voc_measurement: None,
+            voc_fresh: false,
+            voc_average: VocAverage::default(),
+            interval_stats: IntervalAccumulator::default(),
+            last_voc_raw: None,
+            voc_raw_due: SEND_VOC_RAW,
+            stuck_check_raw: None,
+            i2c_error_streak: 0,
+            bme280_presence: PresenceWatchdog::new(SENSOR_LOST_AFTER_ERRORS),
+            sgp40_presence: PresenceWatchdog::new(SENSOR_LOST_AFTER_ERRORS),
+            lost_sensor: None,
         })
     }
 
+    /// The BME280 + SGP40/SGP41 reading as measured, before the filters (`CALIBRATION_MODE`).
+    pub(crate) async fn read_raw_sample(&mut self) -> Option<AirSample> {
+        match self.simulator.as_mut() {
+            Some(simulator) => Some(simulator.next_sample()),
+            None => self.read_air_sample().await,
+        }
+    }
+
     pub(crate) async fn read_sensor_data(&mut self) -> Option<WeatherData> {
-        match self.bme280.read_sample() {
+        let sample = self.read_raw_sample().await?;
+        // A missing channel skips its filter; a filter rejecting a present value still drops the
+        // sample
+        let t = match sample.temperature {
+            Some(temperature) => Some(self.temperature_filter.filter(temperature)?),
+            None => None,
+        };
+        let h = match sample.humidity {
+            Some(humidity) => Some(self.humidity_filter.filter(humidity)?),
+            None => None,
+        };
+        // Standard conversion to hPa
+        let pressure_hpa = match sample.pressure_pa {
+            Some(pressure_pa) => Some(self.pressure_filter.filter(pressure_pa / 100.0)?),
+            None => None,
+        };
+        self.last_voc = sample.voc;
+        self.voc_fresh = sample.voc_fresh;
+
+        // Only fresh, post-warmup measurements count: early SGP40 values would skew the mean
+        if let Some(voc) = sample.voc
+            && sample.voc_fresh
+            && self.sgp40health.is_warmed_up()
+        {
+            self.voc_average.add(voc);
+        }
+        if SEND_INTERVAL_PERCENTILES {
+            let fresh_voc = sample
+                .voc
+                .filter(|_| sample.voc_fresh && self.sgp40health.is_warmed_up());
+            self.interval_stats.update(t, h, pressure_hpa, fresh_voc);
+        }
+        let voc = if VOC_AVERAGE_OVER_INTERVAL {
+            self.voc_average.mean()
+        } else {
+            sample.voc
+        };
+
+        let lux = match self.bh1750.as_mut() {
+            Some(bh1750) => match bh1750.read_lux() {
+                Ok(lux) => Some(lux),
+                Err(bh_error) => {
+                    log_sensor_error("BH1750", bh_error);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let (wind_speed_kmh, rain_mm) = match self.wind_rain.as_mut() {
+            Some(gauge) => (
+                Some(gauge.wind_speed_kmh()),
+                Some(gauge.rain_mm_since_reset()),
+            ),
+            None => (None, None),
+        };
+
+        let temperature_probe_c = self
+            .temperature_probe
+            .as_mut()
+            .and_then(Ds18b20::read_celsius);
+
+        let anomaly = self.anomaly_detector.check(t, h, pressure_hpa, sample.voc);
+
+        let time_synced = time_utils::is_time_synced();
+        let timestamp_unix_s = time_utils::timestamp_unix_s();
+        let is_daytime = LOCATION_LAT_LON
+            .filter(|_| time_synced)
+            .map(|(lat, lon)| solar::is_daytime(lat, lon, timestamp_unix_s));
+
+        Some(WeatherData {
+            temperature: t,
+            humidity: h,
+            absolute_humidity_gm3: t.zip(h).map(|(t, h)| absolute_humidity(t, h)),
+            pressure: pressure_hpa,
+            pressure_qnh_hpa: pressure_hpa
+                .zip(STATION_ALTITUDE_M)
+                .filter(|_| SEND_PRESSURE_QNH)
+                .map(|(pressure, altitude_m)| to_qnh(pressure, altitude_m)),
+            temperature_raw: sample.temperature.filter(|_| SEND_RAW),
+            humidity_raw: sample.humidity.filter(|_| SEND_RAW),
+            pressure_raw: sample.pressure_pa.filter(|_| SEND_RAW).map(|p| p / 100.0),
+            temperature_probe_c,
+            temperature_ambient_c: self.ambient_fusion.fuse(t, temperature_probe_c),
+            gas_ppm: self.gas_sensor.as_mut().and_then(MqSensor::read_ppm),
+            voc,
+            voc_raw: self.last_voc_raw,
+            nox_index: sample.nox,
+            lux,
+            wind_speed_kmh,
+            rain_mm,
+            is_daytime,
+            wifi: SEND_WIFI_DIAGNOSTICS.then(network::link_info).flatten(),
+            anomaly,
+            alert: false,
+            seq: 0,
+            interval_stats: None,
+            time_synced,
+            timestamp_unix_s,
+            uptime_us: time_utils::uptime_us(),
+            timezone: time_utils::effective_timezone_name(),
+        })
+    }
+
+    async fn read_air_sample(&mut self) -> Option<AirSample> {
+        if !self.bme_available {
+            return self.read_voc_only_sample().await;
+        }
+
+        match with_retries(|| self.bme280.read_sample()).await {
             Ok(sample) => {
-                if let (Some(t), Some(h), Some(p)) =
-                    (sample.temperature, sample.humidity, sample.pressure)
+                self.i2c_error_streak = 0;
+                self.bme280_presence.on_success();
+                let complete = sample.humidity.is_some() && sample.pressure.is_some();
+
+                if let Some(t) = sample.temperature
+                    && (complete || ALLOW_PARTIAL_SAMPLES)
                 {
-                    Timer::after_millis(50).await;
-
-                    let voc = match self.sgp40.measure_voc_index_with_rht(
-                        h.round().clamp(0.0, 100.0) as u16,
-                        t.round().clamp(-40.0, 85.0) as i16,
-                    ) {
-                        Ok(voc_index) => Some(voc_index),
-                        Err(sgp_error) => {
-                            log_sensor_error("SGP40 Measuring", sgp_error);
-                            None
-                        }
-                    };
-
-                    Some(WeatherData {
-                        temperature: t,
+                    if !complete {
+                        log_partial_sample(sample.humidity.is_none(), sample.pressure.is_none());
+                    }
+                    let (h, p) = (sample.humidity, sample.pressure);
+                    let voc_fresh = self.poll_voc(h.unwrap_or(SGP40_DEFAULT_HUMIDITY), t).await;
+
+                    Some(AirSample {
+                        temperature: Some(t),
                         humidity: h,
-                        pressure: p / 100.0, // Standard conversion to hPa
-                        voc,
-                        time_synced: time_utils::is_time_synced(),
-                        timestamp_unix_s: time_utils::timestamp_unix_s(),
-                        timezone: time_utils::effective_timezone_name(),
+                        pressure_pa: p,
+                        voc: self.last_voc,
+                        voc_fresh,
+                        nox: self.last_nox,
                     })
                 } else {
                     log_empty_sample();
@@ -79,44 +362,575 @@ impl WeatherStation {
             }
             Err(e) => {
                 log_sensor_error("BME280", e);
+                self.on_i2c_error();
+                let check = self.bme280_presence.on_error();
+                self.on_presence_check("BME280", check);
+                None
+            }
+        }
+    }
+
+    /// Without a BME280 the VOC sensor keeps running on its default compensation (50 %RH,
+    /// 25 °C), which costs the index some accuracy but not its trend. `None` without a VOC sensor
+    /// or a VOC value either.
+    async fn read_voc_only_sample(&mut self) -> Option<AirSample> {
+        if !self.sgp_available {
+            return None;
+        }
+
+        let voc_fresh = self
+            .poll_voc(SGP40_DEFAULT_HUMIDITY, SGP40_DEFAULT_TEMPERATURE)
+            .await;
+        self.last_voc.map(|voc| AirSample {
+            temperature: None,
+            humidity: None,
+            pressure_pa: None,
+            voc: Some(voc),
+            voc_fresh,
+            nox: self.last_nox,
+        })
+    }
+
+    /// Measures VOC (and NOx) once `SGP40_MEASURE_INTERVAL_MS` has passed since the last
+    /// measurement, compensated for `humidity` and `temperature`; returns whether it did.
+    async fn poll_voc(&mut self, humidity: f32, temperature: f32) -> bool {
+        let voc_due = self.sgp_available
+            && self.last_voc_measurement.is_none_or(|measured| {
+                measured.elapsed() >= Duration::from_millis(SGP40_MEASURE_INTERVAL_MS)
+            });
+        if !voc_due {
+            return false;
+        }
+
+        Timer::after_millis(50).await;
+        self.last_voc = self.measure_voc(humidity, temperature).await;
+        self.last_voc_measurement = Some(Instant::now());
+
+        // The index call can't hand out its raw value, so this is a second measurement; it is
+        // kept to one per send interval, plus one per poll while the stuck check wants it
+        let cross_check = SGP40_STUCK_RAW_CROSS_CHECK && self.last_voc == Some(1);
+        self.stuck_check_raw = None;
+        if self.voc_raw_due || cross_check {
+            let raw = self.measure_voc_raw(humidity, temperature).await;
+            if self.voc_raw_due {
+                self.last_voc_raw = raw;
+                self.voc_raw_due = false;
+            }
+            self.stuck_check_raw = raw.filter(|_| cross_check);
+        }
+        true
+    }
+
+    /// Runs the bus recovery once `I2C_RECOVERY_AFTER_ERRORS` reads in a row have failed, then
+    /// re-initializes the BME280, which may have been reset along the way.
+    fn on_i2c_error(&mut self) {
+        let Some(limit) = I2C_RECOVERY_AFTER_ERRORS else {
+            return;
+        };
+        self.i2c_error_streak += 1;
+        if self.i2c_error_streak < limit {
+            return;
+        }
+        self.i2c_error_streak = 0;
+
+        warn!("🔧 {} I2C errors in a row, recovering the bus...", limit);
+        self.recover_bus();
+    }
+
+    /// `SENSOR_LOST_AFTER_ERRORS`: the first run of failures gets a bus recovery, the second marks
+    /// the sensor as lost.
+    fn on_presence_check(&mut self, sensor: &'static str, check: PresenceCheck) {
+        match check {
+            PresenceCheck::Present => {}
+            PresenceCheck::Recover => {
+                warn!(
+                    "🔧 {} stopped answering, recovering the bus before giving up on it...",
+                    sensor
+                );
+                self.recover_bus();
+            }
+            PresenceCheck::Lost => {
+                warn!("‼️ {} still not answering after the bus recovery", sensor);
+                self.lost_sensor = Some(sensor);
+            }
+        }
+    }
+
+    /// Clears a stuck bus, re-initializes the BME280, probes the gas sensor again and has an
+    /// SGP41 condition again.
+    fn recover_bus(&mut self) {
+        match recover_i2c_bus() {
+            Ok(true) => info!("🔧 I2C bus released"),
+            Ok(false) => warn!("‼️ SDA still held low after the bus recovery"),
+            Err(e) => warn!("‼️ I2C bus recovery failed: {:?}", e),
+        }
+        if init_bme280(&mut self.bme280) {
+            info!("🔧 BME280 re-initialized");
+        }
+        #[cfg(feature = "sgp40")]
+        {
+            // One that was missing at boot may have been what held the bus; one that still doesn't
+            // answer is left to the presence watchdog
+            if probe_voc_sensor(&mut self.voc_sensor) {
+                info!("🔧 {} answering", voc_sensor_model().name());
+                self.sgp_available = true;
+            }
+            if let VocSensor::Sgp41(sgp41) = &mut self.voc_sensor {
+                sgp41.recondition();
+            }
+        }
+    }
+
+    /// One VOC index measurement, compensated with the humidity and temperature just read. The
+    /// SGP41 measures its NOx index (`last_nox`) and raw signal in the same go.
+    #[cfg(feature = "sgp40")]
+    async fn measure_voc(&mut self, humidity: f32, temperature: f32) -> Option<u16> {
+        let measured = match &mut self.voc_sensor {
+            VocSensor::Sgp40(sgp40) => {
+                let (rh, temperature) = sgp40_compensation(humidity, temperature);
+                with_retries(|| sgp40.measure_voc_index_with_rht(rh, temperature))
+                    .await
+                    .map(|voc_index| (Some(voc_index), None))
+                    .map_err(|e| anyhow::anyhow!("{:?}", e))
+            }
+            VocSensor::Sgp41(sgp41) => with_retries(|| sgp41.measure(humidity, temperature))
+                .await
+                .map(|reading| (reading.voc_index, reading.nox_index)),
+        };
+
+        match measured {
+            Ok((voc_index, nox_index)) => {
+                self.sgp40_presence.on_success();
+                self.last_nox = nox_index;
+                voc_index
+            }
+            Err(sgp_error) => {
+                let sensor = voc_sensor_model().name();
+                log_sensor_error(&format!("{} Measuring", sensor), sgp_error);
+                self.last_nox = None;
+                let check = self.sgp40_presence.on_error();
+                self.on_presence_check(sensor, check);
                 None
             }
         }
     }
 
-    pub(crate) fn sgp40_stuck_at_one(&mut self, voc: Option<u16>) -> bool {
-        self.sgp40health.check_stuck_condition(voc)
+    /// One raw SRAW measurement with the same compensation as `measure_voc`; the SGP41 hands out
+    /// the one `measure_voc` just took.
+    #[cfg(feature = "sgp40")]
+    async fn measure_voc_raw(&mut self, humidity: f32, temperature: f32) -> Option<u16> {
+        let sgp40 = match &mut self.voc_sensor {
+            VocSensor::Sgp40(sgp40) => sgp40,
+            VocSensor::Sgp41(sgp41) => return sgp41.last_voc_raw(),
+        };
+        let (rh, temperature) = sgp40_compensation(humidity, temperature);
+
+        match with_retries(|| sgp40.measure_raw_with_rht(rh, temperature)).await {
+            Ok(raw) => Some(raw),
+            Err(sgp_error) => {
+                log_sensor_error("SGP40 Raw Measuring", sgp_error);
+                None
+            }
+        }
+    }
+
+    /// Without the `sgp40` feature `sgp_available` is always `false`, so this is never reached.
+    #[cfg(not(feature = "sgp40"))]
+    async fn measure_voc(&mut self, _humidity: f32, _temperature: f32) -> Option<u16> {
+        None
+    }
+
+    #[cfg(not(feature = "sgp40"))]
+    async fn measure_voc_raw(&mut self, _humidity: f32, _temperature: f32) -> Option<u16> {
+        None
+    }
+
+    /// Percentiles of the readings since the last queued sample; `None` unless
+    /// `SEND_INTERVAL_PERCENTILES`.
+    pub(crate) fn interval_stats(&self) -> Option<IntervalStats> {
+        SEND_INTERVAL_PERCENTILES.then(|| self.interval_stats.summary())
+    }
+
+    /// Starts a new rain accumulation, VOC averaging and percentile window and schedules the next
+    /// raw VOC read; called once a sample has been queued for sending.
+    pub(crate) fn reset_interval_accumulators(&mut self) {
+        if let Some(gauge) = self.wind_rain.as_mut() {
+            gauge.reset_rain();
+        }
+        self.voc_average.reset();
+        self.interval_stats.reset();
+        self.voc_raw_due = SEND_VOC_RAW;
+    }
+
+    /// Only fresh measurements count towards stuck detection; a reused VOC value between
+    /// `SGP40_MEASURE_INTERVAL_MS` polls would otherwise look like a repeated reading.
+    /// Checks the latest SGP40 measurement (never the interval average).
+    pub(crate) fn sgp40_stuck_at_one(&mut self) -> bool {
+        if !self.voc_fresh {
+            return false;
+        }
+        self.sgp40health
+            .check_stuck_condition(self.last_voc, self.stuck_check_raw)
+    }
+
+    /// Whether the BME280 answered at boot. Without it there are no samples to fail, so no read
+    /// failures either.
+    pub(crate) fn bme280_available(&self) -> bool {
+        self.bme_available
+    }
+
+    /// The sensor that stopped answering altogether (`SENSOR_LOST_AFTER_ERRORS`), as opposed to
+    /// one returning frozen values.
+    pub(crate) fn lost_sensor(&self) -> Option<&'static str> {
+        self.lost_sensor
     }
 }
 
-struct Sgp40Health {
-    boot_time: Instant,
-    consecutive_one: u16,
+/// Standard I2C bus clear: a slave reset mid-transfer can hold SDA low indefinitely, waiting for
+/// clock pulses the master will never send. With both pins taken off the I2C peripheral, SCL is
+/// pulsed (up to 9 times: a byte plus ACK) until SDA is let go, then a STOP is sent and the pins
+/// are handed back, whether or not the clear went through. Returns whether SDA is free afterwards.
+pub(crate) fn recover_i2c_bus() -> anyhow::Result<bool> {
+    // SAFETY: only the I2C pins are touched. This runs synchronously on the executor thread, so
+    // no other user of the shared bus (the display) can start a transfer in between.
+    let released = unsafe { clear_i2c_bus() };
+    let reattached = unsafe {
+        esp!(i2c_set_pin(
+            I2C_PORT,
+            I2C_SDA_GPIO,
+            I2C_SCL_GPIO,
+            true,
+            true,
+            i2c_mode_t_I2C_MODE_MASTER
+        ))
+    };
+
+    let released = released?;
+    reattached?;
+    Ok(released)
 }
 
-impl Sgp40Health {
+/// The bit-banged part of `recover_i2c_bus`, which always hands the pins back afterwards.
+unsafe fn clear_i2c_bus() -> anyhow::Result<bool> {
+    const HALF_PERIOD_US: u32 = 5;
+
+    unsafe {
+        // Configuring a pin as an output routes it back to the GPIO matrix, off the I2C peripheral
+        esp!(gpio_set_direction(
+            I2C_SDA_GPIO,
+            gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD
+        ))?;
+        esp!(gpio_set_direction(
+            I2C_SCL_GPIO,
+            gpio_mode_t_GPIO_MODE_INPUT_OUTPUT_OD
+        ))?;
+        gpio_set_level(I2C_SDA_GPIO, 1);
+        gpio_set_level(I2C_SCL_GPIO, 1);
+        Ets::delay_us(HALF_PERIOD_US);
+
+        for _ in 0..9 {
+            if gpio_get_level(I2C_SDA_GPIO) == 1 {
+                break;
+            }
+            gpio_set_level(I2C_SCL_GPIO, 0);
+            Ets::delay_us(HALF_PERIOD_US);
+            gpio_set_level(I2C_SCL_GPIO, 1);
+            Ets::delay_us(HALF_PERIOD_US);
+        }
+
+        // STOP: SDA rises while SCL is high
+        gpio_set_level(I2C_SCL_GPIO, 0);
+        Ets::delay_us(HALF_PERIOD_US);
+        gpio_set_level(I2C_SDA_GPIO, 0);
+        Ets::delay_us(HALF_PERIOD_US);
+        gpio_set_level(I2C_SCL_GPIO, 1);
+        Ets::delay_us(HALF_PERIOD_US);
+        gpio_set_level(I2C_SDA_GPIO, 1);
+        Ets::delay_us(HALF_PERIOD_US);
+        Ok(gpio_get_level(I2C_SDA_GPIO) == 1)
+    }
+}
+
+/// Probes every 7-bit address in `0x08..=0x77` with a zero-length write and returns the ones
+/// that ACK. Useful for checking the wiring before the drivers are initialized.
+pub(crate) fn scan_i2c_bus(i2c_bus: &'static SharedI2cBus) -> Vec<u8> {
+    let mut probe = RefCellDevice::new(i2c_bus);
+
+    (0x08..=0x77)
+        .filter(|&address| probe.write(address, &[]).is_ok())
+        .collect()
+}
+
+fn build_filter_chain((min, max): (f32, f32)) -> FilterChain {
+    Box::new(
+        RangeClamp::new(min, max)
+            .then(MedianFilter::new(FILTER_MEDIAN_WINDOW))
+            .then(EmaFilter::new(FILTER_EMA_ALPHA)),
+    )
+}
+
+/// Like `build_filter_chain`, with the optional spike guard between the clamp and the smoothing
+/// stages, so a single bad sample is caught before it reaches (and drags) the median and EMA.
+fn build_pressure_filter_chain() -> FilterChain {
+    let (min, max) = PRESSURE_RANGE_HPA;
+    let Some(max_delta) = PRESSURE_SPIKE_MAX_DELTA_HPA else {
+        return build_filter_chain(PRESSURE_RANGE_HPA);
+    };
+
+    Box::new(
+        RangeClamp::new(min, max)
+            .then(SpikeGuard::new(max_delta, pressure_spike_policy()))
+            .then(MedianFilter::new(FILTER_MEDIAN_WINDOW))
+            .then(EmaFilter::new(FILTER_EMA_ALPHA)),
+    )
+}
+
+/// Synthetic readings for `SENSOR_MODE=simulated`: a sine-wave temperature, random-walk
+/// humidity/VOC and a slowly drifting pressure, so the full pipeline runs without hardware.
+struct SensorSimulator {
+    started: Instant,
+    humidity: f32,
+    voc: f32,
+}
+
+impl SensorSimulator {
     fn new() -> Self {
         Self {
-            boot_time: Instant::now(),
-            consecutive_one: 0,
+            started: Instant::now(),
+            humidity: 45.0,
+            voc: 100.0,
         }
     }
 
-    fn check_stuck_condition(&mut self, voc: Option<u16>) -> bool {
-        if self.boot_time.elapsed() < Duration::from_secs(SGP_40_WARMUP_SECS) {
-            self.consecutive_one = 0;
-            return false;
+    fn next_sample(&mut self) -> AirSample {
+        let elapsed_s = self.started.elapsed().as_secs() as f32;
+        let phase = elapsed_s / SIMULATED_TEMPERATURE_PERIOD_SECS * TAU;
+
+        self.humidity = (self.humidity + random_step(0.5)).clamp(20.0, 80.0);
+        self.voc = (self.voc + random_step(5.0)).clamp(1.0, 500.0);
+
+        AirSample {
+            temperature: Some(21.0 + 3.0 * phase.sin()),
+            humidity: Some(self.humidity),
+            pressure_pa: Some(101_325.0 + 50.0 * phase.cos()),
+            voc: Some(self.voc.round() as u16),
+            voc_fresh: true,
+            nox: matches!(voc_sensor_model(), VocSensorModel::Sgp41).then_some(1),
         }
+    }
+}
+
+/// Rounded and clamped to the ranges the SGP40 accepts for its humidity/temperature compensation.
+#[cfg(feature = "sgp40")]
+fn sgp40_compensation(humidity: f32, temperature: f32) -> (u16, i16) {
+    (
+        humidity.round().clamp(0.0, 100.0) as u16,
+        temperature.round().clamp(-40.0, 85.0) as i16,
+    )
+}
+
+/// Uniformly distributed step in `[-max, max]`, using the hardware RNG.
+fn random_step(max: f32) -> f32 {
+    let unit = unsafe { esp_random() } as f32 / u32::MAX as f32;
+    (unit * 2.0 - 1.0) * max
+}
 
-        match voc {
-            Some(1) => {
-                self.consecutive_one = self.consecutive_one.saturating_add(1);
-                self.consecutive_one >= SGP_40_STUCK_AT_ONE_THRESHOLD
+/// Retries a failed sensor read up to `SENSOR_READ_RETRIES` times after a short pause: transient
+/// I2C NAKs are common and usually clear on the next try. Only the final error is returned, so
+/// callers log once retries are exhausted.
+async fn with_retries<T, E>(mut read: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut retries_left = SENSOR_READ_RETRIES;
+    loop {
+        match read() {
+            Err(_) if retries_left > 0 => {
+                retries_left -= 1;
+                Timer::after_millis(SENSOR_READ_RETRY_DELAY_MS).await;
             }
-            Some(_) | None => {
-                self.consecutive_one = 0;
-                false
+            result => return result,
+        }
+    }
+}
+
+fn init_bme280(bme: &mut Bme280<I2cBusDevice, Delay>) -> bool {
+    let bme_sampling_config = Configuration::default()
+        .with_humidity_oversampling(Oversampling::Oversample1)
+        .with_temperature_oversampling(Oversampling::Oversample1)
+        .with_pressure_oversampling(Oversampling::Oversample1)
+        .with_sensor_mode(SensorMode::Normal);
+
+    let result = bme.init().context("‼️Failed to init BME280").and_then(|_| {
+        bme.set_sampling_configuration(bme_sampling_config)
+            .context("‼️BME280 sensor configuration error")
+    });
+
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("{:?}", e);
+            false
+        }
+    }
+}
+
+/// The SGP40/SGP41 only answer commands, so reading the serial number doubles as a presence check.
+#[cfg(feature = "sgp40")]
+fn probe_voc_sensor(voc_sensor: &mut VocSensor) -> bool {
+    let serial = match voc_sensor {
+        VocSensor::Sgp40(sgp40) => sgp40.serial().map_err(|e| anyhow::anyhow!("{:?}", e)),
+        VocSensor::Sgp41(sgp41) => sgp41.serial(),
+    };
+
+    match serial {
+        Ok(_) => true,
+        Err(e) => {
+            warn!(
+                "‼️ {} not detected, VOC will be omitted: {:?}",
+                voc_sensor_model().name(),
+                e
+            );
+            false
+        }
+    }
+}
+
+impl VocSensorModel {
+    fn name(self) -> &'static str {
+        match self {
+            VocSensorModel::Sgp40 => "SGP40",
+            VocSensorModel::Sgp41 => "SGP41",
+        }
+    }
+
+    /// The index algorithms start after the same blackout and ramp on both; the SGP41 only
+    /// begins measuring once its conditioning phase is over.
+    fn warmup_secs(self) -> u64 {
+        match self {
+            VocSensorModel::Sgp40 => SGP_40_WARMUP_SECS,
+            VocSensorModel::Sgp41 => SGP41_CONDITIONING_SECS + SGP_40_WARMUP_SECS,
+        }
+    }
+}
+
+fn availability(available: bool) -> &'static str {
+    if available { "✅" } else { "❌" }
+}
+
+/// The light sensor is optional hardware: if it doesn't answer at boot, the station runs without
+/// it.
+fn init_bh1750(i2c_bus: &'static SharedI2cBus) -> Option<Bh1750> {
+    if !BH1750_ENABLED {
+        return None;
+    }
+
+    match Bh1750::new(RefCellDevice::new(i2c_bus), BH1750_ADDRESS) {
+        Ok(bh1750) => {
+            info!("💡 BH1750 detected at 0x{:02X}", BH1750_ADDRESS);
+            Some(bh1750)
+        }
+        Err(e) => {
+            warn!("💡 BH1750 not detected, skipping lux readings: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Running mean of the VOC index over one send interval (`VOC_AVERAGE_OVER_INTERVAL`).
+#[derive(Default)]
+struct VocAverage {
+    sum: u32,
+    count: u32,
+}
+
+impl VocAverage {
+    fn add(&mut self, voc: u16) {
+        self.sum += u32::from(voc);
+        self.count += 1;
+    }
+
+    /// `None` until a post-warmup sample arrived, rather than a misleading early value.
+    fn mean(&self) -> Option<u16> {
+        (self.count > 0).then(|| ((self.sum + self.count / 2) / self.count) as u16)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// `temperature_ambient_c` per `TEMPERATURE_FUSION`, from the filtered BME280 temperature and
+/// the DS18B20 reading of the same moment.
+#[derive(Default)]
+struct AmbientFusion {
+    /// How far the BME280 reads above the probe, averaged; `None` until both were seen together.
+    offset: Option<f32>,
+}
+
+impl AmbientFusion {
+    fn fuse(&mut self, bme280_c: Option<f32>, probe_c: Option<f32>) -> Option<f32> {
+        match temperature_fusion() {
+            TemperatureFusion::Off => None,
+            TemperatureFusion::Probe => probe_c,
+            TemperatureFusion::LearnedOffset => {
+                let bme280_c = bme280_c?;
+                if let Some(probe_c) = probe_c {
+                    let difference = bme280_c - probe_c;
+                    self.offset = Some(match self.offset {
+                        Some(offset) => offset + FUSION_OFFSET_ALPHA * (difference - offset),
+                        None => difference,
+                    });
+                }
+                self.offset.map(|offset| bme280_c - offset)
             }
         }
     }
 }
+
+/// Time since a sensor was powered up, for sensors whose readings are meaningless until they
+/// have settled (SGP40, MQ heaters).
+pub(crate) struct Warmup {
+    started: Instant,
+    duration: Duration,
+}
+
+impl Warmup {
+    pub(crate) fn new(secs: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            duration: Duration::from_secs(secs),
+        }
+    }
+
+    pub(crate) fn is_over(&self) -> bool {
+        self.started.elapsed() >= self.duration
+    }
+}
+
+struct Sgp40Health {
+    warmup: Warmup,
+    stuck: StuckDetector,
+}
+
+impl Sgp40Health {
+    fn new(warmup_secs: u64) -> Self {
+        Self {
+            warmup: Warmup::new(warmup_secs),
+            stuck: StuckDetector::new(SGP_40_STUCK_AT_ONE_THRESHOLD, SGP40_STUCK_RAW_TOLERANCE),
+        }
+    }
+
+    fn is_warmed_up(&self) -> bool {
+        self.warmup.is_over()
+    }
+
+    /// Stuck means `SGP_40_STUCK_AT_ONE_THRESHOLD` consecutive ones after the warm-up, and with
+    /// `SGP40_STUCK_RAW_CROSS_CHECK` a raw signal that stayed within `SGP40_STUCK_RAW_TOLERANCE`
+    /// over the same readings (see `StuckDetector`).
+    fn check_stuck_condition(&mut self, voc: Option<u16>, raw: Option<u16>) -> bool {
+        if !self.is_warmed_up() {
+            self.stuck.reset();
+            return false;
+        }
+
+        self.stuck.check(voc, raw)
+    }
+}
@@ -0,0 +1,191 @@
+use crate::config::SERIAL_CONSOLE_COMMANDS;
+use crate::nvs;
+use crate::settings::{self, SettingsPatch};
+use crate::tasks;
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+
+/// Longer lines are discarded whole rather than dispatched truncated.
+const MAX_LINE_LEN: usize = 128;
+/// Without a UART driver installed, stdin doesn't block but reports no data; poll at this pace.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// One console command: `name` is its first word, `run` gets the words after it.
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    run: fn(&[&str]) -> Result<()>,
+}
+
+/// Every command the console knows; `SERIAL_CONSOLE_COMMANDS` picks which ones are answered.
+/// A new command is one more entry here.
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        usage: "help                      list the enabled commands",
+        run: help,
+    },
+    Command {
+        name: "status",
+        usage: "status                    health line and runtime settings",
+        run: status,
+    },
+    Command {
+        name: "send",
+        usage: "send                      send the next reading regardless of the schedule",
+        run: send,
+    },
+    Command {
+        name: "set",
+        usage: "set interval|delay|mode|timezone <value>   retune and persist a runtime setting",
+        run: set,
+    },
+    Command {
+        name: "calibrate",
+        usage: "calibrate                 reboot once into the calibration CSV stream",
+        run: calibrate,
+    },
+    Command {
+        name: "reboot",
+        usage: "reboot                    restart the MCU",
+        run: reboot,
+    },
+];
+
+/// `serial-console`: reads commands line by line from the USB serial console, for field debugging
+/// without a network. Runs on its own thread, since reading stdin is a blocking call.
+pub(crate) fn spawn() -> Result<()> {
+    std::thread::Builder::new()
+        .stack_size(6144)
+        .spawn(read_lines)
+        .context("‼️ Failed to spawn the serial console")?;
+
+    info!(
+        "⌨️ Serial console ready, commands: {}",
+        SERIAL_CONSOLE_COMMANDS.join(", ")
+    );
+    Ok(())
+}
+
+fn read_lines() {
+    let mut stdin = std::io::stdin();
+    let mut line = Vec::with_capacity(MAX_LINE_LEN);
+    let mut overflowed = false;
+    let mut buf = [0u8; 32];
+
+    loop {
+        let len = match stdin.read(&mut buf) {
+            Ok(len) if len > 0 => len,
+            Ok(_) => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(e) => {
+                warn!("⌨️ Serial console read failed: {:?}", e);
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+
+        for &byte in &buf[..len] {
+            match byte {
+                b'\r' | b'\n' => {
+                    if !overflowed && !line.is_empty() {
+                        dispatch(&String::from_utf8_lossy(&line));
+                    }
+                    line.clear();
+                    overflowed = false;
+                }
+                _ if line.len() >= MAX_LINE_LEN => overflowed = true,
+                byte => line.push(byte),
+            }
+        }
+    }
+}
+
+fn dispatch(line: &str) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let [name, args @ ..] = words.as_slice() else {
+        return;
+    };
+
+    let result = match enabled_command(name) {
+        Some(command) => (command.run)(args),
+        None => Err(anyhow!("unknown command {:?}, try `help`", name)),
+    };
+    if let Err(e) = result {
+        println!("error: {}", e);
+    }
+}
+
+fn enabled_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| {
+        command.name.eq_ignore_ascii_case(name) && SERIAL_CONSOLE_COMMANDS.contains(&command.name)
+    })
+}
+
+fn help(_args: &[&str]) -> Result<()> {
+    for command in COMMANDS
+        .iter()
+        .filter(|command| SERIAL_CONSOLE_COMMANDS.contains(&command.name))
+    {
+        println!("  {}", command.usage);
+    }
+    Ok(())
+}
+
+fn status(_args: &[&str]) -> Result<()> {
+    println!("{}", tasks::health_line());
+    println!("settings: {:?}", settings::current());
+    Ok(())
+}
+
+fn send(_args: &[&str]) -> Result<()> {
+    tasks::request_send();
+    println!("ok, the next reading goes out");
+    Ok(())
+}
+
+/// `interval` is in seconds, `delay` (between sensor reads) in milliseconds. The patch goes
+/// through the same validation and NVS persistence as `POST /config`.
+fn set(args: &[&str]) -> Result<()> {
+    let [key, value] = args else {
+        anyhow::bail!("usage: set interval|delay|mode|timezone <value>");
+    };
+    let number = || {
+        value
+            .parse::<u64>()
+            .map_err(|_| anyhow!("{:?} is not a number", value))
+    };
+
+    let patch = match *key {
+        "interval" => serde_json::json!({ "send_interval_ms": number()?.saturating_mul(1000) }),
+        "delay" => serde_json::json!({ "execution_delay_ms": number()? }),
+        "mode" => serde_json::json!({ "send_mode": value }),
+        "timezone" => serde_json::json!({ "timezone": value }),
+        _ => anyhow::bail!("unknown setting {:?}", key),
+    };
+
+    let settings = settings::apply(serde_json::from_value::<SettingsPatch>(patch)?)?;
+    println!("ok, {:?}", settings);
+    Ok(())
+}
+
+fn calibrate(_args: &[&str]) -> Result<()> {
+    nvs::request_calibration_boot()?;
+    println!("ok, rebooting into calibration mode; reset the board to leave it");
+    tasks::request_console_reboot();
+    Ok(())
+}
+
+fn reboot(_args: &[&str]) -> Result<()> {
+    println!("ok, rebooting");
+    tasks::request_console_reboot();
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use crate::config::{EXECUTION_DELAY_MS, HTTP_SEND_INTERVAL_MS, SEND_ON_CHANGE};
+use crate::nvs;
+#[cfg(feature = "ntp")]
+use crate::time_utils;
+#[cfg(feature = "ntp")]
+use chrono_tz::Tz;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+const NVS_KEY: &str = "settings";
+#[cfg(feature = "ntp")]
+const TIMEZONE_NVS_KEY: &str = "timezone";
+#[cfg(feature = "http")]
+const ETAG_NVS_KEY: &str = "config_etag";
+const MIN_EXECUTION_DELAY_MS: u64 = 100;
+
+/// Parameters that can be retuned at runtime (and persisted to NVS) without re-flashing.
+/// The compile-time consts in `config.rs` are the defaults.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub(crate) struct RuntimeSettings {
+    pub(crate) send_interval_ms: u64,
+    pub(crate) execution_delay_ms: u64,
+    pub(crate) send_mode: SendMode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SendMode {
+    /// Queue a sample for the transports every `send_interval_ms`.
+    Interval,
+    /// Queue a sample only when it differs from the last sent one (with a heartbeat).
+    OnChange,
+    /// Keep reading and logging, but queue nothing.
+    Paused,
+}
+
+/// Partial update, e.g. `{"send_interval_ms": 30000}`. Unknown keys are rejected.
+///
+/// `timezone` (an IANA name such as `"America/Denver"`) overrides the compiled-in `TIMEZONE`, so
+/// one firmware image can serve a fleet spread over several zones. It needs the `ntp` feature.
+#[cfg(any(feature = "http", feature = "http-server", feature = "serial-console"))]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SettingsPatch {
+    send_interval_ms: Option<u64>,
+    execution_delay_ms: Option<u64>,
+    send_mode: Option<SendMode>,
+    timezone: Option<String>,
+}
+
+const DEFAULT_SETTINGS: RuntimeSettings = RuntimeSettings {
+    send_interval_ms: HTTP_SEND_INTERVAL_MS,
+    execution_delay_ms: EXECUTION_DELAY_MS,
+    send_mode: if SEND_ON_CHANGE {
+        SendMode::OnChange
+    } else {
+        SendMode::Interval
+    },
+};
+
+static SETTINGS: Mutex<CriticalSectionRawMutex, Cell<RuntimeSettings>> =
+    Mutex::new(Cell::new(DEFAULT_SETTINGS));
+
+pub(crate) fn current() -> RuntimeSettings {
+    SETTINGS.lock(|settings| settings.get())
+}
+
+/// Restores previously saved settings; falls back to the compiled-in defaults.
+pub(crate) fn load_from_nvs() {
+    let stored = match nvs::get_blob(NVS_KEY) {
+        Ok(Some(blob)) => serde_json::from_slice::<RuntimeSettings>(&blob).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            warn!("⚙️ Could not read settings from NVS: {:?}", e);
+            None
+        }
+    };
+
+    match stored.filter(|settings| validate(settings).is_ok()) {
+        Some(settings) => {
+            SETTINGS.lock(|cell| cell.set(settings));
+            info!("⚙️ Runtime settings restored from NVS: {:?}", settings);
+        }
+        None => info!("⚙️ Using default runtime settings"),
+    }
+
+    #[cfg(feature = "ntp")]
+    if let Ok(Some(name)) = nvs::get_blob(TIMEZONE_NVS_KEY) {
+        match parse_timezone(&String::from_utf8_lossy(&name)) {
+            Ok(timezone) => {
+                time_utils::set_timezone(timezone);
+                info!("⚙️ Timezone restored from NVS: {}", timezone.name());
+            }
+            Err(e) => warn!("⚙️ Ignoring the stored timezone: {}", e),
+        }
+    }
+}
+
+/// Validates and applies a patch; on error nothing is changed. The result is staged for NVS (see
+/// `nvs::stage_blob`), so it takes effect at once but reaches flash only with the next flush.
+#[cfg(any(feature = "http", feature = "http-server", feature = "serial-console"))]
+pub(crate) fn apply(patch: SettingsPatch) -> anyhow::Result<RuntimeSettings> {
+    let mut settings = current();
+    #[cfg(feature = "ntp")]
+    let timezone = patch.timezone.as_deref().map(parse_timezone).transpose()?;
+    #[cfg(not(feature = "ntp"))]
+    if patch.timezone.is_some() {
+        anyhow::bail!("timezone needs the `ntp` feature, the clock is never synced without it");
+    }
+
+    if let Some(send_interval_ms) = patch.send_interval_ms {
+        settings.send_interval_ms = send_interval_ms;
+    }
+    if let Some(execution_delay_ms) = patch.execution_delay_ms {
+        settings.execution_delay_ms = execution_delay_ms;
+    }
+    if let Some(send_mode) = patch.send_mode {
+        settings.send_mode = send_mode;
+    }
+
+    validate(&settings)?;
+    let blob = serde_json::to_vec(&settings)?;
+
+    SETTINGS.lock(|cell| cell.set(settings));
+    nvs::stage_blob(NVS_KEY, &blob);
+
+    info!("⚙️ Runtime settings updated: {:?}", settings);
+    #[cfg(feature = "ntp")]
+    if let Some(timezone) = timezone {
+        time_utils::set_timezone(timezone);
+        nvs::stage_blob(TIMEZONE_NVS_KEY, timezone.name().as_bytes());
+        info!("⚙️ Timezone set to {}", timezone.name());
+    }
+    Ok(settings)
+}
+
+/// ETag of the remote config currently applied, sent back as `If-None-Match`.
+#[cfg(feature = "http")]
+pub(crate) fn stored_config_etag() -> Option<String> {
+    nvs::get_blob(ETAG_NVS_KEY)
+        .ok()
+        .flatten()
+        .and_then(|etag| String::from_utf8(etag).ok())
+}
+
+/// Applies a fetched remote config and remembers its ETag. The ETag is only stored once the
+/// config was accepted, so a rejected one is fetched (and reported) again on the next poll.
+#[cfg(feature = "http")]
+pub(crate) fn apply_remote(body: &[u8], etag: Option<&str>) -> anyhow::Result<RuntimeSettings> {
+    let settings = apply(serde_json::from_slice::<SettingsPatch>(body)?)?;
+
+    match etag {
+        Some(etag) => nvs::stage_blob(ETAG_NVS_KEY, etag.as_bytes()),
+        None => {
+            let _ = nvs::remove(ETAG_NVS_KEY);
+        }
+    }
+
+    Ok(settings)
+}
+
+#[cfg(feature = "ntp")]
+fn parse_timezone(name: &str) -> anyhow::Result<Tz> {
+    name.parse()
+        .map_err(|e| anyhow::anyhow!("unknown timezone {:?}: {}", name, e))
+}
+
+fn validate(settings: &RuntimeSettings) -> anyhow::Result<()> {
+    if settings.send_interval_ms == 0 {
+        anyhow::bail!("send_interval_ms must be positive");
+    }
+    if settings.execution_delay_ms < MIN_EXECUTION_DELAY_MS {
+        anyhow::bail!(
+            "execution_delay_ms must be at least {}",
+            MIN_EXECUTION_DELAY_MS
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::I2cBusDevice;
+use embassy_time::{Delay, Duration, Instant};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use smog_core::gas_index::{GasIndex, GasKind};
+use smog_core::sensirion::{compensation_ticks, crc8};
+
+pub(crate) const SGP41_ADDRESS: u8 = 0x59;
+const EXECUTE_CONDITIONING: [u8; 2] = [0x26, 0x12];
+const MEASURE_RAW_SIGNALS: [u8; 2] = [0x26, 0x19];
+const GET_SERIAL_NUMBER: [u8; 2] = [0x36, 0x82];
+const MEASUREMENT_DURATION_MS: u32 = 50;
+/// The datasheet's conditioning after power-up: needed by the NOx pixel, and capped at 10 s to
+/// not damage it.
+pub(crate) const CONDITIONING_SECS: u64 = 10;
+
+/// One measurement; both indices are `None` while conditioning.
+pub(crate) struct Sgp41Reading {
+    pub(crate) voc_index: Option<u16>,
+    pub(crate) nox_index: Option<u16>,
+}
+
+/// Minimal SGP41 driver: the sensor only reports raw signals, so the VOC and NOx indices come
+/// from `gas_index`, fed once per `measure` (`sampling_interval_s` apart).
+pub(crate) struct Sgp41 {
+    i2c: I2cBusDevice,
+    /// Set by the first conditioning command: the sensor may be constructed well before it is
+    /// first measured, and `recondition` starts over.
+    conditioning_since: Option<Instant>,
+    voc: GasIndex,
+    nox: GasIndex,
+    last_voc_raw: Option<u16>,
+}
+
+impl Sgp41 {
+    pub(crate) fn new(i2c: I2cBusDevice, sampling_interval_s: f32) -> Self {
+        Self {
+            i2c,
+            conditioning_since: None,
+            voc: GasIndex::new(GasKind::Voc, sampling_interval_s),
+            nox: GasIndex::new(GasKind::Nox, sampling_interval_s),
+            last_voc_raw: None,
+        }
+    }
+
+    /// The SGP41 only answers commands, so reading its serial number doubles as a presence check.
+    pub(crate) fn serial(&mut self) -> anyhow::Result<u64> {
+        let words = self.command::<3>(&GET_SERIAL_NUMBER, None, 1)?;
+        Ok(words
+            .iter()
+            .fold(0, |serial, &word| (serial << 16) | u64::from(word)))
+    }
+
+    /// Conditions the sensor for `CONDITIONING_SECS` from the first call, then measures both
+    /// signals and runs them through the index algorithms. Humidity (%) and temperature (°C)
+    /// compensate the measurement, as for the SGP40.
+    pub(crate) fn measure(
+        &mut self,
+        humidity: f32,
+        temperature: f32,
+    ) -> anyhow::Result<Sgp41Reading> {
+        let compensation = compensation_ticks(humidity, temperature);
+
+        if self.is_conditioning() {
+            let [voc_raw] = self.command::<1>(
+                &EXECUTE_CONDITIONING,
+                Some(compensation),
+                MEASUREMENT_DURATION_MS,
+            )?;
+            self.last_voc_raw = Some(voc_raw);
+            return Ok(Sgp41Reading {
+                voc_index: None,
+                nox_index: None,
+            });
+        }
+
+        let [voc_raw, nox_raw] = self.command::<2>(
+            &MEASURE_RAW_SIGNALS,
+            Some(compensation),
+            MEASUREMENT_DURATION_MS,
+        )?;
+        self.last_voc_raw = Some(voc_raw);
+        Ok(Sgp41Reading {
+            voc_index: Some(self.voc.process(voc_raw)),
+            nox_index: Some(self.nox.process(nox_raw)),
+        })
+    }
+
+    /// The raw VOC signal of the last `measure`: unlike the SGP40, it comes with every index.
+    pub(crate) fn last_voc_raw(&self) -> Option<u16> {
+        self.last_voc_raw
+    }
+
+    /// Conditions again from the next `measure`: a bus recovery may have reset the sensor, which
+    /// turns its heater off. The index algorithms keep what they learned.
+    pub(crate) fn recondition(&mut self) {
+        self.conditioning_since = None;
+    }
+
+    fn is_conditioning(&mut self) -> bool {
+        let since = *self.conditioning_since.get_or_insert_with(Instant::now);
+        since.elapsed() < Duration::from_secs(CONDITIONING_SECS)
+    }
+
+    /// Sends a command with optional arguments and reads back `N` CRC-checked words.
+    fn command<const N: usize>(
+        &mut self,
+        command: &[u8; 2],
+        arguments: Option<[u16; 2]>,
+        duration_ms: u32,
+    ) -> anyhow::Result<[u16; N]> {
+        let mut request = Vec::with_capacity(8);
+        request.extend_from_slice(command);
+        for word in arguments.into_iter().flatten() {
+            let bytes = word.to_be_bytes();
+            request.extend_from_slice(&bytes);
+            request.push(crc8(&bytes));
+        }
+        self.i2c
+            .write(SGP41_ADDRESS, &request)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+
+        Delay.delay_ms(duration_ms);
+
+        let mut response = [0u8; 9];
+        let response = &mut response[..N * 3];
+        self.i2c
+            .read(SGP41_ADDRESS, response)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+
+        let mut words = [0u16; N];
+        for (word, chunk) in words.iter_mut().zip(response.chunks_exact(3)) {
+            if crc8(&chunk[..2]) != chunk[2] {
+                anyhow::bail!("SGP41 CRC mismatch");
+            }
+            *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+        }
+        Ok(words)
+    }
+}
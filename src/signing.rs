@@ -0,0 +1,78 @@
+use crate::config::{HMAC_SECRET, is_signing_enabled};
+use crate::device::device_id;
+use crate::time_utils::timestamp_unix_s;
+use anyhow::{Result, anyhow};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt::Write;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC-SHA256 over `{device_id}.{timestamp}.{body}` with the shared `HMAC_SECRET`, sent as the
+/// `X-Device-Id`, `X-Timestamp` and `X-Signature` (lowercase hex) headers. The server recomputes
+/// it and rejects stale timestamps, so a captured request can't be replayed later; that check
+/// relies on the device clock, i.e. on NTP.
+///
+/// The body can be fed in pieces, so a streamed batch is signed without buffering it.
+pub(crate) struct PayloadSigner {
+    mac: HmacSha256,
+    timestamp: String,
+}
+
+impl PayloadSigner {
+    /// `None` while signing is disabled.
+    pub(crate) fn new() -> Result<Option<Self>> {
+        if !is_signing_enabled() {
+            return Ok(None);
+        }
+        validate_config()?;
+
+        let mut mac = HmacSha256::new_from_slice(HMAC_SECRET.as_bytes())
+            .map_err(|e| anyhow!("‼️ Invalid HMAC_SECRET: {}", e))?;
+        let timestamp = timestamp_unix_s().to_string();
+        mac.update(device_id().as_bytes());
+        mac.update(b".");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+
+        Ok(Some(Self { mac, timestamp }))
+    }
+
+    pub(crate) fn update(&mut self, body_part: &[u8]) {
+        self.mac.update(body_part);
+    }
+
+    pub(crate) fn into_headers(self) -> [(&'static str, String); 3] {
+        let signature = self.mac.finalize().into_bytes().iter().fold(
+            String::with_capacity(64),
+            |mut hex, byte| {
+                let _ = write!(hex, "{:02x}", byte);
+                hex
+            },
+        );
+
+        [
+            ("X-Device-Id", device_id().to_string()),
+            ("X-Timestamp", self.timestamp),
+            ("X-Signature", signature),
+        ]
+    }
+}
+
+/// Signature headers for a body available in one piece; empty while signing is disabled.
+pub(crate) fn signature_headers(body: &[u8]) -> Result<Vec<(&'static str, String)>> {
+    Ok(PayloadSigner::new()?
+        .map(|mut signer| {
+            signer.update(body);
+            signer.into_headers().to_vec()
+        })
+        .unwrap_or_default())
+}
+
+/// Signing without a secret would send requests the server can only reject.
+pub(crate) fn validate_config() -> Result<()> {
+    if is_signing_enabled() && HMAC_SECRET.is_empty() {
+        anyhow::bail!("‼️ HTTP_SIGNING_ENABLED=true but HMAC_SECRET is empty");
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use crate::config::INTERVAL_STATS_MAX_SAMPLES;
+use crate::models::WeatherData;
+use serde::Serialize;
+use smog_core::stats::percentile;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Lowest and highest value seen in a window.
+#[derive(Clone, Copy)]
+pub(crate) struct MinMax {
+    range: Option<(f32, f32)>,
+}
+
+impl MinMax {
+    const EMPTY: Self = Self { range: None };
+
+    fn update(&mut self, value: f32) {
+        self.range = Some(match self.range {
+            Some((min, max)) => (min.min(value), max.max(value)),
+            None => (value, value),
+        });
+    }
+}
+
+impl fmt::Display for MinMax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.range {
+            Some((min, max)) => write!(f, "{:.1}..{:.1}", min, max),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+/// Daily min/max of the core metrics, reset at local midnight by the rollover task.
+#[derive(Clone, Copy)]
+pub(crate) struct DailySummary {
+    pub(crate) samples: u32,
+    pub(crate) temperature: MinMax,
+    pub(crate) humidity: MinMax,
+    pub(crate) pressure: MinMax,
+}
+
+impl DailySummary {
+    pub(crate) const EMPTY: Self = Self {
+        samples: 0,
+        temperature: MinMax::EMPTY,
+        humidity: MinMax::EMPTY,
+        pressure: MinMax::EMPTY,
+    };
+
+    pub(crate) fn update(&mut self, data: &WeatherData) {
+        self.samples += 1;
+        if let Some(temperature) = data.temperature {
+            self.temperature.update(temperature);
+        }
+        if let Some(humidity) = data.humidity {
+            self.humidity.update(humidity);
+        }
+        if let Some(pressure) = data.pressure {
+            self.pressure.update(pressure);
+        }
+    }
+}
+
+/// Distribution of one field over a send interval.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Percentiles {
+    pub(crate) p50: f32,
+    pub(crate) p90: f32,
+    pub(crate) max: f32,
+}
+
+impl Percentiles {
+    /// Applies a unit conversion or rounding to every value; both keep the order intact.
+    pub(crate) fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Self {
+            p50: f(self.p50),
+            p90: f(self.p90),
+            max: f(self.max),
+        }
+    }
+}
+
+/// One field's readings since the last send, capped at `INTERVAL_STATS_MAX_SAMPLES` (the oldest
+/// go first), so a long interval costs bounded memory and the tail of it is what counts.
+#[derive(Default)]
+struct SampleWindow {
+    values: VecDeque<f32>,
+}
+
+impl SampleWindow {
+    fn push(&mut self, value: f32) {
+        if self.values.len() >= INTERVAL_STATS_MAX_SAMPLES {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    fn percentiles(&self) -> Option<Percentiles> {
+        let mut sorted: Vec<f32> = self.values.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+
+        Some(Percentiles {
+            p50: percentile(&sorted, 50.0)?,
+            p90: percentile(&sorted, 90.0)?,
+            max: *sorted.last()?,
+        })
+    }
+}
+
+/// p50/p90/max of the core fields over one send interval (`SEND_INTERVAL_PERCENTILES`).
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct IntervalStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<Percentiles>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) humidity: Option<Percentiles>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pressure: Option<Percentiles>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) voc: Option<Percentiles>,
+}
+
+/// Collects the readings behind `IntervalStats`, reset whenever a sample is queued.
+#[derive(Default)]
+pub(crate) struct IntervalAccumulator {
+    temperature: SampleWindow,
+    humidity: SampleWindow,
+    pressure: SampleWindow,
+    voc: SampleWindow,
+}
+
+impl IntervalAccumulator {
+    pub(crate) fn update(
+        &mut self,
+        temperature: Option<f32>,
+        humidity: Option<f32>,
+        pressure: Option<f32>,
+        voc: Option<u16>,
+    ) {
+        if let Some(temperature) = temperature {
+            self.temperature.push(temperature);
+        }
+        if let Some(humidity) = humidity {
+            self.humidity.push(humidity);
+        }
+        if let Some(pressure) = pressure {
+            self.pressure.push(pressure);
+        }
+        if let Some(voc) = voc {
+            self.voc.push(f32::from(voc));
+        }
+    }
+
+    pub(crate) fn summary(&self) -> IntervalStats {
+        IntervalStats {
+            temperature: self.temperature.percentiles(),
+            humidity: self.humidity.percentiles(),
+            pressure: self.pressure.percentiles(),
+            voc: self.voc.percentiles(),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
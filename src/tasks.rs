@@ -1,27 +1,163 @@
+use crate::alarm::Alarm;
 use crate::config::{
-    EXECUTION_DELAY_MS, HTTP_CONSUMER_ENDPOINT_URL, HTTP_SEND_INTERVAL_MS, is_sending_enabled,
+    ADAPTIVE_SEND_INTERVAL_MS, ADAPTIVE_SEND_SENSITIVITY, ADAPTIVE_SEND_WINDOW_MS,
+    DROP_SUMMARY_INTERVAL_MS, EXECUTION_DELAY_MS, HEALTH_REPORT_INTERVAL_MS,
+    HTTP_CLIENT_MIN_FREE_HEAP_BYTES, HUMIDITY_CHANGE_DELTA, LOCAL_ALERT_OFFLINE_MS,
+    LOCAL_ALERT_SENSOR_FAILURES, NVS_MIN_FLUSH_INTERVAL_MS, PRESSURE_CHANGE_DELTA,
+    SCHEDULED_REBOOT_INTERVAL_HOURS, SEND_ON_CHANGE_HEARTBEAT_MS, SENSOR_READ_BACKOFF_MAX_MS,
+    TEMPERATURE_CHANGE_DELTA, UnsyncedPolicy, VOC_ALERT_CONFIRM_READINGS, VOC_ALERT_COOLDOWN_MS,
+    VOC_ALERT_DELTA, VOC_CHANGE_DELTA, unsynced_policy,
 };
-use crate::logging::log_weather_data;
+#[cfg(feature = "http")]
+use crate::config::{
+    DELIVERY_VERIFY_URL, HTTP_CONSUMER_ENDPOINT_URL, HTTP_KEEPALIVE_MS, MAX_PAYLOAD_AGE_MS,
+    MAX_SILENCE_MS, NETWORK_MAX_CONSECUTIVE_FAILURES, NETWORK_STANDBY_MS, OFFLINE_BUFFER_CAPACITY,
+    QuietHoursPolicy, REMOTE_CONFIG_POLL_INTERVAL_MS, REMOTE_CONFIG_URL, is_sending_enabled,
+    quiet_hours_policy,
+};
+#[cfg(feature = "mqtt")]
+use crate::config::{MQTT_SEND_INTERVAL_MS, is_mqtt_enabled};
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use crate::config::{SEND_JITTER_EVERY_INTERVAL, SEND_JITTER_WINDOW_MS, channel_full_policy};
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use crate::device::mac_seed;
+use crate::display::{self, Display};
+#[cfg(feature = "http")]
+use crate::http_client::{ConfigResponse, HttpClient};
+use crate::led::{LedPattern, StatusLed};
 use crate::models::WeatherData;
-use crate::network::HttpClient;
+#[cfg(feature = "mqtt")]
+use crate::mqtt::{MQTT_CONNECTED_SIGNAL, MqttClient};
+use crate::network::{allow_out_of_band_send, is_wifi_connected, link_info};
+#[cfg(feature = "http")]
+use crate::network::{post_latency_summary, record_post_latency};
+use crate::nvs;
 use crate::sensors::WeatherStation;
-use crate::time_utils::{ntp_sync_watcher, wait_time_sync_grace_period};
+use crate::settings::{self, SendMode};
+#[cfg(feature = "http")]
+use crate::signing;
+use crate::stats::DailySummary;
+#[cfg(feature = "http")]
+use crate::telegram;
+#[cfg(feature = "ntp")]
+use crate::time_utils::ntp_sync_watcher;
+use crate::time_utils::{is_quiet_hours_now, is_time_synced, local_yesterday, wait_local_midnight};
+#[cfg(feature = "http")]
+use crate::time_utils::{timestamp_unix_s, wait_quiet_hours_end, wait_time_sync_grace_period};
+use crate::wind_rain::ReedSwitch;
+#[cfg(feature = "mqtt")]
+use embassy_futures::select::{self, Either};
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::channel::Channel;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use embassy_sync::channel::{Channel, TrySendError};
 use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Instant, Timer};
+#[cfg(feature = "ntp")]
 use esp_idf_svc::sntp::EspSntp;
-use log::{error, info, warn};
+use esp_idf_svc::sys::esp_get_free_heap_size;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use log::error;
+use log::{info, warn};
+use smog_core::change::ChangeDeltas;
+#[cfg(feature = "http")]
+use smog_core::delivery::{self, Outcome};
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use smog_core::queue::{Pushed, push_with_policy};
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use smog_core::schedule::SinkSchedule;
+use smog_core::schedule::{AdaptiveDelay, AdaptiveInterval, SendPolicy, Sink, interval_owner};
+use std::cell::Cell;
+#[cfg(feature = "http")]
+use std::collections::VecDeque;
+#[cfg(any(feature = "http", feature = "mqtt"))]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(any(feature = "http", feature = "mqtt"))]
+type SampleChannel = Channel<CriticalSectionRawMutex, WeatherData, 2>;
+
+#[cfg(feature = "http")]
+static NETWORK_CHANNEL: SampleChannel = Channel::new();
+#[cfg(feature = "mqtt")]
+static MQTT_CHANNEL: SampleChannel = Channel::new();
+
+/// Callback run by `sensor_task` for every successful reading.
+pub(crate) type Observer = fn(&WeatherData);
+/// Takes a sample due for a sink (see `SinkSchedule`) and returns whether it was queued.
+#[cfg(any(feature = "http", feature = "mqtt"))]
+pub(crate) type SinkQueue = fn(WeatherData) -> bool;
+
+/// The built-in observers plus room for a few more.
+const MAX_OBSERVERS: usize = 12;
+
+/// Where `sensor_task` hands its readings: every reading to the observers, the due samples to the
+/// sink queues. Fixed-size and `Copy`, so it is copied out of the lock without allocating and the
+/// callbacks never run inside the critical section.
+#[derive(Clone, Copy)]
+struct Registry {
+    observers: [Option<Observer>; MAX_OBSERVERS],
+    #[cfg(feature = "http")]
+    http: Option<SinkQueue>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<SinkQueue>,
+}
+
+static REGISTRY: Mutex<CriticalSectionRawMutex, Cell<Registry>> = Mutex::new(Cell::new(Registry {
+    observers: [None; MAX_OBSERVERS],
+    #[cfg(feature = "http")]
+    http: None,
+    #[cfg(feature = "mqtt")]
+    mqtt: None,
+}));
+
+/// Today's extremes, fed by the `record_daily_summary` observer.
+static DAILY_SUMMARY: Mutex<CriticalSectionRawMutex, Cell<DailySummary>> =
+    Mutex::new(Cell::new(DailySummary::EMPTY));
 
-static NETWORK_CHANNEL: Channel<CriticalSectionRawMutex, WeatherData, 2> = Channel::new();
+/// Samples lost because a channel was full, regardless of which end was dropped.
+static DROPPED_SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+/// Samples lost per cause since the last drop summary (`DROP_SUMMARY_INTERVAL_MS`).
+static WINDOW_DROPS_CHANNEL_FULL: AtomicU32 = AtomicU32::new(0);
+static WINDOW_DROPS_BUFFER_FULL: AtomicU32 = AtomicU32::new(0);
+static WINDOW_DROPS_REJECTED: AtomicU32 = AtomicU32::new(0);
+static WINDOW_DROPS_STALE: AtomicU32 = AtomicU32::new(0);
+static WINDOW_DROPS_QUIET_HOURS: AtomicU32 = AtomicU32::new(0);
+
+/// Failed sensor reads in a row, reset by the next good one; drives the local alarm. Stays at 0
+/// without a BME280.
+static CONSECUTIVE_READ_FAILURES: AtomicU32 = AtomicU32::new(0);
+
+/// Samples the server accepted (2xx).
+static SENT_SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+/// HTTP status of the last POST, or one of the `POST_STATUS_*` markers.
+static LAST_POST_STATUS: AtomicU32 = AtomicU32::new(POST_STATUS_NONE);
+const POST_STATUS_NONE: u32 = 0;
+const POST_STATUS_ERROR: u32 = 1;
+
+/// Uptime (seconds) of the last successful POST; starts at boot so the watchdog has a grace period.
+#[cfg(feature = "http")]
+static LAST_POST_SUCCESS_UPTIME_S: AtomicU32 = AtomicU32::new(0);
 
 #[derive(Copy, Clone, Debug)]
 enum RebootReason {
     Sgp40StuckAtOne,
+    #[cfg(feature = "http")]
+    NetworkSilence,
+    SensorLost,
+    Scheduled,
+    #[cfg(feature = "serial-console")]
+    Console,
 }
 
 static REBOOT_SIGNAL: Signal<CriticalSectionRawMutex, RebootReason> = Signal::new();
 
+/// Set by the serial console's `send`; the next reading goes to every transport off schedule.
+#[cfg(any(feature = "http", feature = "mqtt"))]
+static SEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+
 /// Sensor polling task.
 ///
 /// Continuously reads weather data from the sensor station at a fixed interval and manages data flow.
@@ -30,17 +166,29 @@ static REBOOT_SIGNAL: Signal<CriticalSectionRawMutex, RebootReason> = Signal::ne
 ///
 /// This task performs the following operations in an infinite loop:
 /// 1. Reads sensor data from the `WeatherStation` (BME280 + SGP40)
-/// 2. Logs the retrieved weather data to the console
+/// 2. Passes the reading to every registered observer (console logging is one of them)
 /// 3. Checks if the SGP40 VOC sensor is stuck at `VOC=1` (a known failure mode)
 /// 4. If a stuck condition is detected, signals the reboot supervisor to restart the MCU
-/// 5. Attempts to send data to the network task via `NETWORK_CHANNEL` if the sending interval has elapsed
-/// 6. Waits for `EXECUTION_DELAY_MS` before the next iteration
+/// 5. Attempts to send data to the network and MQTT tasks via `NETWORK_CHANNEL`/`MQTT_CHANNEL`
+///    if that transport's sending interval has elapsed (see `SinkSchedule`)
+/// 6. Waits for the execution delay before the next iteration, backing off after failed reads
+///    (see `AdaptiveDelay`)
+///
+/// Intervals and the send mode come from the runtime settings (`settings::current()`), so they
+/// can be retuned while running; the compile-time consts are only the defaults.
 ///
 /// # Data Flow
 ///
 /// - Successfully read sensor data is sent to `NETWORK_CHANNEL` for HTTP transmission
 /// - The channel uses a non-blocking `try_send()` to avoid blocking if the network task is busy
-/// - Data is only sent if `HTTP_SEND_INTERVAL_MS` has elapsed since the last sending
+/// - When a channel is full, `CHANNEL_FULL_POLICY` decides whether the new or the oldest sample is
+///   dropped
+/// - Data is only sent if the send interval has elapsed since the last sending; in `OnChange` mode
+///   it additionally has to differ from the last sent sample (see `is_send_due`)
+/// - HTTP follows `send_interval_ms`, MQTT `MQTT_SEND_INTERVAL_MS` when set, each timed from its
+///   own last send, and numbers its samples (`seq`) on its own
+/// - The interval accumulators (rain, VOC average, interval stats) restart with the sends of one
+///   sink, see `interval_owner`
 ///
 /// # SGP40 Stuck Detection
 ///
@@ -49,6 +197,17 @@ static REBOOT_SIGNAL: Signal<CriticalSectionRawMutex, RebootReason> = Signal::ne
 /// - The `REBOOT_SIGNAL` is triggered with `RebootReason::Sgp40StuckAtOne`
 /// - The `reboot_supervisor_task` will handle the actual MCU restart
 ///
+/// A sensor that keeps failing every read past `SENSOR_LOST_AFTER_ERRORS`, even after a bus
+/// recovery, requests a reboot the same way with `RebootReason::SensorLost`.
+///
+/// A VOC jump past `VOC_ALERT_DELTA` is queued right away, tagged `alert`, regardless of the
+/// schedule; the send timer restarts from there.
+///
+/// Samples captured before the first NTP sync are not queued at all under `UNSYNCED_POLICY=drop`.
+///
+/// Sends are held back by a per-device jitter (`SEND_JITTER_WINDOW_MS`) after boot and, with
+/// `SEND_JITTER_EVERY_INTERVAL`, after every send.
+///
 /// # Arguments
 ///
 /// * `station` - A static mutable reference to the initialized `WeatherStation` instance
@@ -59,33 +218,404 @@ static REBOOT_SIGNAL: Signal<CriticalSectionRawMutex, RebootReason> = Signal::ne
 /// If sensor reads fail, they are logged and the task continues.
 #[embassy_executor::task]
 pub(crate) async fn sensor_task(station: &'static mut WeatherStation) {
-    let mut last_send_time = Instant::now();
-    let send_interval = Duration::from_millis(HTTP_SEND_INTERVAL_MS);
+    #[cfg(feature = "http")]
+    let mut http_schedule = SinkSchedule::new(Instant::now().as_millis());
+    #[cfg(feature = "mqtt")]
+    let mut mqtt_schedule = SinkSchedule::new(Instant::now().as_millis());
+    let mut last_sent: Option<WeatherData> = None;
+    let mut read_delay = AdaptiveDelay::new(EXECUTION_DELAY_MS, SENSOR_READ_BACKOFF_MAX_MS);
+    #[cfg(any(feature = "http", feature = "mqtt"))]
+    let mut send_jitter = SendJitter::new(SEND_JITTER_WINDOW_MS, mac_seed());
+    #[cfg(any(feature = "http", feature = "mqtt"))]
+    let mut jitter = send_jitter.next_delay();
+    let mut voc_alert = VocAlert::default();
+    let mut adaptive_interval = ADAPTIVE_SEND_INTERVAL_MS.map(|bounds_ms| {
+        AdaptiveInterval::new(
+            bounds_ms,
+            settings::current().send_interval_ms,
+            ADAPTIVE_SEND_WINDOW_MS,
+            ADAPTIVE_SEND_SENSITIVITY,
+            CHANGE_DELTAS,
+        )
+    });
 
     loop {
-        if let Some(data) = station.read_sensor_data().await {
-            log_weather_data(&data);
+        let settings = settings::current();
+        read_delay.set_base(settings.execution_delay_ms);
+
+        if let Some(mut data) = station.read_sensor_data().await {
+            read_delay.on_success();
+            CONSECUTIVE_READ_FAILURES.store(0, Ordering::Relaxed);
+            notify_observers(&data);
+
+            if let Some(adaptive_interval) = adaptive_interval.as_mut() {
+                let previous_ms = adaptive_interval.current();
+                if let Some(rate) =
+                    adaptive_interval.observe(Instant::now().as_millis(), data.reading())
+                {
+                    info!(
+                        "⏱️ Adaptive send interval {}s -> {}s ({:.2} change deltas/min)",
+                        previous_ms / 1000,
+                        adaptive_interval.current() / 1000,
+                        rate
+                    );
+                }
+            }
+            #[cfg(any(feature = "http", feature = "mqtt"))]
+            let send_interval_ms = adaptive_interval
+                .as_ref()
+                .map_or(settings.send_interval_ms, AdaptiveInterval::current);
 
-            let is_stuck_at_one = station.sgp40_stuck_at_one(data.voc);
+            let is_stuck_at_one = station.sgp40_stuck_at_one();
 
             if is_stuck_at_one {
                 warn!("‼️ SGP40 appears stuck at VOC=1. Requesting reboot...");
                 REBOOT_SIGNAL.signal(RebootReason::Sgp40StuckAtOne)
             }
 
-            if last_send_time.elapsed() >= send_interval && NETWORK_CHANNEL.try_send(data).is_ok() {
-                last_send_time = Instant::now();
+            data.alert = !matches!(settings.send_mode, SendMode::Paused)
+                && voc_alert.check(last_sent.as_ref(), &data);
+            if data.alert && !allow_out_of_band_send() {
+                warn!(
+                    "🚨 VOC jumped to {:?}, but ALERT_RATE_LIMIT_BURST is used up, left to the schedule",
+                    data.voc
+                );
+                data.alert = false;
+            }
+            if data.alert {
+                warn!("🚨 VOC jumped to {:?}, sending out of band", data.voc);
+            }
+
+            #[cfg(any(feature = "http", feature = "mqtt"))]
+            let forced = SEND_REQUESTED.swap(false, Ordering::Relaxed);
+            #[cfg(any(feature = "http", feature = "mqtt"))]
+            let (now_ms, reading) = (Instant::now().as_millis(), data.reading());
+
+            #[cfg(feature = "http")]
+            let http_sending = is_sending_enabled();
+            #[cfg(not(feature = "http"))]
+            let http_sending = false;
+
+            // Each transport keeps its own cadence; an alert goes out on all of them
+            #[cfg(feature = "http")]
+            let http_due = http_sending
+                && (forced
+                    || http_schedule.is_due(
+                        settings.send_mode.into(),
+                        send_interval_ms,
+                        jitter.as_millis(),
+                        now_ms,
+                        &reading,
+                        data.alert,
+                    ));
+            #[cfg(not(feature = "http"))]
+            let http_due = false;
+            #[cfg(feature = "mqtt")]
+            let mqtt_due = is_mqtt_enabled()
+                && (forced
+                    || mqtt_schedule.is_due(
+                        settings.send_mode.into(),
+                        MQTT_SEND_INTERVAL_MS.unwrap_or(send_interval_ms),
+                        jitter.as_millis(),
+                        now_ms,
+                        &reading,
+                        data.alert,
+                    ));
+            #[cfg(not(feature = "mqtt"))]
+            let mqtt_due = false;
+
+            if http_due || mqtt_due {
+                if !data.time_synced && matches!(unsynced_policy(), UnsyncedPolicy::Drop) {
+                    info!("🕒 Time not synced yet, sample dropped (UNSYNCED_POLICY=drop)");
+                    // Restart the intervals, so this logs once per interval instead of every read
+                    #[cfg(feature = "http")]
+                    if http_due {
+                        http_schedule.restart(now_ms);
+                    }
+                    #[cfg(feature = "mqtt")]
+                    if mqtt_due {
+                        mqtt_schedule.restart(now_ms);
+                    }
+                } else {
+                    data.interval_stats = station.interval_stats();
+                    // Each sink numbers its own samples, so its `seq` has no gaps from the other's
+                    #[cfg(feature = "mqtt")]
+                    let mqtt_queued = mqtt_due
+                        && queue_sample(
+                            Sink::Mqtt,
+                            WeatherData {
+                                seq: mqtt_schedule.seq(),
+                                ..data.clone()
+                            },
+                        );
+                    #[cfg(not(feature = "mqtt"))]
+                    let mqtt_queued = false;
+                    #[cfg(feature = "http")]
+                    let http_queued = http_due
+                        && queue_sample(
+                            Sink::Http,
+                            WeatherData {
+                                seq: http_schedule.seq(),
+                                ..data.clone()
+                            },
+                        );
+                    #[cfg(not(feature = "http"))]
+                    let http_queued = false;
+
+                    #[cfg(feature = "mqtt")]
+                    if mqtt_queued {
+                        mqtt_schedule.mark_sent(now_ms, reading);
+                    }
+                    #[cfg(feature = "http")]
+                    if http_queued {
+                        http_schedule.mark_sent(now_ms, reading);
+                    }
+
+                    let interval_done = match interval_owner(http_sending) {
+                        Sink::Http => http_queued,
+                        Sink::Mqtt => mqtt_queued,
+                    };
+                    if interval_done {
+                        station.reset_interval_accumulators();
+                    }
+                    if mqtt_queued || http_queued {
+                        last_sent = Some(data);
+                        #[cfg(any(feature = "http", feature = "mqtt"))]
+                        {
+                            jitter = if SEND_JITTER_EVERY_INTERVAL {
+                                send_jitter.next_delay()
+                            } else {
+                                Duration::from_millis(0)
+                            };
+                        }
+                    }
+                }
+            }
+        } else {
+            read_delay.on_failure();
+            // A BME280 missing since boot is no data, not failing reads: no local alarm for it
+            if station.bme280_available() {
+                CONSECUTIVE_READ_FAILURES.fetch_add(1, Ordering::Relaxed);
             }
         }
-        Timer::after_millis(EXECUTION_DELAY_MS).await;
+
+        if let Some(sensor) = station.lost_sensor() {
+            warn!("‼️ {} is gone from the bus. Requesting reboot...", sensor);
+            REBOOT_SIGNAL.signal(RebootReason::SensorLost);
+        }
+        Timer::after_millis(read_delay.current()).await;
     }
 }
 
-/// The Http Client resets on every HTTP call to prevent ESP_FAIL 'connection is not in the initial phase'
-/// It is a known quirk of the esp-idf-svc HTTP client.
-/// This resets the internal state machine and clears any "poisoned" sockets.
-/// When we continue the worker loop, the client variable goes out of the scope.
-/// Its Drop implementation is called, which internally tells the ESP-IDF to close the socket and free the memory.
+/// Debounced VOC jump detection for out-of-band alerts: trips once the VOC index has stayed more
+/// than `VOC_ALERT_DELTA` away from the last sent sample for `VOC_ALERT_CONFIRM_READINGS` readings
+/// in a row, at most once per `VOC_ALERT_COOLDOWN_MS`.
+#[derive(Default)]
+struct VocAlert {
+    consecutive: u8,
+    last_alert: Option<Instant>,
+}
+
+impl VocAlert {
+    fn check(&mut self, last_sent: Option<&WeatherData>, data: &WeatherData) -> bool {
+        let Some(delta) = VOC_ALERT_DELTA else {
+            return false;
+        };
+
+        let jumped = matches!(
+            (last_sent.and_then(|last| last.voc), data.voc),
+            (Some(last), Some(current)) if last.abs_diff(current) > delta
+        );
+        if !jumped {
+            self.consecutive = 0;
+            return false;
+        }
+
+        self.consecutive = self.consecutive.saturating_add(1);
+        let cooled_down = self.last_alert.is_none_or(|alerted| {
+            alerted.elapsed() >= Duration::from_millis(VOC_ALERT_COOLDOWN_MS)
+        });
+
+        if self.consecutive >= VOC_ALERT_CONFIRM_READINGS && cooled_down {
+            self.consecutive = 0;
+            self.last_alert = Some(Instant::now());
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Deterministic xorshift32 sequence of delays in `0..=window_ms`; the seed makes it per-device.
+#[cfg(any(feature = "http", feature = "mqtt"))]
+pub(crate) struct SendJitter {
+    state: u32,
+    window_ms: u64,
+}
+
+#[cfg(any(feature = "http", feature = "mqtt"))]
+impl SendJitter {
+    /// `seed` must be non-zero (xorshift would stay at zero forever).
+    pub(crate) fn new(window_ms: u64, seed: u32) -> Self {
+        Self {
+            state: seed.max(1),
+            window_ms,
+        }
+    }
+
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        if self.window_ms == 0 {
+            return Duration::from_millis(0);
+        }
+
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        Duration::from_millis(u64::from(x) % (self.window_ms + 1))
+    }
+}
+
+/// Adds a callback that receives every new reading, e.g. to drive an LED or a relay, without
+/// touching `sensor_task`. Register before the sensor task is spawned to not miss any reading.
+///
+/// Observers run on the sensor task, in registration order, and must return quickly. At most
+/// `MAX_OBSERVERS`; one past that is logged and left out.
+pub(crate) fn register_observer(observer: Observer) {
+    let registered = REGISTRY.lock(|registry| {
+        let mut updated = registry.get();
+        let slot = updated.observers.iter_mut().find(|slot| slot.is_none());
+        let registered = slot.map(|slot| *slot = Some(observer)).is_some();
+        registry.set(updated);
+        registered
+    });
+
+    if !registered {
+        warn!("‼️ More than {} observers, one is left out", MAX_OBSERVERS);
+    }
+}
+
+/// Sets the queue that `sink`'s due samples are handed to, replacing the built-in channel of its
+/// task (`queue_for_http`, `queue_for_mqtt`). A sink without a queue drops its samples.
+#[cfg(any(feature = "http", feature = "mqtt"))]
+pub(crate) fn register_sink(sink: Sink, queue: SinkQueue) {
+    REGISTRY.lock(|registry| {
+        let mut updated = registry.get();
+        match sink {
+            #[cfg(feature = "http")]
+            Sink::Http => updated.http = Some(queue),
+            #[cfg(feature = "mqtt")]
+            Sink::Mqtt => updated.mqtt = Some(queue),
+            #[allow(unreachable_patterns)]
+            _ => {}
+        }
+        registry.set(updated);
+    });
+}
+
+fn notify_observers(data: &WeatherData) {
+    let observers = REGISTRY.lock(Cell::get).observers;
+    for observer in observers.into_iter().flatten() {
+        observer(data);
+    }
+}
+
+#[cfg(any(feature = "http", feature = "mqtt"))]
+fn queue_sample(sink: Sink, data: WeatherData) -> bool {
+    let registry = REGISTRY.lock(Cell::get);
+    let queue = match sink {
+        #[cfg(feature = "http")]
+        Sink::Http => registry.http,
+        #[cfg(feature = "mqtt")]
+        Sink::Mqtt => registry.mqtt,
+        #[allow(unreachable_patterns)]
+        _ => None,
+    };
+    queue.is_some_and(|queue| queue(data))
+}
+
+/// `network_task`'s queue, the `Sink::Http` default.
+#[cfg(feature = "http")]
+pub(crate) fn queue_for_http(data: WeatherData) -> bool {
+    send_with_policy(&NETWORK_CHANNEL, data)
+}
+
+/// `mqtt_task`'s queue, the `Sink::Mqtt` default.
+#[cfg(feature = "mqtt")]
+pub(crate) fn queue_for_mqtt(data: WeatherData) -> bool {
+    send_with_policy(&MQTT_CHANNEL, data)
+}
+
+/// The `*_CHANGE_DELTA`s the on-change send mode compares against, also the unit the adaptive
+/// send interval measures the rate of change in.
+const CHANGE_DELTAS: ChangeDeltas = ChangeDeltas {
+    temperature: TEMPERATURE_CHANGE_DELTA,
+    humidity: HUMIDITY_CHANGE_DELTA,
+    pressure: PRESSURE_CHANGE_DELTA,
+    voc: VOC_CHANGE_DELTA,
+};
+
+impl From<SendMode> for SendPolicy {
+    fn from(mode: SendMode) -> Self {
+        match mode {
+            SendMode::Interval => SendPolicy::Interval,
+            SendMode::OnChange => SendPolicy::OnChange {
+                heartbeat_ms: SEND_ON_CHANGE_HEARTBEAT_MS,
+                deltas: CHANGE_DELTAS,
+            },
+            SendMode::Paused => SendPolicy::Paused,
+        }
+    }
+}
+
+/// Queues a sample without blocking, applying the configured `ChannelFullPolicy` when the
+/// consumer is behind. Returns whether `data` ended up in the channel.
+#[cfg(any(feature = "http", feature = "mqtt"))]
+fn send_with_policy(channel: &SampleChannel, data: WeatherData) -> bool {
+    let pushed = push_with_policy(
+        data,
+        channel_full_policy(),
+        |data| {
+            channel
+                .try_send(data)
+                .map_err(|TrySendError::Full(data)| data)
+        },
+        || channel.try_receive().ok(),
+    );
+
+    if pushed != Pushed::Queued {
+        DROPPED_SAMPLES.fetch_add(1, Ordering::Relaxed);
+        WINDOW_DROPS_CHANNEL_FULL.fetch_add(1, Ordering::Relaxed);
+    }
+    pushed != Pushed::Dropped
+}
+
+/// The Http Client is reused for `HTTP_KEEPALIVE_MS` and then rebuilt, so the connection lifecycle
+/// is predictable; with the default of `0` it is rebuilt for every request. ESP-IDF keeps the
+/// socket open between requests on the same client, which saves the TLS handshake within the
+/// window.
+///
+/// Any transport error drops the client early regardless of the window, to prevent ESP_FAIL
+/// 'connection is not in the initial phase', a known quirk of the esp-idf-svc HTTP client.
+/// This resets the internal state machine and clears any "poisoned" sockets: the Drop
+/// implementation tells the ESP-IDF to close the socket and free the memory.
+///
+/// After `NETWORK_MAX_CONSECUTIVE_FAILURES` failed deliveries in a row it stands by for
+/// `NETWORK_STANDBY_MS` before resuming; any success resets the count.
+///
+/// Samples that could not be delivered (transport errors, 429, 5xx) stay in an offline buffer of
+/// `OFFLINE_BUFFER_CAPACITY` samples (oldest dropped first) and are sent as one streamed JSON array
+/// with the next request. Samples rejected with other statuses are dropped, and so are buffered
+/// samples older than `MAX_PAYLOAD_AGE_MS` before they are sent.
+///
+/// During the configured quiet hours no requests are made; samples are dropped or held per
+/// `quiet_hours_policy()`.
+///
+/// With `DELIVERY_VERIFY_URL` set, every successful post is followed by a status GET that checks
+/// the server actually stored the newest sample; a mismatch is only logged.
+#[cfg(feature = "http")]
 #[embassy_executor::task]
 pub(crate) async fn network_task() {
     if !is_sending_enabled() {
@@ -93,12 +623,61 @@ pub(crate) async fn network_task() {
         return;
     }
 
+    if let Err(e) = signing::validate_config() {
+        error!("{}", e);
+        return;
+    }
+
     wait_time_sync_grace_period().await;
 
-    info!("📡 Network Task: Ready and using a new connection per request.");
+    if HTTP_KEEPALIVE_MS == 0 {
+        info!("📡 Network Task: Ready and using a new connection per request.");
+    } else {
+        info!(
+            "📡 Network Task: Ready and reusing connections for {}s.",
+            HTTP_KEEPALIVE_MS / 1000
+        );
+    }
+
+    let mut pending: VecDeque<WeatherData> = VecDeque::with_capacity(OFFLINE_BUFFER_CAPACITY);
+    let mut consecutive_failures = 0;
+    let mut connection: Option<(HttpClient, Instant)> = None;
 
     loop {
-        let mut client = match HttpClient::new() {
+        if pending.is_empty() {
+            let data = NETWORK_CHANNEL.receive().await;
+            buffer_sample(&mut pending, data);
+        }
+
+        // Pick up whatever else queued up in the meantime
+        while let Ok(data) = NETWORK_CHANNEL.try_receive() {
+            buffer_sample(&mut pending, data);
+        }
+
+        if is_quiet_hours_now() {
+            match quiet_hours_policy() {
+                QuietHoursPolicy::Drop => {
+                    info!(
+                        "🌙 Network: Quiet hours, {} sample(s) dropped.",
+                        pending.len()
+                    );
+                    WINDOW_DROPS_QUIET_HOURS.fetch_add(pending.len() as u32, Ordering::Relaxed);
+                    pending.clear();
+                    continue;
+                }
+                QuietHoursPolicy::Hold => {
+                    info!("🌙 Network: Quiet hours, holding samples until the window ends.");
+                    wait_quiet_hours_end().await;
+                }
+            }
+        }
+
+        drop_stale_samples(&mut pending);
+        if pending.is_empty() {
+            continue;
+        }
+
+        let client = match reusable_client(&mut connection) {
             Ok(c) => c,
             Err(e) => {
                 warn!("‼️ Network Task: Could not init HTTP client: {:?}", e);
@@ -107,29 +686,645 @@ pub(crate) async fn network_task() {
             }
         };
 
-        let data = NETWORK_CHANNEL.receive().await;
+        match deliver_pending(client, &mut pending, &mut consecutive_failures).await {
+            Delivery::Accepted(Some(sent_timestamp)) if !DELIVERY_VERIFY_URL.is_empty() => {
+                match client.verify_delivery(DELIVERY_VERIFY_URL, sent_timestamp) {
+                    Ok(true) => info!("📡 Network: Delivery verified"),
+                    Ok(false) => warn!(
+                        "‼️📡 Network: Posted, but the server reports no sample at {} yet",
+                        sent_timestamp
+                    ),
+                    Err(e) => {
+                        warn!("‼️📡 Network: Delivery verification failed: {:?}", e);
+                        connection = None;
+                    }
+                }
+            }
+            Delivery::Failed => connection = None,
+            Delivery::Accepted(_) | Delivery::Retained | Delivery::Dropped => {}
+        }
+    }
+}
+
+/// What one delivery attempt did with the buffered samples.
+#[cfg(feature = "http")]
+enum Delivery {
+    /// Accepted and cleared; carries the newest sample's timestamp for `DELIVERY_VERIFY_URL`.
+    Accepted(Option<i64>),
+    /// Kept for the next attempt (429 or 5xx).
+    Retained,
+    /// Rejected by the server and dropped, retrying wouldn't change the answer.
+    Dropped,
+    /// The request itself failed, so the connection should be rebuilt.
+    Failed,
+}
+
+/// Posts the buffered samples via `smog_core::delivery::deliver` (2xx clears the buffer, 429 and
+/// 5xx keep it, any other status drops it) and adds the side effects: counters, logging and the
+/// backoff before the next attempt.
+#[cfg(feature = "http")]
+async fn deliver_pending(
+    client: &mut HttpClient,
+    pending: &mut VecDeque<WeatherData>,
+    consecutive_failures: &mut u32,
+) -> Delivery {
+    let newest_timestamp = pending.back().map(|data| data.timestamp_unix_s);
+    let started = Instant::now();
+    let Some(outcome) = delivery::deliver(client, pending) else {
+        return Delivery::Retained;
+    };
+    // Only requests that got an answer: a timeout says nothing about the server's speed
+    if !matches!(outcome, Outcome::Failed(_)) {
+        record_post_latency(started.elapsed().as_millis());
+    }
+
+    let status = match outcome {
+        Outcome::Accepted { status, .. }
+        | Outcome::Rejected { status, .. }
+        | Outcome::ServerError { status } => status as u32,
+        Outcome::RateLimited => 429,
+        Outcome::Failed(_) => POST_STATUS_ERROR,
+    };
+    LAST_POST_STATUS.store(status, Ordering::Relaxed);
+
+    match outcome {
+        Outcome::Accepted { status, count } => {
+            info!("📡 Network: {} sample(s) posted (Status {})", count, status);
+            LAST_POST_SUCCESS_UPTIME_S.store(uptime_s(), Ordering::Relaxed);
+            SENT_SAMPLES.fetch_add(count as u32, Ordering::Relaxed);
+            *consecutive_failures = 0;
+            Delivery::Accepted(newest_timestamp)
+        }
+        Outcome::RateLimited => {
+            warn!("📡 Network: Rate limited (429). Cooling down...");
+            Timer::after_secs(5).await;
+            Delivery::Retained
+        }
+        Outcome::ServerError { status } => {
+            error!(
+                "📡 Network: Server error (Status {}), keeping {} sample(s) buffered",
+                status,
+                pending.len()
+            );
+            Timer::after_secs(2).await;
+            standby_after_repeated_failures(consecutive_failures).await;
+            Delivery::Retained
+        }
+        Outcome::Rejected { status, count } => {
+            error!(
+                "📡 Network: Rejected (Status {}), dropping {} sample(s)",
+                status, count
+            );
+            WINDOW_DROPS_REJECTED.fetch_add(count as u32, Ordering::Relaxed);
+            Delivery::Dropped
+        }
+        Outcome::Failed(error) => {
+            error!(
+                "📡‼️ Network: Request failed: {:?}. Resetting http client...",
+                error
+            );
+            Timer::after_secs(2).await;
+            standby_after_repeated_failures(consecutive_failures).await;
+            Delivery::Failed
+        }
+    }
+}
+
+/// The open client, rebuilt first if its `HTTP_KEEPALIVE_MS` reuse window has lapsed or it was
+/// left mid-response (`HttpClient::is_reusable`).
+#[cfg(feature = "http")]
+fn reusable_client(
+    connection: &mut Option<(HttpClient, Instant)>,
+) -> anyhow::Result<&mut HttpClient> {
+    let window = Duration::from_millis(HTTP_KEEPALIVE_MS);
+    if connection
+        .as_ref()
+        .is_some_and(|(client, opened)| opened.elapsed() >= window || !client.is_reusable())
+    {
+        // Drop the old socket before opening a new one, both TLS contexts won't fit in the heap
+        *connection = None;
+    }
+
+    let (client, opened) = match connection.take() {
+        Some(open) => open,
+        None => (new_http_client()?, Instant::now()),
+    };
+    Ok(&mut connection.insert((client, opened)).0)
+}
+
+/// `HttpClient::new`, refused up front while the free heap is below
+/// `HTTP_CLIENT_MIN_FREE_HEAP_BYTES`: the caller's retry then waits for memory to come back
+/// instead of the allocation failing halfway through the TLS setup.
+#[cfg(feature = "http")]
+fn new_http_client() -> anyhow::Result<HttpClient> {
+    let free = free_heap();
+    if let Some(floor) = HTTP_CLIENT_MIN_FREE_HEAP_BYTES
+        && free < floor
+    {
+        anyhow::bail!(
+            "‼️ Low heap: {}B free, below HTTP_CLIENT_MIN_FREE_HEAP_BYTES ({}B), skipped",
+            free,
+            floor
+        );
+    }
+    HttpClient::new()
+}
+
+/// Counts a failed delivery; after `NETWORK_MAX_CONSECUTIVE_FAILURES` in a row the network task
+/// goes dormant for `NETWORK_STANDBY_MS` instead of retrying every few seconds, sparing power and
+/// the server while the endpoint is down.
+#[cfg(feature = "http")]
+async fn standby_after_repeated_failures(consecutive_failures: &mut u32) {
+    *consecutive_failures += 1;
+
+    if *consecutive_failures >= NETWORK_MAX_CONSECUTIVE_FAILURES {
+        warn!(
+            "💤 Network: {} consecutive failures, standing by for {}s",
+            consecutive_failures,
+            NETWORK_STANDBY_MS / 1000
+        );
+        Timer::after_millis(NETWORK_STANDBY_MS).await;
+        *consecutive_failures = 0;
+        info!("📡 Network: Resuming after standby");
+    }
+}
+
+/// Appends to the offline buffer, evicting the oldest sample once it is full.
+#[cfg(feature = "http")]
+fn buffer_sample(pending: &mut VecDeque<WeatherData>, data: WeatherData) {
+    if delivery::buffer(pending, data, OFFLINE_BUFFER_CAPACITY) {
+        DROPPED_SAMPLES.fetch_add(1, Ordering::Relaxed);
+        WINDOW_DROPS_BUFFER_FULL.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Drops buffered samples older than `MAX_PAYLOAD_AGE_MS`, so a long outage doesn't replay
+/// hours-old readings. Needs a synced clock both now and at capture; other samples are kept.
+#[cfg(feature = "http")]
+fn drop_stale_samples(pending: &mut VecDeque<WeatherData>) {
+    if MAX_PAYLOAD_AGE_MS == 0 || !is_time_synced() {
+        return;
+    }
+
+    let oldest_allowed = timestamp_unix_s() - (MAX_PAYLOAD_AGE_MS / 1000) as i64;
+    let before = pending.len();
+    pending.retain(|data| !data.time_synced || data.timestamp_unix_s >= oldest_allowed);
+
+    let dropped = before - pending.len();
+    WINDOW_DROPS_STALE.fetch_add(dropped as u32, Ordering::Relaxed);
+    if dropped > 0 {
+        warn!(
+            "📡 Network: Dropped {} stale sample(s) older than {}s",
+            dropped,
+            MAX_PAYLOAD_AGE_MS / 1000
+        );
+    }
+}
+
+/// Network silence watchdog.
+///
+/// Catch-all recovery for stuck network stacks: Wi-Fi may report "connected" while no data flows.
+/// If no POST has succeeded within `MAX_SILENCE_MS`, it requests a reboot via `REBOOT_SIGNAL`
+/// with `RebootReason::NetworkSilence`. Only armed when HTTP sending is enabled; quiet hours
+/// pause the silence clock since no requests are expected then.
+#[cfg(feature = "http")]
+#[embassy_executor::task]
+pub(crate) async fn network_silence_watchdog_task() {
+    if !is_sending_enabled() {
+        return;
+    }
+
+    let max_silence_s = (MAX_SILENCE_MS / 1000) as u32;
 
-        match client.post_data(HTTP_CONSUMER_ENDPOINT_URL, &data) {
-            Ok(status) if status == 200 || status == 201 => {
-                info!("📡 Network: Data posted (Status {})", status);
+    loop {
+        Timer::after_secs(60).await;
+
+        let now_s = uptime_s();
+
+        if is_quiet_hours_now() {
+            LAST_POST_SUCCESS_UPTIME_S.store(now_s, Ordering::Relaxed);
+            continue;
+        }
+
+        let silence_s = now_s.saturating_sub(LAST_POST_SUCCESS_UPTIME_S.load(Ordering::Relaxed));
+
+        if silence_s >= max_silence_s {
+            warn!(
+                "‼️📡 No successful POST for {}s. Requesting reboot...",
+                silence_s
+            );
+            REBOOT_SIGNAL.signal(RebootReason::NetworkSilence);
+            return;
+        }
+    }
+}
+
+fn free_heap() -> u32 {
+    unsafe { esp_get_free_heap_size() }
+}
+
+/// Whether the free heap is below `HTTP_CLIENT_MIN_FREE_HEAP_BYTES`, flagged in the health report.
+fn is_heap_low() -> bool {
+    HTTP_CLIENT_MIN_FREE_HEAP_BYTES.is_some_and(|floor| free_heap() < floor)
+}
+
+/// Periodic one-line health summary, easy to grep for in long serial logs.
+#[embassy_executor::task]
+pub(crate) async fn health_report_task() {
+    loop {
+        Timer::after_millis(HEALTH_REPORT_INTERVAL_MS).await;
+        info!("{}", health_line());
+    }
+}
+
+/// One line of device health, for the periodic report and the serial console's `status`.
+pub(crate) fn health_line() -> String {
+    let last_post = match LAST_POST_STATUS.load(Ordering::Relaxed) {
+        POST_STATUS_NONE => "-".to_string(),
+        POST_STATUS_ERROR => "error".to_string(),
+        status => status.to_string(),
+    };
+
+    let wifi = match link_info() {
+        Some(link) => format!(
+            "up(ch{} rssi={}dBm bssid={} auth={})",
+            link.channel,
+            link.rssi_dbm,
+            link.bssid_string(),
+            link.auth_mode
+        ),
+        None => "down".to_string(),
+    };
+
+    #[cfg(feature = "http")]
+    let post_latency = post_latency_summary();
+    #[cfg(not(feature = "http"))]
+    let post_latency = "-";
+
+    format!(
+        "🩺 Health: uptime={}s heap_free={}B{} wifi={} time_synced={} last_post={} post_latency={} sent={} dropped={}",
+        uptime_s(),
+        free_heap(),
+        if is_heap_low() { "(low)" } else { "" },
+        wifi,
+        is_time_synced(),
+        last_post,
+        post_latency,
+        SENT_SAMPLES.load(Ordering::Relaxed),
+        DROPPED_SAMPLES.load(Ordering::Relaxed),
+    )
+}
+
+/// Sample-loss summary: every `DROP_SUMMARY_INTERVAL_MS`, logs how many samples were lost in that
+/// window and why, then starts a new window. Sits between logging every drop and only the running
+/// total in the health report:
+///
+/// - `channel_full`: a transport channel was full (`CHANNEL_FULL_POLICY`)
+/// - `buffer_full`: evicted from the full offline buffer during an outage
+/// - `rejected`: the server answered with a non-retryable status
+/// - `stale`: older than `MAX_PAYLOAD_AGE_MS` by the time it could be sent
+/// - `quiet_hours`: discarded under `QUIET_HOURS_POLICY=drop`
+#[embassy_executor::task]
+pub(crate) async fn drop_summary_task() {
+    if DROP_SUMMARY_INTERVAL_MS == 0 {
+        info!("📉 Drop Summary Task: Disabled via config. Standing by.");
+        return;
+    }
+
+    loop {
+        Timer::after_millis(DROP_SUMMARY_INTERVAL_MS).await;
+
+        let counts = [
+            ("channel_full", &WINDOW_DROPS_CHANNEL_FULL),
+            ("buffer_full", &WINDOW_DROPS_BUFFER_FULL),
+            ("rejected", &WINDOW_DROPS_REJECTED),
+            ("stale", &WINDOW_DROPS_STALE),
+            ("quiet_hours", &WINDOW_DROPS_QUIET_HOURS),
+        ]
+        .map(|(cause, counter)| (cause, counter.swap(0, Ordering::Relaxed)));
+
+        let total: u32 = counts.iter().map(|(_, count)| count).sum();
+        if total == 0 {
+            continue;
+        }
+
+        let causes: Vec<String> = counts
+            .iter()
+            .map(|(cause, count)| format!("{}={}", cause, count))
+            .collect();
+        warn!(
+            "📉 Dropped {} sample(s) in the last {}s: {}",
+            total,
+            DROP_SUMMARY_INTERVAL_MS / 1000,
+            causes.join(" ")
+        );
+    }
+}
+
+/// Polls `REMOTE_CONFIG_URL` for a settings patch every `REMOTE_CONFIG_POLL_INTERVAL_MS`.
+///
+/// Requests carry the ETag of the applied config, so an unchanged config costs a bodyless
+/// `304 Not Modified` and is neither downloaded nor reparsed.
+#[cfg(feature = "http")]
+#[embassy_executor::task]
+pub(crate) async fn remote_config_task() {
+    if REMOTE_CONFIG_URL.is_empty() {
+        info!("⚙️ Remote Config Task: Disabled via config. Standing by.");
+        return;
+    }
+
+    // No wait for the time sync: the fetch doesn't need it, and `TIME_SYNCED_SIGNAL` has room
+    // for only one waiter, the network task
+    loop {
+        if let Err(e) = poll_remote_config() {
+            warn!("⚙️ Remote config: fetch failed: {:?}", e);
+        }
+        Timer::after_millis(REMOTE_CONFIG_POLL_INTERVAL_MS).await;
+    }
+}
+
+#[cfg(feature = "http")]
+fn poll_remote_config() -> anyhow::Result<()> {
+    let etag = settings::stored_config_etag();
+    let mut client = new_http_client()?;
+
+    match client.fetch_config(REMOTE_CONFIG_URL, etag.as_deref())? {
+        ConfigResponse::NotModified => info!("⚙️ Remote config: unchanged (304)"),
+        ConfigResponse::Updated { body, etag } => {
+            settings::apply_remote(&body, etag.as_deref())?;
+        }
+        ConfigResponse::Failed(status) => {
+            warn!("⚙️ Remote config: server answered Status {}", status)
+        }
+    }
+
+    Ok(())
+}
+
+/// Drives the status LED from the same state the health report shows.
+#[embassy_executor::task]
+pub(crate) async fn led_task(mut led: StatusLed) {
+    loop {
+        let last_post_failed = match LAST_POST_STATUS.load(Ordering::Relaxed) {
+            POST_STATUS_NONE => false,
+            POST_STATUS_ERROR => true,
+            status => status >= 400,
+        };
+
+        let pattern = if is_quiet_hours_now() {
+            LedPattern::Off
+        } else if !is_wifi_connected() || last_post_failed {
+            LedPattern::FastBlink
+        } else {
+            LedPattern::SlowBlink
+        };
+
+        let (on_ms, off_ms) = pattern.timing_ms();
+        if on_ms > 0 {
+            led.set(true);
+            Timer::after_millis(on_ms).await;
+        }
+        led.set(false);
+        Timer::after_millis(off_ms).await;
+    }
+}
+
+/// Counts the pulses of one wind/rain reed switch, see `ReedSwitch::run`.
+#[embassy_executor::task(pool_size = 2)]
+pub(crate) async fn reed_switch_task(reed_switch: ReedSwitch) {
+    reed_switch.run().await;
+}
+
+/// Beeps (or blinks) the local alarm once a second while WiFi has been down for
+/// `LOCAL_ALERT_OFFLINE_MS` or the sensors keep failing, and silences it on recovery.
+#[embassy_executor::task]
+pub(crate) async fn alarm_task(mut alarm: Alarm) {
+    let offline_limit = Duration::from_millis(LOCAL_ALERT_OFFLINE_MS);
+    let mut offline_since: Option<Instant> = None;
+    let mut sounding = false;
+
+    loop {
+        offline_since = if is_wifi_connected() {
+            None
+        } else {
+            offline_since.or_else(|| Some(Instant::now()))
+        };
+        let offline = offline_since.is_some_and(|since| since.elapsed() >= offline_limit);
+        let sensor_failing =
+            CONSECUTIVE_READ_FAILURES.load(Ordering::Relaxed) >= LOCAL_ALERT_SENSOR_FAILURES;
+
+        if (offline || sensor_failing) != sounding {
+            sounding = offline || sensor_failing;
+            if sounding {
+                warn!(
+                    "🔔 Local alarm on: {}",
+                    if offline {
+                        "WiFi down too long"
+                    } else {
+                        "sensor reads failing"
+                    }
+                );
+            } else {
+                info!("🔕 Local alarm off, recovered");
             }
-            Ok(429) => {
-                warn!("📡 Network: Rate limited (429). Cooling down...");
-                Timer::after_secs(5).await;
+        }
+
+        if sounding {
+            alarm.set(true);
+            Timer::after_millis(200).await;
+            alarm.set(false);
+            Timer::after_millis(800).await;
+        } else {
+            Timer::after_secs(1).await;
+        }
+    }
+}
+
+/// Redraws the OLED whenever a new reading comes in.
+#[embassy_executor::task]
+pub(crate) async fn display_task(mut display: Display) {
+    loop {
+        let data = display::next_reading().await;
+
+        if let Err(e) = display.render(&data, is_wifi_connected(), is_time_synced()) {
+            warn!("⚠️ Display update failed: {:?}", e);
+        }
+    }
+}
+
+/// Sends the alerts raised by `telegram::check_alert`, one fresh connection each: they are rare,
+/// so keeping a TLS session open for them isn't worth the heap.
+#[cfg(feature = "http")]
+#[embassy_executor::task]
+pub(crate) async fn telegram_task() {
+    loop {
+        let text = telegram::next_alert().await;
+
+        let result =
+            new_http_client().and_then(|mut client| telegram::send_message(&mut client, &text));
+        match result {
+            Ok(status) if (200..300).contains(&status) => info!("✉️ Telegram alert sent"),
+            Ok(status) => warn!("‼️✉️ Telegram rejected the alert (Status {})", status),
+            Err(e) => warn!("‼️✉️ Telegram alert failed: {:?}", e),
+        }
+    }
+}
+
+/// Reading observer feeding the daily summary.
+pub(crate) fn record_daily_summary(data: &WeatherData) {
+    DAILY_SUMMARY.lock(|summary| {
+        let mut today = summary.get();
+        today.update(data);
+        summary.set(today);
+    });
+}
+
+/// Logs the daily min/max and starts a new day at every local midnight (DST-aware, see
+/// `until_next_local_midnight`). Waits for a synced clock, as the local date is bogus before that.
+#[embassy_executor::task]
+pub(crate) async fn daily_rollover_task() {
+    loop {
+        if !is_time_synced() {
+            Timer::after_secs(60).await;
+            continue;
+        }
+
+        wait_local_midnight().await;
+
+        let day = DAILY_SUMMARY.lock(|summary| summary.replace(DailySummary::EMPTY));
+        info!(
+            "📅 Daily summary {}: samples={} temperature={}°C humidity={}% pressure={}hPa",
+            local_yesterday(),
+            day.samples,
+            day.temperature,
+            day.humidity,
+            day.pressure
+        );
+    }
+}
+
+fn uptime_s() -> u32 {
+    Instant::now().as_secs() as u32
+}
+
+/// MQTT publisher.
+///
+/// Announces the sensors via Home Assistant discovery once on boot, then publishes every
+/// sample received on `MQTT_CHANNEL` to the device state topic.
+/// After every (re)connect it publishes the `online` birth message; the broker takes care of
+/// the `offline` Last Will when the connection drops.
+/// The ESP-IDF MQTT client reconnects on its own, so unlike `network_task` it is created only once.
+#[cfg(feature = "mqtt")]
+#[embassy_executor::task]
+pub(crate) async fn mqtt_task() {
+    if !is_mqtt_enabled() {
+        info!("📨 MQTT Task: Disabled via config. Standing by.");
+        return;
+    }
+
+    let mut client = match MqttClient::new() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("‼️ MQTT Task: Could not init MQTT client: {:?}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = client.publish_discovery() {
+        warn!("📨 MQTT: Failed to publish discovery config: {:?}", e);
+    }
+
+    loop {
+        match select::select(MQTT_CONNECTED_SIGNAL.wait(), MQTT_CHANNEL.receive()).await {
+            Either::First(()) => {
+                if let Err(e) = client.publish_online() {
+                    warn!("📨 MQTT: Failed to publish availability: {:?}", e);
+                }
             }
-            Ok(status) => error!("📡 Network: Server error (Status {})", status),
-            Err(error) => {
-                error!(
-                    "📡‼️ Network: Request failed: {:?}. Resetting http client...",
-                    error
-                );
-                Timer::after_secs(2).await;
-                continue;
+            Either::Second(data) => {
+                if let Err(e) = client.publish_state(&data) {
+                    warn!("📨 MQTT: Failed to publish state: {:?}", e);
+                }
             }
         }
     }
 }
 
+/// Writes out NVS values staged since the last flush once `NVS_MIN_FLUSH_INTERVAL_MS` has passed
+/// since it, so a burst of changes costs one flash write per value, see `nvs::stage_blob`.
+#[embassy_executor::task]
+pub(crate) async fn nvs_flush_task() {
+    if NVS_MIN_FLUSH_INTERVAL_MS == 0 {
+        info!("💾 NVS Flush Task: Staged values are written straight through. Standing by.");
+        return;
+    }
+
+    loop {
+        Timer::after(nvs::until_flush_due()).await;
+        nvs::flush_due();
+    }
+}
+
+/// Preventive restart every `SCHEDULED_REBOOT_INTERVAL_HOURS` (disabled with `0`).
+///
+/// Before rebooting it posts a `scheduled_reboot` event so the server can tell the gap apart from
+/// an outage. It doesn't wait for the network task to go idle: a sample queued or in flight at
+/// that moment is lost, which is logged.
+#[embassy_executor::task]
+pub(crate) async fn scheduled_reboot_task() {
+    if SCHEDULED_REBOOT_INTERVAL_HOURS == 0 {
+        return;
+    }
+
+    Timer::after_secs(u64::from(SCHEDULED_REBOOT_INTERVAL_HOURS) * 60 * 60).await;
+
+    info!(
+        "🔁 Scheduled reboot after {}h of uptime",
+        SCHEDULED_REBOOT_INTERVAL_HOURS
+    );
+
+    #[cfg(feature = "http")]
+    announce_scheduled_reboot();
+
+    REBOOT_SIGNAL.signal(RebootReason::Scheduled);
+}
+
+#[cfg(feature = "http")]
+fn announce_scheduled_reboot() {
+    if !is_sending_enabled() {
+        return;
+    }
+
+    let queued = NETWORK_CHANNEL.len();
+    if queued > 0 {
+        warn!(
+            "🔁 Scheduled reboot: {} queued sample(s) will be lost",
+            queued
+        );
+    }
+
+    match HttpClient::new()
+        .and_then(|mut client| client.post_event(HTTP_CONSUMER_ENDPOINT_URL, "scheduled_reboot"))
+    {
+        Ok(status) => info!("🔁 Scheduled reboot announced (Status {})", status),
+        Err(e) => warn!("🔁 Failed to announce scheduled reboot: {:?}", e),
+    }
+}
+
+/// Serial console `send`: the next reading is queued for every transport, due or not.
+#[cfg(feature = "serial-console")]
+pub(crate) fn request_send() {
+    // Without a transport there is nothing to send
+    #[cfg(any(feature = "http", feature = "mqtt"))]
+    SEND_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Serial console `reboot` (and `calibrate`), through the supervisor like every other reboot.
+#[cfg(feature = "serial-console")]
+pub(crate) fn request_console_reboot() {
+    REBOOT_SIGNAL.signal(RebootReason::Console);
+}
+
 /// Reboot supervisor.
 ///
 /// Why this task exists:
@@ -141,18 +1336,24 @@ pub(crate) async fn network_task() {
 ///
 /// Flow:
 /// 1) `sensor_task` detects "SGP40 stuck at 1" **after a warm-up window**
+///    (or `network_silence_watchdog_task` sees no successful POST for too long, a sensor stops
+///    answering past `SENSOR_LOST_AFTER_ERRORS`, the `scheduled_reboot_task` interval is up, or
+///    the serial console asks for it)
 /// 2) it signals `REBOOT_SIGNAL` with a `RebootReason`
-/// 3) this task waits for the signal, optionally delays for a log flush, and reboots the MCU
+/// 3) this task waits for the signal, writes out staged NVS values (`nvs::flush_all`), optionally
+///    delays for a log flush, and reboots the MCU
 #[embassy_executor::task]
 pub(crate) async fn reboot_supervisor_task() {
     let reason = REBOOT_SIGNAL.wait().await;
     warn!("🔁 Reboot supervisor: reboot requested: {:?}", reason);
+    nvs::flush_all();
 
     Timer::after_millis(200).await;
 
     unsafe { esp_idf_svc::sys::esp_restart() }
 }
 
+#[cfg(feature = "ntp")]
 #[embassy_executor::task]
 pub(crate) async fn ntp_watcher_task(ntp_client: EspSntp<'static>) {
     ntp_sync_watcher(ntp_client).await
@@ -0,0 +1,96 @@
+use crate::config::{
+    TELEGRAM_ALERT_COOLDOWN_MS, TELEGRAM_BOT_TOKEN, TELEGRAM_CHAT_ID, TELEGRAM_TEMPERATURE_RANGE,
+    TELEGRAM_VOC_THRESHOLD,
+};
+use crate::device::device_id;
+use crate::http_client::HttpClient;
+use crate::models::WeatherData;
+use crate::network::allow_out_of_band_send;
+use anyhow::Result;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant};
+use log::warn;
+use std::cell::Cell;
+
+const API_BASE_URL: &str = "https://api.telegram.org";
+
+/// The pending alert text, raised by the `check_alert` observer and sent by `telegram_task`, so
+/// the HTTPS request never runs inside the sensor loop. A newer alert replaces an unsent one.
+static PENDING_ALERT: Signal<CriticalSectionRawMutex, String> = Signal::new();
+static LAST_ALERT: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    Mutex::new(Cell::new(None));
+
+/// Both the bot token and the chat id are needed.
+pub(crate) fn is_enabled() -> bool {
+    !TELEGRAM_BOT_TOKEN.is_empty() && !TELEGRAM_CHAT_ID.is_empty()
+}
+
+/// Reading observer: raises an alert when a condition trips, at most once per
+/// `TELEGRAM_ALERT_COOLDOWN_MS` across all conditions.
+pub(crate) fn check_alert(data: &WeatherData) {
+    let Some(message) = alert_message(data) else {
+        return;
+    };
+
+    let cooled_down = LAST_ALERT.lock(|last| {
+        let due = last.get().is_none_or(|alerted| {
+            alerted.elapsed() >= Duration::from_millis(TELEGRAM_ALERT_COOLDOWN_MS)
+        });
+        if due {
+            last.set(Some(Instant::now()));
+        }
+        due
+    });
+
+    if !cooled_down {
+        return;
+    }
+    if allow_out_of_band_send() {
+        PENDING_ALERT.signal(message);
+    } else {
+        warn!(
+            "✉️ Telegram alert skipped, ALERT_RATE_LIMIT_BURST is used up: {}",
+            message
+        );
+    }
+}
+
+/// Waits for the next alert to send.
+pub(crate) async fn next_alert() -> String {
+    PENDING_ALERT.wait().await
+}
+
+/// Sends `text` to `TELEGRAM_CHAT_ID` through the Bot API `sendMessage` method.
+pub(crate) fn send_message(client: &mut HttpClient, text: &str) -> Result<u16> {
+    let url = format!("{}/bot{}/sendMessage", API_BASE_URL, TELEGRAM_BOT_TOKEN);
+    let payload = serde_json::to_vec(&serde_json::json!({
+        "chat_id": TELEGRAM_CHAT_ID,
+        "text": text,
+    }))?;
+
+    client.post_json(&url, &payload)
+}
+
+/// The first tripped condition as a message, e.g. `smog-a1b2c3: VOC index 312 (above 250)`.
+fn alert_message(data: &WeatherData) -> Option<String> {
+    let condition = if let Some(voc) = data.voc
+        && let Some(threshold) = TELEGRAM_VOC_THRESHOLD
+        && voc > threshold
+    {
+        format!("VOC index {} (above {})", voc, threshold)
+    } else if let Some((min, max)) = TELEGRAM_TEMPERATURE_RANGE
+        && let Some(temperature) = data.temperature
+        && !(min..=max).contains(&temperature)
+    {
+        format!(
+            "temperature {:.1} °C (outside {:.1}..{:.1})",
+            temperature, min, max
+        )
+    } else {
+        return None;
+    };
+
+    Some(format!("⚠️ {}: {}", device_id(), condition))
+}
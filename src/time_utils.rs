@@ -1,51 +1,216 @@
-use crate::config::{TIMESTAMP_PATTERN, TIMEZONE};
+use crate::config::{
+    LOG_TIMEZONE_OFFSET_PER_LINE, QUIET_HOURS_END, QUIET_HOURS_START, TIMESTAMP_PATTERN,
+};
+#[cfg(feature = "ntp")]
+use crate::config::{
+    NTP_PROBE_TIMEOUT_MS, NTP_SERVERS, NTP_SYNC_ATTEMPTS, NTP_SYNC_TIMEOUT_MS, NtpSyncMode,
+    TIMEZONE, ntp_sync_mode,
+};
+#[cfg(feature = "ntp")]
 use anyhow::Context;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Timelike, Utc};
+#[cfg(feature = "ntp")]
 use chrono_tz::Tz;
-use embassy_futures::select;
-use embassy_futures::select::Either;
+#[cfg(any(feature = "ntp", feature = "http"))]
+use embassy_futures::select::{self, Either};
+#[cfg(feature = "ntp")]
+use embassy_sync::blocking_mutex::Mutex;
+#[cfg(any(feature = "ntp", feature = "http"))]
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::once_lock::OnceLock;
+#[cfg(any(feature = "ntp", feature = "http"))]
 use embassy_sync::signal::Signal;
 use embassy_time::Timer;
-use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+#[cfg(feature = "ntp")]
+use esp_idf_svc::sntp::{EspSntp, SntpConf, SyncMode, SyncStatus};
 use esp_idf_svc::sys::esp_timer_get_time;
-use log::{info, warn};
+#[cfg(feature = "ntp")]
+use log::info;
+#[cfg(any(feature = "ntp", feature = "http"))]
+use log::warn;
+use smog_core::time::{in_quiet_hours, until_next_local_midnight};
+#[cfg(feature = "ntp")]
+use std::cell::Cell;
+#[cfg(feature = "ntp")]
+use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "ntp")]
+use std::time::{Duration, Instant};
 
+/// Only ever set by SNTP: a build without the `ntp` feature stays unsynced (`time_synced=false`).
 static TIME_SYNCED: AtomicBool = AtomicBool::new(false);
+#[cfg(any(feature = "ntp", feature = "http"))]
 static TIME_SYNCED_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
+/// Without `ntp` the clock never leaves 1970, so there is no local time worth the timezone
+/// database: everything is reported in UTC.
+#[cfg(not(feature = "ntp"))]
+type Tz = Utc;
+
+/// The zone used for log timestamps, quiet hours and the `timezone` field: `TIMEZONE` until a
+/// runtime override (`settings`) replaces it. Resolved lazily on first use.
+#[cfg(feature = "ntp")]
+static ACTIVE_TIMEZONE: Mutex<CriticalSectionRawMutex, Cell<Option<Tz>>> =
+    Mutex::new(Cell::new(None));
+/// Raised by `set_timezone`, so a wait for local midnight is recomputed in the new zone.
+#[cfg(feature = "ntp")]
+static TIMEZONE_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
 pub(crate) fn is_time_synced() -> bool {
     TIME_SYNCED.load(Ordering::Relaxed)
 }
 
+/// Starts SNTP and waits up to `NTP_SYNC_TIMEOUT_MS` for the first sync. If that times out, the
+/// client is recreated and the wait repeated, up to `NTP_SYNC_ATTEMPTS` attempts in total; after
+/// the last one boot proceeds unsynced and sync continues in the background.
+///
+/// Corrections are stepped or slewed according to `NTP_SYNC_MODE`, against the fastest of
+/// `NTP_SERVERS` when several are configured.
+#[cfg(feature = "ntp")]
 pub(crate) async fn setup_ntp() -> anyhow::Result<EspSntp<'static>> {
-    let ntp_client = EspSntp::new_default().context("‼️ Failed to init NTP")?;
-    info!("\x1b[38;5;27m ⏳ Time sync in progress...");
+    let attempts = NTP_SYNC_ATTEMPTS.max(1);
+    let server = match NTP_SERVERS {
+        [] => None,
+        [server] => Some(*server),
+        servers => Some(select_fastest_ntp(servers)),
+    };
 
-    let mut wait_cycles = 0;
-    const MAX_WAIT_CYCLES: u32 = 100;
+    for attempt in 1..=attempts {
+        let mut conf = SntpConf {
+            sync_mode: ntp_sync_mode().into(),
+            ..Default::default()
+        };
+        if let Some(server) = server {
+            conf.servers[0] = server;
+        }
+        let ntp_client = EspSntp::new(&conf).context("‼️ Failed to init NTP")?;
+        info!(
+            "\x1b[38;5;27m ⏳ Time sync in progress (attempt {}/{})...",
+            attempt, attempts
+        );
 
-    while ntp_client.get_sync_status() != SyncStatus::Completed {
-        if wait_cycles >= MAX_WAIT_CYCLES {
+        if wait_for_sync(&ntp_client).await {
+            mark_time_synced();
+            info!("\x1b[38;5;27m ⏳ Time is synchronized");
+            return Ok(ntp_client);
+        }
+
+        if attempt == attempts {
             warn!(
                 "\x1b[38;5;11m ⏳ NTP sync timed out. Proceeding with system time (sync will continue in background)."
             );
             return Ok(ntp_client);
         }
 
-        Timer::after_millis(100).await;
+        warn!(
+            "\x1b[38;5;11m ⏳ NTP sync attempt {} timed out after {}ms, retrying...",
+            attempt, NTP_SYNC_TIMEOUT_MS
+        );
+        // Only one SNTP instance may exist, so the old one is dropped before the next attempt
+        drop(ntp_client);
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+/// Sends one SNTP request to each server and picks the one with the shortest round trip, as a
+/// stand-in for the nearest. Falls back to the first server if none answers within
+/// `NTP_PROBE_TIMEOUT_MS`. Blocks for at most that long per server, once at boot.
+#[cfg(feature = "ntp")]
+pub(crate) fn select_fastest_ntp(servers: &[&'static str]) -> &'static str {
+    let fastest = servers
+        .iter()
+        .filter_map(|&server| match probe_ntp(server) {
+            Ok(latency) => {
+                info!(
+                    "⏳ NTP server {} answered in {}ms",
+                    server,
+                    latency.as_millis()
+                );
+                Some((server, latency))
+            }
+            Err(e) => {
+                warn!("⏳ NTP server {} didn't answer: {:?}", server, e);
+                None
+            }
+        })
+        .min_by_key(|&(_, latency)| latency);
 
-        wait_cycles += 1;
+    match fastest {
+        Some((server, latency)) => {
+            info!(
+                "\x1b[38;5;27m ⏳ Using NTP server {} ({}ms)",
+                server,
+                latency.as_millis()
+            );
+            server
+        }
+        None => {
+            warn!(
+                "\x1b[38;5;11m ⏳ No NTP server answered the probe, using {}",
+                servers[0]
+            );
+            servers[0]
+        }
     }
+}
+
+/// Round trip of a single SNTP client request, from sending it to a valid server reply.
+#[cfg(feature = "ntp")]
+fn probe_ntp(server: &str) -> anyhow::Result<Duration> {
+    const NTP_PACKET_LEN: usize = 48;
+    // LI 0, version 3, mode 3 (client)
+    const CLIENT_REQUEST: u8 = 0x1B;
+    const MODE_SERVER: u8 = 4;
+
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(Duration::from_millis(NTP_PROBE_TIMEOUT_MS)))?;
+    socket.connect((server, 123))?;
+
+    let mut request = [0u8; NTP_PACKET_LEN];
+    request[0] = CLIENT_REQUEST;
+    let started = Instant::now();
+    socket.send(&request)?;
+
+    let mut reply = [0u8; NTP_PACKET_LEN];
+    let len = socket.recv(&mut reply)?;
+    let latency = started.elapsed();
 
-    mark_time_synced();
+    // A stratum of 0 is a "kiss-o'-death", the server refusing service
+    if len < NTP_PACKET_LEN || reply[0] & 0x07 != MODE_SERVER || reply[1] == 0 {
+        anyhow::bail!("not a valid NTP reply");
+    }
+    Ok(latency)
+}
 
-    info!("\x1b[38;5;27m ⏳ Time is synchronized");
-    Ok(ntp_client)
+#[cfg(feature = "ntp")]
+impl From<NtpSyncMode> for SyncMode {
+    fn from(mode: NtpSyncMode) -> Self {
+        match mode {
+            NtpSyncMode::Immediate => SyncMode::Immediate,
+            NtpSyncMode::Smooth => SyncMode::Smooth,
+        }
+    }
 }
 
+#[cfg(feature = "ntp")]
+async fn wait_for_sync(ntp_client: &EspSntp<'static>) -> bool {
+    const POLL_INTERVAL_MS: u64 = 100;
+    let mut waited_ms = 0;
+
+    while ntp_client.get_sync_status() != SyncStatus::Completed {
+        if waited_ms >= NTP_SYNC_TIMEOUT_MS {
+            return false;
+        }
+
+        Timer::after_millis(POLL_INTERVAL_MS).await;
+
+        waited_ms += POLL_INTERVAL_MS;
+    }
+
+    true
+}
+
+#[cfg(feature = "ntp")]
 pub(crate) async fn ntp_sync_watcher(ntp_client: EspSntp<'static>) {
     loop {
         if ntp_client.get_sync_status() == SyncStatus::Completed {
@@ -62,6 +227,7 @@ pub(crate) async fn ntp_sync_watcher(ntp_client: EspSntp<'static>) {
     }
 }
 
+#[cfg(feature = "http")]
 pub(crate) async fn wait_time_sync_grace_period() {
     if is_time_synced() {
         return;
@@ -75,12 +241,77 @@ pub(crate) async fn wait_time_sync_grace_period() {
     }
 }
 
+/// `STRICT_BOOT`: waits up to `timeout_ms` for the first NTP sync and returns whether it happened.
+/// Polls the flag instead of waiting on `TIME_SYNCED_SIGNAL`, which the network task listens on.
+pub(crate) async fn wait_first_sync(timeout_ms: u64) -> bool {
+    const POLL_INTERVAL_MS: u64 = 500;
+    let mut waited_ms = 0;
+
+    while !is_time_synced() {
+        if waited_ms >= timeout_ms {
+            return false;
+        }
+
+        Timer::after_millis(POLL_INTERVAL_MS).await;
+
+        waited_ms += POLL_INTERVAL_MS;
+    }
+
+    true
+}
+
+/// Quiet hours are only honored once the clock is synced; before that the local time is bogus.
+pub(crate) fn is_quiet_hours_now() -> bool {
+    is_time_synced()
+        && in_quiet_hours(
+            QUIET_HOURS_START,
+            QUIET_HOURS_END,
+            get_current_time_in_timezone().hour(),
+        )
+}
+
+#[cfg(feature = "http")]
+pub(crate) async fn wait_quiet_hours_end() {
+    while is_quiet_hours_now() {
+        Timer::after_secs(60).await;
+    }
+}
+
+/// Sleeps until the next local midnight, in the zone in effect when it comes: a timezone change
+/// meanwhile restarts the wait. Only meaningful once the clock is synced.
+pub(crate) async fn wait_local_midnight() {
+    loop {
+        let wait = until_next_local_midnight(get_current_time_in_timezone());
+        // A small margin so that the wake-up lands on the new date
+        let midnight = Timer::after_millis(wait.num_milliseconds().max(0) as u64 + 1000);
+
+        #[cfg(feature = "ntp")]
+        if let Either::Second(()) = select::select(midnight, TIMEZONE_CHANGED.wait()).await {
+            continue;
+        }
+        #[cfg(not(feature = "ntp"))]
+        midnight.await;
+
+        return;
+    }
+}
+
+/// Local date of the day that just ended, for labelling the daily summary after midnight.
+pub(crate) fn local_yesterday() -> chrono::NaiveDate {
+    get_current_time_in_timezone().date_naive() - chrono::Days::new(1)
+}
+
 pub(crate) fn timestamp_unix_s() -> i64 {
     Utc::now().timestamp()
 }
 
+/// Monotonic microseconds since boot; unaffected by NTP adjustments.
+pub(crate) fn uptime_us() -> i64 {
+    unsafe { esp_timer_get_time() }
+}
+
 pub(crate) fn get_uptime_string() -> String {
-    let micros = unsafe { esp_timer_get_time() };
+    let micros = uptime_us();
     let seconds = micros / 1_000_000;
     let millis = (micros % 1_000_000) / 1_000;
     format!("[{:>4}.{:03}s]", seconds, millis)
@@ -88,27 +319,67 @@ pub(crate) fn get_uptime_string() -> String {
 
 pub(crate) fn get_formatted_timestamp() -> String {
     let now = get_current_time_in_timezone();
-    now.format(TIMESTAMP_PATTERN).to_string()
+
+    if LOG_TIMEZONE_OFFSET_PER_LINE {
+        format!("{} {}", now.format(TIMESTAMP_PATTERN), now.format("%:z"))
+    } else {
+        now.format(TIMESTAMP_PATTERN).to_string()
+    }
 }
 
+/// Current abbreviation and UTC offset of the display timezone, e.g. `CEST, UTC+02:00`.
+/// Both depend on the date (DST), so this is only meaningful once the clock is synced.
+pub(crate) fn timezone_offset_description() -> String {
+    get_current_time_in_timezone()
+        .format("%Z, UTC%:z")
+        .to_string()
+}
+
+#[cfg(feature = "ntp")]
 pub(crate) fn effective_timezone_name() -> &'static str {
     cached_timezone().name()
 }
 
-fn cached_timezone() -> &'static Tz {
-    static TZ: OnceLock<Tz> = OnceLock::new();
-    TZ.get_or_init(|| {
-        TIMEZONE.parse().unwrap_or_else(|e| {
-            warn!("‼️ TZ parsing failed: {}, falling back to UTC", e);
-            chrono_tz::UTC
-        })
+#[cfg(not(feature = "ntp"))]
+pub(crate) fn effective_timezone_name() -> &'static str {
+    "UTC"
+}
+
+/// Switches every local-time computation to `timezone` from now on.
+#[cfg(feature = "ntp")]
+pub(crate) fn set_timezone(timezone: Tz) {
+    ACTIVE_TIMEZONE.lock(|active| active.set(Some(timezone)));
+    TIMEZONE_CHANGED.signal(());
+}
+
+#[cfg(feature = "ntp")]
+fn cached_timezone() -> Tz {
+    if let Some(timezone) = ACTIVE_TIMEZONE.lock(Cell::get) {
+        return timezone;
+    }
+
+    // Parsed (and warned about) outside the lock; a concurrent override set meanwhile wins
+    let compiled = TIMEZONE.parse().unwrap_or_else(|e| {
+        warn!("‼️ TZ parsing failed: {}, falling back to UTC", e);
+        chrono_tz::UTC
+    });
+    ACTIVE_TIMEZONE.lock(|active| {
+        let timezone = active.get().unwrap_or(compiled);
+        active.set(Some(timezone));
+        timezone
     })
 }
 
+#[cfg(not(feature = "ntp"))]
+fn cached_timezone() -> Tz {
+    Utc
+}
+
 fn get_current_time_in_timezone() -> DateTime<Tz> {
-    Utc::now().with_timezone(cached_timezone())
+    Utc::now().with_timezone(&cached_timezone())
 }
 
+#[cfg(feature = "ntp")]
 fn mark_time_synced() {
     if !TIME_SYNCED.swap(true, Ordering::Relaxed) {
         TIME_SYNCED_SIGNAL.signal(())
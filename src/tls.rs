@@ -0,0 +1,82 @@
+use crate::config::is_mtls_enabled;
+use anyhow::{Result, anyhow};
+use esp_idf_svc::tls::X509;
+use std::ffi::CStr;
+
+// Written by build.rs from the paths in `.env`; nul-terminated (and nul-only when not configured)
+// because mbedTLS expects PEM buffers as C strings.
+static CLIENT_CERTIFICATE_PEM: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/client_certificate.pem"));
+static CLIENT_PRIVATE_KEY_PEM: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/client_private_key.pem"));
+static PINNED_SERVER_CERTIFICATE_PEM: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/pinned_server_certificate.pem"));
+
+/// Client certificate and private key for mutual TLS, or `None` when mTLS is disabled.
+///
+/// Both are checked up front, so a missing or malformed file fails the HTTP client init with a
+/// clear message instead of an opaque handshake error later on.
+pub(crate) fn client_identity() -> Result<Option<(X509<'static>, X509<'static>)>> {
+    if !is_mtls_enabled() {
+        return Ok(None);
+    }
+
+    let certificate = pem(
+        CLIENT_CERTIFICATE_PEM,
+        "mTLS client certificate",
+        "CERTIFICATE",
+    )?;
+    let private_key = pem(
+        CLIENT_PRIVATE_KEY_PEM,
+        "mTLS client private key",
+        "PRIVATE KEY",
+    )?;
+
+    Ok(Some((certificate, private_key)))
+}
+
+/// The pinned server certificate, or `None` (trust the CA bundle) when none was embedded.
+///
+/// When present it is the only trust anchor: pin either the server's self-signed certificate or
+/// the private CA that issued it.
+pub(crate) fn pinned_server_certificate() -> Result<Option<X509<'static>>> {
+    if is_pem_empty(PINNED_SERVER_CERTIFICATE_PEM) {
+        return Ok(None);
+    }
+
+    pem(
+        PINNED_SERVER_CERTIFICATE_PEM,
+        "pinned server certificate",
+        "CERTIFICATE",
+    )
+    .map(Some)
+}
+
+fn is_pem_empty(bytes: &[u8]) -> bool {
+    bytes.iter().all(|b| *b == 0 || b.is_ascii_whitespace())
+}
+
+fn pem(bytes: &'static [u8], what: &str, label: &str) -> Result<X509<'static>> {
+    let cstr = CStr::from_bytes_with_nul(bytes)
+        .map_err(|_| anyhow!("‼️🔐 {} is not a valid PEM text", what))?;
+    let text = cstr
+        .to_str()
+        .map_err(|_| anyhow!("‼️🔐 {} is not a valid PEM text", what))?;
+
+    if text.trim().is_empty() {
+        return Err(anyhow!(
+            "‼️🔐 No {} was embedded, check the .env paths",
+            what
+        ));
+    }
+
+    // Accepts e.g. `BEGIN CERTIFICATE`, `BEGIN PRIVATE KEY`, `BEGIN EC PRIVATE KEY`
+    let has_block = text
+        .lines()
+        .any(|line| line.starts_with("-----BEGIN ") && line.ends_with(&format!("{}-----", label)));
+    if !has_block {
+        return Err(anyhow!("‼️🔐 {} has no PEM `{}` block", what, label));
+    }
+
+    Ok(X509::pem(cstr))
+}
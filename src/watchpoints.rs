@@ -0,0 +1,71 @@
+use crate::config::{Threshold, WATCHPOINTS, WatchField};
+use crate::models::WeatherData;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use log::{info, warn};
+use smog_core::watchpoint::Hysteresis;
+use std::cell::RefCell;
+
+/// The state of each `WATCHPOINTS` entry, in the same order; built on the first reading.
+static STATES: Mutex<CriticalSectionRawMutex, RefCell<Option<Vec<Hysteresis>>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Reading observer: logs a warning when a watched field enters its alert band and a note when it
+/// leaves it, once per transition. A field missing from the reading leaves its state unchanged.
+pub(crate) fn check(data: &WeatherData) {
+    STATES.lock(|states| {
+        let mut states = states.borrow_mut();
+        let states = states.get_or_insert_with(|| {
+            WATCHPOINTS
+                .iter()
+                .map(|watch| Hysteresis::new(watch.threshold, watch.hysteresis))
+                .collect()
+        });
+
+        for (watch, state) in WATCHPOINTS.iter().zip(states.iter_mut()) {
+            let Some(value) = field_value(watch.field, data) else {
+                continue;
+            };
+
+            let (limit, direction) = match watch.threshold {
+                Threshold::Above(limit) => (limit, "above"),
+                Threshold::Below(limit) => (limit, "below"),
+            };
+            match state.update(value) {
+                Some(true) => warn!(
+                    "👀 Watchpoint: {} {:.1} is {} {:.1}",
+                    field_name(watch.field),
+                    value,
+                    direction,
+                    limit
+                ),
+                Some(false) => info!(
+                    "👀 Watchpoint: {} back to {:.1}, no longer {} {:.1}",
+                    field_name(watch.field),
+                    value,
+                    direction,
+                    limit
+                ),
+                None => {}
+            }
+        }
+    });
+}
+
+fn field_value(field: WatchField, data: &WeatherData) -> Option<f32> {
+    match field {
+        WatchField::Temperature => data.temperature,
+        WatchField::Humidity => data.humidity,
+        WatchField::Pressure => data.pressure,
+        WatchField::Voc => data.voc.map(f32::from),
+    }
+}
+
+fn field_name(field: WatchField) -> &'static str {
+    match field {
+        WatchField::Temperature => "Temperature",
+        WatchField::Humidity => "Humidity",
+        WatchField::Pressure => "Pressure",
+        WatchField::Voc => "VOC index",
+    }
+}
@@ -0,0 +1,99 @@
+use crate::config::{ANEMOMETER_KMH_PER_HZ, RAIN_GAUGE_MM_PER_TIP, REED_SWITCH_DEBOUNCE_US};
+use anyhow::Context;
+use embassy_time::{Duration, Instant, Timer};
+use esp_idf_svc::hal::gpio::{AnyIOPin, IOPin, Input, PinDriver, Pull};
+use log::warn;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Counted by the reed switch tasks, drained by the gauge in the sensor task.
+static WIND_PULSES: AtomicU32 = AtomicU32::new(0);
+static RAIN_TIPS: AtomicU32 = AtomicU32::new(0);
+
+/// Anemometer + tipping-bucket rain gauge, both read as reed-switch pulses on GPIO interrupts.
+///
+/// The pulses are counted by one `ReedSwitch` per input, each run by `tasks::reed_switch_task`.
+pub(crate) struct WindRainGauge {
+    last_wind_read: Instant,
+    rain_tips: u32,
+}
+
+impl WindRainGauge {
+    /// The gauge and the two reed switches to run, anemometer first.
+    pub(crate) fn new(
+        anemometer_pin: impl IOPin,
+        rain_gauge_pin: impl IOPin,
+    ) -> anyhow::Result<(Self, [ReedSwitch; 2])> {
+        let anemometer = ReedSwitch::new(anemometer_pin, &WIND_PULSES)
+            .context("‼️ Failed to init anemometer GPIO")?;
+        let rain_gauge = ReedSwitch::new(rain_gauge_pin, &RAIN_TIPS)
+            .context("‼️ Failed to init rain gauge GPIO")?;
+
+        let gauge = Self {
+            last_wind_read: Instant::now(),
+            rain_tips: 0,
+        };
+        Ok((gauge, [anemometer, rain_gauge]))
+    }
+
+    /// Average wind speed since the previous call, derived from the anemometer pulse rate.
+    pub(crate) fn wind_speed_kmh(&mut self) -> f32 {
+        let pulses = WIND_PULSES.swap(0, Ordering::Relaxed);
+        let elapsed_ms = self.last_wind_read.elapsed().as_millis();
+        self.last_wind_read = Instant::now();
+
+        if elapsed_ms == 0 {
+            return 0.0;
+        }
+
+        let pulses_per_second = pulses as f32 * 1000.0 / elapsed_ms as f32;
+        pulses_per_second * ANEMOMETER_KMH_PER_HZ
+    }
+
+    /// Rain since `reset_rain`. The tips are moved out of the shared counter here, so a tip that
+    /// lands between this reading and the reset is kept for the next interval instead of lost.
+    pub(crate) fn rain_mm_since_reset(&mut self) -> f32 {
+        self.rain_tips += RAIN_TIPS.swap(0, Ordering::Relaxed);
+        self.rain_tips as f32 * RAIN_GAUGE_MM_PER_TIP
+    }
+
+    pub(crate) fn reset_rain(&mut self) {
+        self.rain_tips = 0;
+    }
+}
+
+/// One reed switch input, counting its debounced falling edges into a shared counter.
+pub(crate) struct ReedSwitch {
+    driver: PinDriver<'static, AnyIOPin, Input>,
+    counter: &'static AtomicU32,
+}
+
+impl ReedSwitch {
+    fn new(pin: impl IOPin, counter: &'static AtomicU32) -> anyhow::Result<Self> {
+        let mut driver = PinDriver::input(pin.downgrade())?;
+        driver.set_pull(Pull::Up)?;
+
+        Ok(Self { driver, counter })
+    }
+
+    /// Counts edges forever. esp-idf-hal disarms the interrupt after each notification; waiting
+    /// for the next edge re-arms it, from task context rather than inside the ISR.
+    pub(crate) async fn run(mut self) {
+        let debounce = Duration::from_micros(REED_SWITCH_DEBOUNCE_US.into());
+        let mut last_edge: Option<Instant> = None;
+
+        loop {
+            if let Err(e) = self.driver.wait_for_falling_edge().await {
+                warn!("‼️ Reed switch interrupt failed: {:?}", e);
+                Timer::after_secs(1).await;
+                continue;
+            }
+
+            // Reed switches bounce; ignore edges that follow the previous one too closely.
+            let now = Instant::now();
+            if last_edge.is_none_or(|last| now - last >= debounce) {
+                last_edge = Some(now);
+                self.counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}